@@ -101,16 +101,22 @@ impl Deref for Rfc3339OffsetDateTime {
 #[serde(rename_all = "PascalCase")]
 pub struct AssumeRoleWithWebIdentityResponse {
     pub assume_role_with_web_identity_result: AssumeRoleWithWebIdentityResult,
+    #[serde(default)]
     pub response_metadata: ResponseMetadata,
 }
 
+// STS compatible services like MinIO leave out some of the fields AWS returns
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct AssumeRoleWithWebIdentityResult {
+    #[serde(default)]
     pub subject_from_web_identity_token: String,
+    #[serde(default)]
     pub audience: String,
+    #[serde(default)]
     pub assumed_role_user: AssumedRoleUser,
     pub credentials: StsResponseCredentials,
+    #[serde(default)]
     pub provider: String,
 }
 
@@ -123,16 +129,31 @@ pub struct StsResponseCredentials {
     pub access_key_id: String,
 }
 
-#[derive(Deserialize, Debug)]
+impl From<StsResponseCredentials> for Credentials {
+    fn from(credentials: StsResponseCredentials) -> Self {
+        Credentials {
+            access_key: Some(credentials.access_key_id),
+            secret_key: Some(credentials.secret_access_key),
+            security_token: None,
+            session_token: Some(credentials.session_token),
+            expiration: Some(credentials.expiration),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct AssumedRoleUser {
+    #[serde(default)]
     pub arn: String,
+    #[serde(default)]
     pub assumed_role_id: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct ResponseMetadata {
+    #[serde(default)]
     pub request_id: String,
 }
 
@@ -170,7 +191,7 @@ pub fn set_request_timeout(timeout: Option<Duration>) -> Option<Duration> {
 }
 
 #[cfg(feature = "http-credentials")]
-fn apply_timeout(builder: attohttpc::RequestBuilder) -> attohttpc::RequestBuilder {
+pub(crate) fn apply_timeout(builder: attohttpc::RequestBuilder) -> attohttpc::RequestBuilder {
     let timeout_ms = REQUEST_TIMEOUT_MS.load(Ordering::Relaxed);
     if timeout_ms > 0 {
         return builder.timeout(Duration::from_millis(timeout_ms as u64));
@@ -227,33 +248,10 @@ impl Credentials {
             quick_xml::de::from_str::<AssumeRoleWithWebIdentityResponse>(&response.text()?)?;
         // assert!(quick_xml::de::from_str::<AssumeRoleWithWebIdentityResponse>(&response.text()?).unwrap());
 
-        Ok(Credentials {
-            access_key: Some(
-                serde_response
-                    .assume_role_with_web_identity_result
-                    .credentials
-                    .access_key_id,
-            ),
-            secret_key: Some(
-                serde_response
-                    .assume_role_with_web_identity_result
-                    .credentials
-                    .secret_access_key,
-            ),
-            security_token: None,
-            session_token: Some(
-                serde_response
-                    .assume_role_with_web_identity_result
-                    .credentials
-                    .session_token,
-            ),
-            expiration: Some(
-                serde_response
-                    .assume_role_with_web_identity_result
-                    .credentials
-                    .expiration,
-            ),
-        })
+        Ok(serde_response
+            .assume_role_with_web_identity_result
+            .credentials
+            .into())
     }
 
     #[allow(clippy::should_implement_trait)]
@@ -410,7 +408,16 @@ impl Credentials {
     pub fn from_profile(section: Option<&str>) -> Result<Credentials, CredentialsError> {
         let home_dir = home::home_dir().ok_or(CredentialsError::HomeDir)?;
         let profile = format!("{}/.aws/credentials", home_dir.display());
-        let conf = Ini::load_from_file(profile)?;
+        Credentials::from_profile_file(profile, section)
+    }
+
+    /// Load the `section` profile, `default` if `None`, from the credentials file at `path`
+    /// rather than `~/.aws/credentials`, e.g. one mounted from a secret store.
+    pub fn from_profile_file(
+        path: impl AsRef<std::path::Path>,
+        section: Option<&str>,
+    ) -> Result<Credentials, CredentialsError> {
+        let conf = Ini::load_from_file(path)?;
         let section = section.unwrap_or("default");
         let data = conf
             .section(Some(section))
@@ -485,6 +492,25 @@ fn test_instance_metadata_creds_deserialization() {
     .unwrap();
 }
 
+#[cfg(test)]
+#[test]
+fn test_credentials_from_profile_file() {
+    let path = std::env::temp_dir().join("aws-creds-test-credentials");
+    std::fs::write(
+        &path,
+        "[default]\naws_access_key_id = AKIADEFAULT\naws_secret_access_key = default-secret\n\
+         [rotated]\naws_access_key_id = AKIAROTATED\naws_secret_access_key = rotated-secret\n",
+    )
+    .unwrap();
+
+    let credentials = Credentials::from_profile_file(&path, None).unwrap();
+    assert_eq!(credentials.access_key.as_deref(), Some("AKIADEFAULT"));
+    let credentials = Credentials::from_profile_file(&path, Some("rotated")).unwrap();
+    assert_eq!(credentials.secret_key.as_deref(), Some("rotated-secret"));
+
+    std::fs::remove_file(path).unwrap_or(());
+}
+
 #[cfg(test)]
 #[ignore]
 #[test]
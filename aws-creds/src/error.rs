@@ -31,4 +31,8 @@ pub enum CredentialsError {
     NoCredentials,
     #[error("unexpected status code: {0}")]
     UnexpectedStatusCode(u16),
+    #[error("Web identity token is not a JWT")]
+    InvalidWebIdentityToken,
+    #[error("Web identity token is not issued for {0}, but for {1}")]
+    AudienceMismatch(String, String),
 }
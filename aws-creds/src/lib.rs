@@ -4,6 +4,10 @@
 mod credentials;
 pub use credentials::*;
 pub mod error;
+#[cfg(feature = "http-credentials")]
+mod web_identity;
+#[cfg(feature = "http-credentials")]
+pub use web_identity::*;
 
 // Reexport for e.g. users who need to build Credentials
 pub use time;
@@ -0,0 +1,302 @@
+use crate::credentials::{apply_timeout, AssumeRoleWithWebIdentityResponse};
+use crate::error::CredentialsError;
+use crate::Credentials;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use log::debug;
+use serde::Deserialize;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+use url::Url;
+
+/// Token file read unless `WebIdentityProvider::token_file` is set, a common mount path for
+/// projected service account tokens
+pub const SERVICE_ACCOUNT_TOKEN_FILE: &str = "/var/run/secrets/tokens/sts-token";
+
+/// Credentials are refreshed this long before they expire
+const REFRESH_MARGIN: time::Duration = time::Duration::minutes(5);
+
+/// Exchanges a Kubernetes projected service account token for credentials with
+/// `AssumeRoleWithWebIdentity`, against STS compatible endpoints such as MinIO's.
+///
+/// Kubernetes rotates projected tokens, so the token file is read again on every exchange.
+/// With an audience set, the token's `aud` claim is checked before it is sent, a token
+/// projected for another audience fails here rather than with an opaque STS rejection.
+///
+/// # Example
+///
+/// ```no_run
+/// use awscreds::WebIdentityProvider;
+///
+/// let provider = WebIdentityProvider::new("https://minio.minio.svc:9000")
+///     .unwrap()
+///     .token_file("/var/run/secrets/tokens/minio-token")
+///     .audience("minio");
+/// let mut credentials = provider.credentials().unwrap();
+///
+/// // Later on, exchanges the token again once the credentials are about to expire
+/// provider.refresh(&mut credentials).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct WebIdentityProvider {
+    endpoint: Url,
+    token_file: PathBuf,
+    audience: Option<String>,
+    role_arn: Option<String>,
+    session_name: String,
+    duration_seconds: Option<u32>,
+    clock: fn() -> OffsetDateTime,
+}
+
+impl WebIdentityProvider {
+    pub fn new(endpoint: &str) -> Result<WebIdentityProvider, CredentialsError> {
+        Ok(WebIdentityProvider {
+            endpoint: Url::parse(endpoint)?,
+            token_file: PathBuf::from(SERVICE_ACCOUNT_TOKEN_FILE),
+            audience: None,
+            role_arn: None,
+            session_name: "aws-creds".to_string(),
+            duration_seconds: None,
+            clock: OffsetDateTime::now_utc,
+        })
+    }
+
+    /// Provider for `AWS_WEB_IDENTITY_TOKEN_FILE` and the optional `AWS_ROLE_ARN`, exchanged
+    /// against `endpoint`
+    pub fn from_env(endpoint: &str) -> Result<WebIdentityProvider, CredentialsError> {
+        let mut provider = WebIdentityProvider::new(endpoint)?
+            .token_file(std::env::var("AWS_WEB_IDENTITY_TOKEN_FILE")?);
+        provider.role_arn = std::env::var("AWS_ROLE_ARN").ok();
+        Ok(provider)
+    }
+
+    pub fn token_file(mut self, token_file: impl Into<PathBuf>) -> Self {
+        self.token_file = token_file.into();
+        self
+    }
+
+    /// Audience the token has to be issued for, as set on the projected volume
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Role to assume, MinIO picks the policy from the token's claims when it is left out
+    pub fn role_arn(mut self, role_arn: impl Into<String>) -> Self {
+        self.role_arn = Some(role_arn.into());
+        self
+    }
+
+    pub fn session_name(mut self, session_name: impl Into<String>) -> Self {
+        self.session_name = session_name.into();
+        self
+    }
+
+    pub fn duration_seconds(mut self, duration_seconds: u32) -> Self {
+        self.duration_seconds = Some(duration_seconds);
+        self
+    }
+
+    /// Source of the current time `refresh` checks the expiration against, the system clock
+    /// unless set
+    pub fn clock(mut self, clock: fn() -> OffsetDateTime) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Exchange the current token for credentials
+    pub fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        let token = std::fs::read_to_string(&self.token_file)?;
+        let token = token.trim();
+        if let Some(audience) = &self.audience {
+            check_audience(token, audience)?;
+        }
+
+        // The token is a bearer credential, so it goes in the body rather than in the URL where
+        // proxies and access logs would keep it
+        let response = apply_timeout(attohttpc::post(self.endpoint.as_str()))
+            .header(
+                attohttpc::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .text(self.form_body(token))
+            .send()?;
+        if !response.is_success() {
+            return Err(CredentialsError::UnexpectedStatusCode(
+                response.status().as_u16(),
+            ));
+        }
+        let response =
+            quick_xml::de::from_str::<AssumeRoleWithWebIdentityResponse>(&response.text()?)?;
+        Ok(response
+            .assume_role_with_web_identity_result
+            .credentials
+            .into())
+    }
+
+    /// Exchange the token again if `credentials` expire within five minutes of the provider's
+    /// clock
+    pub fn refresh(&self, credentials: &mut Credentials) -> Result<(), CredentialsError> {
+        if let Some(expiration) = credentials.expiration {
+            if expiration.0 - REFRESH_MARGIN <= (self.clock)() {
+                debug!("Refreshing web identity credentials!");
+                *credentials = self.credentials()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `AssumeRoleWithWebIdentity` parameters for `token`, form encoded
+    fn form_body(&self, token: &str) -> String {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("Action", "AssumeRoleWithWebIdentity")
+            .append_pair("Version", "2011-06-15")
+            .append_pair("RoleSessionName", &self.session_name)
+            .append_pair("WebIdentityToken", token);
+        if let Some(role_arn) = &self.role_arn {
+            form.append_pair("RoleArn", role_arn);
+        }
+        if let Some(duration_seconds) = self.duration_seconds {
+            form.append_pair("DurationSeconds", &duration_seconds.to_string());
+        }
+        form.finish()
+    }
+}
+
+/// `aud` is either a single audience or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    aud: Option<Audience>,
+}
+
+/// Check the unverified `aud` claim of `token`, verifying the signature is up to STS
+fn check_audience(token: &str, audience: &str) -> Result<(), CredentialsError> {
+    let invalid = || CredentialsError::InvalidWebIdentityToken;
+    let payload = token.split('.').nth(1).ok_or_else(invalid)?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| invalid())?;
+    let claims: Claims = serde_json::from_slice(&payload).map_err(|_| invalid())?;
+    let found = match claims.aud {
+        Some(Audience::One(aud)) => vec![aud],
+        Some(Audience::Many(aud)) => aud,
+        None => Vec::new(),
+    };
+    if found.iter().any(|aud| aud == audience) {
+        Ok(())
+    } else {
+        Err(CredentialsError::AudienceMismatch(
+            audience.to_string(),
+            found.join(","),
+        ))
+    }
+}
+
+#[cfg(test)]
+fn token(claims: &str) -> String {
+    format!(
+        "eyJhbGciOiJSUzI1NiJ9.{}.c2lnbmF0dXJl",
+        URL_SAFE_NO_PAD.encode(claims)
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn test_check_audience() {
+    assert!(check_audience(&token(r#"{"aud":"minio"}"#), "minio").is_ok());
+    assert!(check_audience(&token(r#"{"aud":["sts.amazonaws.com","minio"]}"#), "minio").is_ok());
+    assert!(matches!(
+        check_audience(&token(r#"{"aud":["sts.amazonaws.com"]}"#), "minio"),
+        Err(CredentialsError::AudienceMismatch(expected, found))
+            if expected == "minio" && found == "sts.amazonaws.com"
+    ));
+    assert!(matches!(
+        check_audience("not-a-jwt", "minio"),
+        Err(CredentialsError::InvalidWebIdentityToken)
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn test_token_is_sent_in_the_form_body() {
+    let provider = WebIdentityProvider::new("https://minio.minio.svc:9000")
+        .unwrap()
+        .role_arn("arn:minio:iam:::role/worker")
+        .duration_seconds(900);
+    assert_eq!(
+        provider.form_body("eyJ.a+b/c="),
+        "Action=AssumeRoleWithWebIdentity&Version=2011-06-15&RoleSessionName=aws-creds\
+         &WebIdentityToken=eyJ.a%2Bb%2Fc%3D&RoleArn=arn%3Aminio%3Aiam%3A%3A%3Arole%2Fworker\
+         &DurationSeconds=900"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_refresh_checks_the_provider_clock() {
+    let provider = WebIdentityProvider::new("https://minio.minio.svc:9000")
+        .unwrap()
+        .token_file("/nonexistent/sts-token")
+        .clock(|| OffsetDateTime::from_unix_timestamp(1_709_287_200).unwrap());
+    let mut credentials = Credentials::anonymous().unwrap();
+
+    let now = OffsetDateTime::from_unix_timestamp(1_709_287_200).unwrap();
+
+    // Six minutes left on the provider's clock, nothing to exchange
+    credentials.expiration = Some(crate::Rfc3339OffsetDateTime(
+        now + time::Duration::minutes(6),
+    ));
+    provider.refresh(&mut credentials).unwrap();
+
+    // Four minutes left, the exchange is attempted and fails on the missing token file
+    credentials.expiration = Some(crate::Rfc3339OffsetDateTime(
+        now + time::Duration::minutes(4),
+    ));
+    assert!(matches!(
+        provider.refresh(&mut credentials),
+        Err(CredentialsError::Io(_))
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn test_minio_web_identity_response_deserialization() {
+    // MinIO leaves out `Audience` and `Provider`
+    let response = quick_xml::de::from_str::<AssumeRoleWithWebIdentityResponse>(
+        r#"
+        <AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+          <AssumeRoleWithWebIdentityResult>
+            <AssumedRoleUser>
+              <Arn></Arn>
+              <AssumeRoleId></AssumeRoleId>
+            </AssumedRoleUser>
+            <Credentials>
+              <AccessKeyId>Y4RJU1RNFGK48LGO9I2S</AccessKeyId>
+              <SecretAccessKey>sYLRKS1Z7hSjluf6gEbb9066hnx315wHTiACPAjg</SecretAccessKey>
+              <Expiration>2019-08-08T20:26:12Z</Expiration>
+              <SessionToken>eyJhbGciOiJIUzUxMiIsInR5cCI6IkpXVCJ9</SessionToken>
+            </Credentials>
+            <SubjectFromWebIdentityToken>system:serviceaccount:apps:worker</SubjectFromWebIdentityToken>
+          </AssumeRoleWithWebIdentityResult>
+          <ResponseMetadata></ResponseMetadata>
+        </AssumeRoleWithWebIdentityResponse>
+        "#,
+    )
+    .unwrap();
+    let credentials: Credentials = response
+        .assume_role_with_web_identity_result
+        .credentials
+        .into();
+    assert_eq!(
+        credentials.access_key.as_deref(),
+        Some("Y4RJU1RNFGK48LGO9I2S")
+    );
+    assert_eq!(credentials.expiration.unwrap().unix_timestamp(), 1565295972);
+}
@@ -0,0 +1,90 @@
+// cargo run --example mock --features mock-server
+//
+// Runs against the in-memory `s3::mock_server`, no S3 endpoint or credentials needed, which is
+// how CI runs it.
+
+#[cfg(feature = "mock-server")]
+use futures::TryStreamExt;
+#[cfg(feature = "mock-server")]
+use s3::error::S3Error;
+#[cfg(feature = "mock-server")]
+use s3::mock_server::MockServer;
+#[cfg(feature = "mock-server")]
+use s3::post_policy::{PostPolicy, PostPolicyField, PostPolicyValue};
+#[cfg(feature = "mock-server")]
+use s3::serde_types::ListObjectsOptions;
+#[cfg(feature = "mock-server")]
+use s3::utils::PageSize;
+
+#[cfg(not(feature = "mock-server"))]
+fn main() {}
+
+#[cfg(feature = "mock-server")]
+#[tokio::main]
+async fn main() -> Result<(), S3Error> {
+    let server = MockServer::start().await?;
+    // Retry configuration for this bucket alone, the process wide `set_retries` stays as is
+    let bucket = server
+        .bucket("rust-s3")?
+        .with_retries(2)
+        .with_request_trace(64);
+
+    // Multipart upload, anything over one 8 MiB part is uploaded in parts
+    let large = vec![42u8; 20 * 1024 * 1024];
+    let response = bucket
+        .put_object_stream(&mut large.as_slice(), "/large.bin")
+        .await?;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(bucket.get_object("/large.bin").await?.as_slice(), large);
+
+    // Presigned POST form upload, as a browser would send it
+    let policy = PostPolicy::new(3600).condition(
+        PostPolicyField::Key,
+        PostPolicyValue::Exact("form.txt".into()),
+    )?;
+    let presigned_post = bucket.presign_post(policy).await?;
+    let boundary = "rust-s3-example-boundary";
+    let mut form = String::new();
+    for (name, value) in &presigned_post.fields {
+        form.push_str(&format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        ));
+    }
+    form.push_str(&format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"form.txt\"\r\n\r\nposted\r\n--{boundary}--\r\n"
+    ));
+    let response = bucket
+        .http_client()
+        .post(&presigned_post.url)
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(form)
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+    assert_eq!(bucket.get_object("/form.txt").await?.as_slice(), b"posted");
+
+    // List streaming, a page at a time
+    let options = ListObjectsOptions::new().max_keys(PageSize::new(1)?);
+    let mut pages = Box::pin(bucket.list_stream(String::new(), options));
+    let mut keys = Vec::new();
+    while let Some(page) = pages.try_next().await? {
+        keys.extend(page.contents.into_iter().map(|object| object.key));
+    }
+    keys.sort();
+    assert_eq!(keys, vec!["form.txt", "large.bin"]);
+
+    // Retries, the server throttles the next request and the bucket sends it again
+    server.fail_next(1, 503);
+    bucket.put_object("/retried.txt", b"retried").await?;
+    assert_eq!(bucket.retry_count(), 1);
+
+    println!(
+        "{} requests, {} retried",
+        bucket.recent_requests().len(),
+        bucket.retry_count()
+    );
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! A 1 GiB `put_object_stream` upload against the in-process mock S3,
+//! `cargo bench --bench multipart --features mock-server`
+//!
+//! The mock throws the uploaded content away, what's measured is the client side of the
+//! multipart pipeline: reading parts, signing and sending them.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use s3::mock_server::MockServer;
+use tokio::io::AsyncReadExt;
+use tokio::runtime::Runtime;
+
+const OBJECT_SIZE: u64 = 1024 * 1024 * 1024;
+
+fn multipart_benchmarks(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start()).unwrap();
+    server.discard_content();
+    let bucket = server.bucket("rust-s3").unwrap();
+
+    let mut group = c.benchmark_group("multipart");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(OBJECT_SIZE));
+    group.bench_function("put_object_stream_1gib", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let mut reader = tokio::io::repeat(0).take(OBJECT_SIZE);
+            bucket
+                .put_object_stream(&mut reader, "bench/object")
+                .await
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, multipart_benchmarks);
+criterion_main!(benches);
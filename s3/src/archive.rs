@@ -0,0 +1,437 @@
+//! Reading the files of `.tar`, `.tar.gz` and `.zip` objects as they are downloaded, and
+//! packing files into a `.tar` or `.tar.gz` object as it is uploaded.
+//!
+//! [`ArchiveReader`] unpacks the body of `Bucket::get_object_archive` as it arrives, nothing is
+//! buffered beyond the current entry or written to disk. Entries come in archive order and each
+//! one is an `AsyncRead` over the file's content, an entry that isn't read to the end is skipped
+//! when asking for the next one. Only regular files are returned, directories, links and other
+//! special entries are passed over.
+//!
+//! Zip archives are read from their local file headers instead of the central directory at the
+//! end of the file, so an archive whose entries were later replaced or removed may show them
+//! anyway. Stored (uncompressed) zip entries written with a trailing data descriptor can't be
+//! streamed at all and fail with [`ArchiveError::Zip`].
+//!
+//! `Bucket::put_object_archive` goes the other way. A tar header carries the size of its file,
+//! so each file comes with its size and is streamed into the archive behind its header, a file
+//! whose reader yields more or fewer bytes than that fails the upload. Nothing is held beyond
+//! the part being uploaded.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_compression::tokio::bufread::GzipDecoder;
+use async_zip::base::read::stream::{Reading, Ready, ZipFileReader};
+use async_zip::base::read::WithEntry;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader, ReadBuf};
+use tokio_util::compat::Compat;
+
+use crate::error::S3Error;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ArchiveError {
+    #[error("zip: {0}")]
+    Zip(#[from] async_zip::error::ZipError),
+}
+
+/// Container format of an archive object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The format named by the extension of `key`, `.tar`, `.tar.gz`, `.tgz` or `.zip`
+    ///
+    /// ```
+    /// use s3::archive::ArchiveFormat;
+    ///
+    /// assert_eq!(ArchiveFormat::from_key("exports/2024-03-01.TGZ"), Some(ArchiveFormat::TarGz));
+    /// assert_eq!(ArchiveFormat::from_key("exports/2024-03-01.csv"), None);
+    /// ```
+    pub fn from_key(key: &str) -> Option<ArchiveFormat> {
+        let key = key.to_ascii_lowercase();
+        if key.ends_with(".tar.gz") || key.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if key.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if key.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+type Body = Pin<Box<dyn AsyncRead + Send>>;
+
+type ZipReady = ZipFileReader<Ready<Compat<BufReader<Body>>>>;
+type ZipReading = ZipFileReader<Reading<'static, Compat<BufReader<Body>>, WithEntry<'static>>>;
+
+enum ZipState {
+    Ready(ZipReady),
+    Reading(Box<ZipReading>),
+    Done,
+}
+
+enum Inner {
+    Tar(Box<tokio_tar::Entries<Body>>),
+    Zip(ZipState),
+}
+
+/// Files of an archive object, see `Bucket::get_object_archive`
+///
+/// ```no_run
+/// use s3::archive::ArchiveFormat;
+/// use s3::bucket::Bucket;
+/// use s3::creds::Credentials;
+/// use anyhow::Result;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let bucket = Bucket::new("rust-s3-test", "us-east-1".parse()?, Credentials::default()?)?;
+///
+/// let mut archive = bucket
+///     .get_object_archive("/exports/2024-03-01.tar.gz", ArchiveFormat::TarGz)
+///     .await?;
+/// while let Some(mut entry) = archive.next_entry().await? {
+///     let mut rows = Vec::new();
+///     tokio::io::copy(&mut entry, &mut rows).await?;
+///     println!("{}: {} bytes", entry.path(), rows.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ArchiveReader {
+    inner: Inner,
+}
+
+impl ArchiveReader {
+    /// Read the archive in `body`, an object body or any other reader
+    pub fn new<R>(body: R, format: ArchiveFormat) -> Result<ArchiveReader, S3Error>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let body: Body = Box::pin(body);
+        let inner = match format {
+            ArchiveFormat::Tar => Inner::Tar(Box::new(tokio_tar::Archive::new(body).entries()?)),
+            ArchiveFormat::TarGz => {
+                let mut decoder = GzipDecoder::new(BufReader::new(body));
+                // `tar czf` on several inputs, or concatenated `.gz` files, give several members
+                decoder.multiple_members(true);
+                let body: Body = Box::pin(decoder);
+                Inner::Tar(Box::new(tokio_tar::Archive::new(body).entries()?))
+            }
+            ArchiveFormat::Zip => Inner::Zip(ZipState::Ready(ZipFileReader::with_tokio(
+                BufReader::new(body),
+            ))),
+        };
+        Ok(ArchiveReader { inner })
+    }
+
+    /// The next regular file of the archive, `None` once the archive is exhausted. What is
+    /// left of the previous entry is skipped.
+    pub async fn next_entry(&mut self) -> Result<Option<ArchiveEntry<'_>>, S3Error> {
+        match &mut self.inner {
+            Inner::Tar(entries) => {
+                while let Some(entry) = entries.next().await {
+                    let entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let path = entry.path()?.to_string_lossy().into_owned();
+                    return Ok(Some(ArchiveEntry {
+                        path,
+                        reader: EntryReader::Tar(Box::new(entry)),
+                    }));
+                }
+                Ok(None)
+            }
+            Inner::Zip(state) => {
+                let mut ready = match std::mem::replace(state, ZipState::Done) {
+                    ZipState::Ready(ready) => ready,
+                    ZipState::Reading(reading) => {
+                        reading.skip().await.map_err(ArchiveError::from)?
+                    }
+                    ZipState::Done => return Ok(None),
+                };
+                loop {
+                    let Some(reading) =
+                        ready.next_with_entry().await.map_err(ArchiveError::from)?
+                    else {
+                        return Ok(None);
+                    };
+                    let path = reading
+                        .reader()
+                        .entry()
+                        .filename()
+                        .as_str()
+                        .map_err(ArchiveError::from)?
+                        .to_string();
+                    // Directories are entries of their own, named with a trailing slash
+                    if path.ends_with('/') {
+                        ready = reading.skip().await.map_err(ArchiveError::from)?;
+                        continue;
+                    }
+                    *state = ZipState::Reading(Box::new(reading));
+                    let ZipState::Reading(reading) = state else {
+                        unreachable!()
+                    };
+                    return Ok(Some(ArchiveEntry {
+                        path,
+                        reader: EntryReader::Zip(reading),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+enum EntryReader<'a> {
+    Tar(Box<tokio_tar::Entry<tokio_tar::Archive<Body>>>),
+    Zip(&'a mut ZipReading),
+}
+
+/// A file of an `ArchiveReader`, read its content through `AsyncRead`
+pub struct ArchiveEntry<'a> {
+    path: String,
+    reader: EntryReader<'a>,
+}
+
+impl ArchiveEntry<'_> {
+    /// Path of the file within the archive
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl AsyncRead for ArchiveEntry<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().reader {
+            EntryReader::Tar(entry) => Pin::new(entry).poll_read(cx, buf),
+            EntryReader::Zip(reading) => {
+                // The zip entry reader is a `futures` reader
+                let reader = reading.reader_mut();
+                let n = match futures::io::AsyncRead::poll_read(
+                    Pin::new(reader),
+                    cx,
+                    buf.initialize_unfilled(),
+                ) {
+                    Poll::Ready(Ok(n)) => n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                };
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+/// Two zeroed blocks end a tar archive
+static TAR_TRAILER: [u8; 1024] = [0; 1024];
+
+/// Bytes of a file read into a tar archive at a time
+const TAR_READ_SIZE: usize = 64 * 1024;
+
+/// A tar archive of `files`, triples of a path, the size of the file and a reader of its
+/// content, streamed as it is read. Paths longer than the 255 bytes of a ustar header fail with
+/// an `InvalidInput` error, readers that end before `size` bytes with `UnexpectedEof` and
+/// readers that go on past it with `InvalidData`.
+pub(crate) fn tar_stream<I, N, R>(
+    files: I,
+    mtime: time::OffsetDateTime,
+) -> impl Stream<Item = io::Result<Bytes>>
+where
+    I: IntoIterator<Item = (N, u64, R)>,
+    N: AsRef<str>,
+    R: AsyncRead + Unpin,
+{
+    let mtime = mtime.unix_timestamp().max(0) as u64;
+    futures::stream::iter(files)
+        .map(move |(name, size, reader)| tar_entry(name.as_ref(), size, reader, mtime))
+        .flatten()
+        .chain(futures::stream::once(async {
+            Ok(Bytes::from_static(&TAR_TRAILER))
+        }))
+}
+
+enum TarEntryState<R> {
+    Header(io::Result<Bytes>, R),
+    Body(R, u64),
+    Done,
+}
+
+/// Header, content and padding of a single tar entry
+fn tar_entry<R>(
+    path: &str,
+    size: u64,
+    reader: R,
+    mtime: u64,
+) -> impl Stream<Item = io::Result<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    let header = tar_header(path, size, mtime);
+    let path = path.to_string();
+    futures::stream::try_unfold(TarEntryState::Header(header, reader), move |state| {
+        let path = path.clone();
+        async move {
+            match state {
+                TarEntryState::Header(header, reader) => {
+                    Ok(Some((header?, TarEntryState::Body(reader, size))))
+                }
+                TarEntryState::Body(mut reader, 0) => {
+                    // One more read to tell a reader that ends here from one that goes on
+                    if reader.read(&mut [0u8; 1]).await? > 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{} is longer than its size of {} bytes", path, size),
+                        ));
+                    }
+                    let padding = ((512 - size % 512) % 512) as usize;
+                    if padding == 0 {
+                        return Ok(None);
+                    }
+                    Ok(Some((
+                        Bytes::from_static(&TAR_TRAILER[..padding]),
+                        TarEntryState::Done,
+                    )))
+                }
+                TarEntryState::Body(mut reader, remaining) => {
+                    let mut chunk = BytesMut::zeroed(remaining.min(TAR_READ_SIZE as u64) as usize);
+                    let n = reader.read(&mut chunk).await?;
+                    if n == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!(
+                                "{} ended {} bytes short of its size of {} bytes",
+                                path, remaining, size
+                            ),
+                        ));
+                    }
+                    chunk.truncate(n);
+                    Ok(Some((
+                        chunk.freeze(),
+                        TarEntryState::Body(reader, remaining - n as u64),
+                    )))
+                }
+                TarEntryState::Done => Ok(None),
+            }
+        }
+    })
+}
+
+fn tar_header(path: &str, size: u64, mtime: u64) -> io::Result<Bytes> {
+    let mut header = tokio_tar::Header::new_ustar();
+    header.set_path(path)?;
+    header.set_entry_type(tokio_tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    Ok(Bytes::copy_from_slice(header.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tar_stream, ArchiveFormat, ArchiveReader};
+    use async_compression::tokio::bufread::GzipEncoder;
+    use tokio::io::AsyncReadExt;
+    use tokio_util::io::StreamReader;
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        for (path, content) in files {
+            let mut header = [0u8; 512];
+            header[..path.len()].copy_from_slice(path.as_bytes());
+            header[100..107].copy_from_slice(b"0000644");
+            header[108..115].copy_from_slice(b"0000000");
+            header[116..123].copy_from_slice(b"0000000");
+            header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+            header[136..147].copy_from_slice(b"00000000000");
+            header[156] = b'0';
+            header[148..156].copy_from_slice(b"        ");
+            let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+            header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(content);
+            let padding = (512 - content.len() % 512) % 512;
+            archive.resize(archive.len() + padding, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+        archive
+    }
+
+    #[tokio::test]
+    async fn tar_entries_are_streamed_in_order() {
+        let body = tar(&[("a.csv", b"id\n1\n"), ("b.csv", b"id\n2\n3\n")]);
+        let mut archive =
+            ArchiveReader::new(std::io::Cursor::new(body), ArchiveFormat::Tar).unwrap();
+
+        // The first entry is left unread and skipped
+        let first = archive.next_entry().await.unwrap().unwrap();
+        assert_eq!(first.path(), "a.csv");
+
+        let mut second = archive.next_entry().await.unwrap().unwrap();
+        assert_eq!(second.path(), "b.csv");
+        let mut content = String::new();
+        second.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "id\n2\n3\n");
+
+        assert!(archive.next_entry().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn packed_tar_gz_reads_back() {
+        let files = vec![
+            ("logs/a.log", 5, &b"first"[..]),
+            ("logs/empty.log", 0, &b""[..]),
+            ("logs/b.log", 1500, &[7u8; 1500][..]),
+        ];
+        let tar = tar_stream(files, time::OffsetDateTime::UNIX_EPOCH);
+        let gz = GzipEncoder::new(StreamReader::new(Box::pin(tar)));
+        let mut archive = ArchiveReader::new(gz, ArchiveFormat::TarGz).unwrap();
+
+        let mut unpacked = Vec::new();
+        while let Some(mut entry) = archive.next_entry().await.unwrap() {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).await.unwrap();
+            unpacked.push((entry.path().to_string(), content));
+        }
+        assert_eq!(unpacked.len(), 3);
+        assert_eq!(unpacked[0], ("logs/a.log".to_string(), b"first".to_vec()));
+        assert!(unpacked[1].1.is_empty());
+        assert_eq!(unpacked[2].1, vec![7u8; 1500]);
+    }
+
+    #[tokio::test]
+    async fn tar_entries_must_match_their_size() {
+        use futures::TryStreamExt;
+
+        let short = vec![("short.log", 10, &b"first"[..])];
+        let err = tar_stream(short, time::OffsetDateTime::UNIX_EPOCH)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let long = vec![("long.log", 3, &b"first"[..])];
+        let err = tar_stream(long, time::OffsetDateTime::UNIX_EPOCH)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -34,7 +34,7 @@
 //!
 //! - **ListBucketsResponse Struct**
 //!   - This structure is used to parse and hold the response from the `ListBuckets` API call.
-//!   - Provides methods for retrieving the list of bucket names from the response.
+//!   - Provides methods for retrieving the buckets, with their creation dates, or only their names from the response.
 
 use crate::error::S3Error;
 use crate::{Bucket, Region};
@@ -230,6 +230,125 @@ impl BucketConfiguration {
     }
 }
 
+/// Rules bucket names are checked against by `Bucket::new` and `Bucket::create`, before a
+/// name that can't be used ends up in a URL.
+///
+/// AWS regions get the AWS rules, custom endpoints the relaxed ones as S3 compatible stores
+/// such as MinIO accept some names AWS refuses.
+///
+/// ```
+/// use s3::bucket_ops::BucketNameRules;
+///
+/// assert!(BucketNameRules::strict().validate("my-bucket").is_ok());
+/// assert!(BucketNameRules::strict().validate("My_Bucket").is_err());
+/// assert!(BucketNameRules::strict().allow_uppercase(true).validate("MyBucket").is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BucketNameRules {
+    pub allow_uppercase: bool,
+    pub allow_underscore: bool,
+}
+
+impl BucketNameRules {
+    /// Rules of general purpose and directory buckets on AWS
+    pub const fn strict() -> Self {
+        BucketNameRules {
+            allow_uppercase: false,
+            allow_underscore: false,
+        }
+    }
+
+    /// Uppercase letters and underscores allowed
+    pub const fn relaxed() -> Self {
+        BucketNameRules {
+            allow_uppercase: true,
+            allow_underscore: true,
+        }
+    }
+
+    pub fn for_region(region: &Region) -> Self {
+        match region {
+            Region::Custom { .. } => BucketNameRules::relaxed(),
+            _ => BucketNameRules::strict(),
+        }
+    }
+
+    pub fn allow_uppercase(mut self, allow: bool) -> Self {
+        self.allow_uppercase = allow;
+        self
+    }
+
+    pub fn allow_underscore(mut self, allow: bool) -> Self {
+        self.allow_underscore = allow;
+        self
+    }
+
+    /// Access point ARNs, Multi-Region ones included, are not bucket names and always pass
+    pub fn validate(&self, name: &str) -> Result<(), S3Error> {
+        if is_access_point_arn(name) {
+            return Ok(());
+        }
+        let invalid = |reason| {
+            Err(S3Error::InvalidBucketName {
+                name: name.to_string(),
+                reason,
+            })
+        };
+
+        if !(3..=63).contains(&name.len()) {
+            return invalid("must be between 3 and 63 characters long");
+        }
+        for c in name.chars() {
+            let allowed = match c {
+                'a'..='z' | '0'..='9' | '.' | '-' => true,
+                'A'..='Z' => self.allow_uppercase,
+                '_' => self.allow_underscore,
+                _ => false,
+            };
+            if !allowed {
+                return invalid(match c {
+                    'A'..='Z' => "uppercase letters are not allowed",
+                    '_' => "underscores are not allowed",
+                    _ => "only letters, digits, dots and hyphens are allowed",
+                });
+            }
+        }
+        let alphanumeric = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+        if !alphanumeric(name.chars().next()) || !alphanumeric(name.chars().last()) {
+            return invalid("must begin and end with a letter or digit");
+        }
+        if name.contains("..") {
+            return invalid("must not contain two adjacent dots");
+        }
+        if name.parse::<std::net::Ipv4Addr>().is_ok() {
+            return invalid("must not be formatted as an IP address");
+        }
+        Ok(())
+    }
+}
+
+// `arn:<partition>:s3:<region>:<account-id>:accesspoint/<name>`, the region is empty for
+// Multi-Region Access Points
+fn is_access_point_arn(name: &str) -> bool {
+    let mut parts = name.splitn(6, ':');
+    match (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) {
+        (Some("arn"), Some(_), Some("s3"), Some(_), Some(account), Some(resource)) => {
+            !account.is_empty()
+                && resource
+                    .strip_prefix("accesspoint/")
+                    .is_some_and(|name| !name.is_empty())
+        }
+        _ => false,
+    }
+}
+
 #[allow(dead_code)]
 pub struct CreateBucketResponse {
     pub bucket: Box<Bucket>,
@@ -255,6 +374,16 @@ mod list_buckets {
     }
 
     impl ListBucketsResponse {
+        /// Owner of the listed buckets
+        pub fn owner(&self) -> &BucketOwner {
+            &self.owner
+        }
+
+        /// The listed buckets, with their creation dates
+        pub fn buckets(&self) -> &[BucketInfo] {
+            &self.buckets.bucket
+        }
+
         pub fn bucket_names(&self) -> impl Iterator<Item = String> + '_ {
             self.buckets.bucket.iter().map(|bucket| bucket.name.clone())
         }
@@ -275,6 +404,18 @@ mod list_buckets {
         pub creation_date: String,
     }
 
+    impl BucketInfo {
+        /// `creation_date` parsed and normalized to UTC. Parsed on demand, so a store reporting
+        /// a date in another format doesn't fail the whole listing.
+        pub fn creation_date_at(&self) -> Result<time::OffsetDateTime, crate::error::S3Error> {
+            let creation_date = time::OffsetDateTime::parse(
+                &self.creation_date,
+                &time::format_description::well_known::Rfc3339,
+            )?;
+            Ok(creation_date.to_offset(time::UtcOffset::UTC))
+        }
+    }
+
     #[derive(Deserialize, Default, Clone, Debug)]
     #[serde(rename_all = "PascalCase")]
     pub struct BucketContainer {
@@ -326,6 +467,42 @@ mod list_buckets {
                 parsed.buckets.bucket.last().unwrap().creation_date,
                 "2023-06-04T20:17:47.152Z"
             );
+
+            assert_eq!(parsed.buckets().len(), 2);
+            assert_eq!(
+                parsed.buckets()[0].creation_date_at().unwrap(),
+                time::macros::datetime!(2023-06-04 20:13:37.837 UTC)
+            );
+        }
+
+        #[test]
+        pub fn parse_list_buckets_response_with_unparsable_creation_date() {
+            let response = r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+                <ListAllMyBucketsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                    <Owner>
+                        <ID>02d6176db174dc93cb1b899f7c6078f08654445fe8cf1b6ce98d8855f66bdbf4</ID>
+                    </Owner>
+                    <Buckets>
+                        <Bucket>
+                            <Name>test-rust-s3</Name>
+                            <CreationDate>Sun, 04 Jun 2023 20:13:37 GMT</CreationDate>
+                        </Bucket>
+                        <Bucket>
+                            <Name>test-rust-s3-2</Name>
+                            <CreationDate>2023-06-04T22:17:47+02:00</CreationDate>
+                        </Bucket>
+                    </Buckets>
+                </ListAllMyBucketsResult>
+            "#;
+
+            let parsed = quick_xml::de::from_str::<super::ListBucketsResponse>(response).unwrap();
+            assert_eq!(parsed.buckets().len(), 2);
+            assert!(parsed.buckets()[0].creation_date_at().is_err());
+            assert_eq!(
+                parsed.buckets()[1].creation_date_at().unwrap(),
+                time::macros::datetime!(2023-06-04 20:17:47 UTC)
+            );
         }
 
         #[test]
@@ -353,3 +530,57 @@ mod list_buckets {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::BucketNameRules;
+    use crate::error::S3Error;
+    use crate::Region;
+
+    #[test]
+    fn bucket_name_rules() {
+        let strict = BucketNameRules::strict();
+        for name in [
+            "rust-s3",
+            "logs.2024",
+            "logs--usw2-az1--x-s3",
+            "a1b",
+            "arn:aws:s3:us-east-1:123456789012:accesspoint/regional",
+            "arn:aws:s3::123456789012:accesspoint/mfzwi23gnjvgw.mrap",
+        ] {
+            assert!(strict.validate(name).is_ok(), "{}", name);
+        }
+        for name in [
+            "ab",
+            &"a".repeat(64),
+            "Rust-s3",
+            "rust_s3",
+            "-rust-s3",
+            "rust-s3.",
+            "rust..s3",
+            "192.168.5.4",
+            "rust s3",
+            "arn:aws:s3:us-east-1:123456789012:bucket/rust-s3",
+            "arn:aws:s3:us-east-1::accesspoint/regional",
+        ] {
+            assert!(
+                matches!(
+                    strict.validate(name),
+                    Err(S3Error::InvalidBucketName { .. })
+                ),
+                "{}",
+                name
+            );
+        }
+
+        let minio = BucketNameRules::for_region(&Region::Custom {
+            region: "minio".to_string(),
+            endpoint: "http://localhost:9000".to_string(),
+        });
+        assert!(minio.validate("Rust_S3").is_ok());
+        assert_eq!(
+            BucketNameRules::for_region(&Region::EuWest1),
+            BucketNameRules::strict()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Objects sharded across several buckets.
+//!
+//! Workloads that outgrow the request rate S3 allows per prefix can spread their keys over
+//! several buckets, possibly in different regions. A [`BucketPool`] sends each key to the
+//! bucket picked by the CRC32C of the key, unless one of its prefixes was routed to a given
+//! bucket, and merges the listings of every bucket.
+//!
+//! # Example
+//! ```no_run
+//! use s3::bucket::Bucket;
+//! use s3::bucket_pool::BucketPool;
+//! use s3::creds::Credentials;
+//! use anyhow::Result;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let credentials = Credentials::default()?;
+//! let shards = vec![
+//!     *Bucket::new("events-0", "us-east-1".parse()?, credentials.clone())?,
+//!     *Bucket::new("events-1", "us-east-1".parse()?, credentials.clone())?,
+//!     *Bucket::new("events-2", "eu-west-1".parse()?, credentials)?,
+//! ];
+//! let pool = BucketPool::new(shards)?.with_route("audit/", 2)?;
+//!
+//! pool.put_object("/2024/03/01/click.json", b"{}").await?;
+//! let objects = pool.list_objects("2024/03/").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bucket::Bucket;
+use crate::error::S3Error;
+use crate::request::ResponseData;
+use crate::serde_types::{HeadObjectResult, Object};
+use crate::utils::{Crc32c, DeleteObjectResult};
+
+#[derive(Clone, Debug)]
+pub struct BucketPool {
+    shards: Vec<Bucket>,
+    /// Key prefixes pinned to a shard, the longest matching prefix wins
+    routes: Vec<(String, usize)>,
+}
+
+impl BucketPool {
+    /// Pool over `shards`. Their order decides which bucket holds a key, it has to stay the same
+    /// across processes and restarts, adding a shard moves most keys. Fails with
+    /// `S3Error::EmptyBucketPool` without shards.
+    pub fn new(shards: Vec<Bucket>) -> Result<Self, S3Error> {
+        if shards.is_empty() {
+            return Err(S3Error::EmptyBucketPool);
+        }
+        Ok(BucketPool {
+            shards,
+            routes: Vec::new(),
+        })
+    }
+
+    /// Send keys starting with `prefix` to the shard at index `shard`, whatever their hash.
+    /// Fails with `S3Error::NoSuchShard` for an index past the last shard.
+    pub fn with_route(mut self, prefix: impl Into<String>, shard: usize) -> Result<Self, S3Error> {
+        if shard >= self.shards.len() {
+            return Err(S3Error::NoSuchShard {
+                shard,
+                shards: self.shards.len(),
+            });
+        }
+        self.routes.push((prefix.into(), shard));
+        Ok(self)
+    }
+
+    pub fn shards(&self) -> &[Bucket] {
+        &self.shards
+    }
+
+    /// Index of the shard holding `key`, a leading `/` is ignored
+    pub fn shard_index(&self, key: &str) -> usize {
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let route = self
+            .routes
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((_, shard)) = route {
+            return *shard;
+        }
+        let mut crc = Crc32c::new();
+        crc.update(key.as_bytes());
+        crc.finalize() as usize % self.shards.len()
+    }
+
+    /// Bucket holding `key`
+    pub fn shard(&self, key: &str) -> &Bucket {
+        &self.shards[self.shard_index(key)]
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn put_object(&self, path: &str, content: &[u8]) -> Result<ResponseData, S3Error> {
+        self.shard(path).put_object(path, content).await
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn get_object(&self, path: &str) -> Result<ResponseData, S3Error> {
+        self.shard(path).get_object(path).await
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn head_object(&self, path: &str) -> Result<(HeadObjectResult, u16), S3Error> {
+        self.shard(path).head_object(path).await
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn delete_object(&self, path: &str) -> Result<DeleteObjectResult, S3Error> {
+        self.shard(path).delete_object(path).await
+    }
+
+    /// Objects under `prefix` in every shard, merged in key order. Shards are listed one after
+    /// the other.
+    #[maybe_async::maybe_async]
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<Object>, S3Error> {
+        let mut objects = Vec::new();
+        for shard in &self.shards {
+            for page in shard.list(prefix.to_string(), None).await? {
+                objects.extend(page.contents);
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BucketPool;
+    use crate::creds::Credentials;
+    use crate::error::S3Error;
+    use crate::region::Region;
+    use crate::Bucket;
+
+    fn pool(shards: usize) -> BucketPool {
+        let shards = (0..shards)
+            .map(|i| {
+                *Bucket::new(
+                    &format!("shard-{}", i),
+                    Region::UsEast1,
+                    Credentials::anonymous().unwrap(),
+                )
+                .unwrap()
+            })
+            .collect();
+        BucketPool::new(shards).unwrap()
+    }
+
+    #[test]
+    fn keys_are_routed_by_crc32c() {
+        // CRC32C of "123456789" is 0xe3069283
+        assert_eq!(pool(2).shard_index("123456789"), 1);
+        assert_eq!(pool(3).shard_index("/123456789"), 0);
+        assert_eq!(pool(3).shard("123456789").name, "shard-0");
+    }
+
+    #[test]
+    fn longest_route_wins() {
+        let pool = pool(3)
+            .with_route("audit/", 1)
+            .unwrap()
+            .with_route("audit/eu/", 2)
+            .unwrap();
+        assert_eq!(pool.shard_index("/audit/us/a.json"), 1);
+        assert_eq!(pool.shard_index("audit/eu/a.json"), 2);
+        assert_eq!(pool.shard_index("123456789"), 0);
+    }
+
+    #[test]
+    fn invalid_pools_are_errors() {
+        assert!(matches!(
+            BucketPool::new(Vec::new()),
+            Err(S3Error::EmptyBucketPool)
+        ));
+        assert!(matches!(
+            pool(2).with_route("audit/", 2),
+            Err(S3Error::NoSuchShard {
+                shard: 2,
+                shards: 2
+            })
+        ));
+    }
+}
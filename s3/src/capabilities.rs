@@ -0,0 +1,286 @@
+//! Optional S3 operations and which of them a provider implements.
+//!
+//! Every `Bucket` carries a set of enabled [`Operation`]s, derived from its region when the
+//! bucket is created and adjustable with `Bucket::with_capabilities`. Requests for operations
+//! that are not enabled fail with [`S3Error::UnsupportedOperation`] before anything is sent.
+//!
+//! Providers also differ in the size limits they enforce, a bucket's [`ProviderLimits`] are
+//! likewise derived from its region and adjustable with `Bucket::with_limits`.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::command::Command;
+use crate::error::S3Error;
+use crate::region::Region;
+
+/// S3 operations that not every provider implements
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Torrent,
+    ObjectAttributes,
+    Tagging,
+    Lifecycle,
+    Cors,
+    Versioning,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 6] = [
+        Operation::Torrent,
+        Operation::ObjectAttributes,
+        Operation::Tagging,
+        Operation::Lifecycle,
+        Operation::Cors,
+        Operation::Versioning,
+    ];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Torrent => write!(f, "torrent"),
+            Operation::ObjectAttributes => write!(f, "object attributes"),
+            Operation::Tagging => write!(f, "tagging"),
+            Operation::Lifecycle => write!(f, "lifecycle"),
+            Operation::Cors => write!(f, "cors"),
+            Operation::Versioning => write!(f, "versioning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    enabled: HashSet<Operation>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::all()
+    }
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Capabilities {
+            enabled: Operation::ALL.into_iter().collect(),
+        }
+    }
+
+    pub fn none() -> Self {
+        Capabilities {
+            enabled: HashSet::new(),
+        }
+    }
+
+    /// Operations the provider behind `region` implements, everything for custom endpoints
+    pub fn for_region(region: &Region) -> Self {
+        use Region::*;
+        match region {
+            // BitTorrent is only served from regions launched before June 2016
+            UsEast1
+            | UsWest1
+            | UsWest2
+            | EuWest1
+            | EuCentral1
+            | ApNortheast1
+            | ApNortheast2
+            | ApSoutheast1
+            | ApSoutheast2
+            | SaEast1
+            | Custom { .. } => Capabilities::all(),
+            UsEast2 | CaCentral1 | AfSouth1 | ApEast1 | ApSouth1 | ApNortheast3 | CnNorth1
+            | CnNorthwest1 | EuNorth1 | EuCentral2 | EuWest2 | EuWest3 | IlCentral1 | MeSouth1 => {
+                Capabilities::all().disable(Operation::Torrent)
+            }
+            R2 { .. } => Capabilities::all()
+                .disable(Operation::Torrent)
+                .disable(Operation::ObjectAttributes)
+                .disable(Operation::Tagging)
+                .disable(Operation::Versioning),
+            _ => Capabilities::all()
+                .disable(Operation::Torrent)
+                .disable(Operation::ObjectAttributes),
+        }
+    }
+
+    pub fn enable(mut self, operation: Operation) -> Self {
+        self.enabled.insert(operation);
+        self
+    }
+
+    pub fn disable(mut self, operation: Operation) -> Self {
+        self.enabled.remove(&operation);
+        self
+    }
+
+    pub fn supports(&self, operation: Operation) -> bool {
+        self.enabled.contains(&operation)
+    }
+
+    /// Fail with `S3Error::UnsupportedOperation` if `command` needs a disabled operation
+    pub fn check(&self, command: &Command) -> Result<(), S3Error> {
+        match command.operation() {
+            Some(operation) if !self.supports(operation) => {
+                Err(S3Error::UnsupportedOperation(operation))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+const TIB: u64 = 1024 * GIB;
+
+/// Size limits of a provider, checked by the multipart upload code before requests are sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProviderLimits {
+    /// Smallest part, the last part of an upload may be smaller
+    pub min_part_size: u64,
+    pub max_part_size: u64,
+    /// Parts of a single multipart upload
+    pub max_parts: u32,
+    pub max_object_size: u64,
+    /// Keys a single `DeleteObjects` request takes, 1 where batch deletes aren't implemented
+    pub max_keys_per_delete: usize,
+}
+
+impl Default for ProviderLimits {
+    fn default() -> Self {
+        ProviderLimits::aws()
+    }
+}
+
+impl ProviderLimits {
+    /// Limits of AWS S3, which most compatible providers, MinIO among them, follow
+    pub const fn aws() -> Self {
+        ProviderLimits {
+            min_part_size: 5 * MIB,
+            max_part_size: 5 * GIB,
+            max_parts: 10_000,
+            max_object_size: 5 * TIB,
+            max_keys_per_delete: 1000,
+        }
+    }
+
+    /// Limits of the provider behind `region`. Custom endpoints get those of AWS, except for
+    /// Google Cloud Storage, whose XML API has no batch delete.
+    pub fn for_region(region: &Region) -> Self {
+        match region {
+            // 4.995 TiB
+            Region::R2 { .. } => ProviderLimits {
+                max_object_size: 5 * TIB - 5 * GIB,
+                ..ProviderLimits::aws()
+            },
+            Region::Custom { endpoint, .. } if endpoint.contains("storage.googleapis.com") => {
+                ProviderLimits {
+                    max_keys_per_delete: 1,
+                    ..ProviderLimits::aws()
+                }
+            }
+            _ => ProviderLimits::aws(),
+        }
+    }
+
+    /// Fail with `S3Error::InvalidPartSize` unless parts of `part_size` bytes are accepted
+    pub fn check_part_size(&self, part_size: usize) -> Result<(), S3Error> {
+        let size = part_size as u64;
+        if size < self.min_part_size || size > self.max_part_size {
+            return Err(S3Error::InvalidPartSize(part_size));
+        }
+        Ok(())
+    }
+
+    /// Part size for a server side copy of `object_size` bytes, 512 MiB unless more parts than
+    /// `max_parts` would be needed. Copied parts cost a request each but no transfer.
+    pub fn copy_part_size(&self, object_size: u64) -> u64 {
+        let fitting = object_size.div_ceil(self.max_parts as u64);
+        (512 * MIB)
+            .max(fitting)
+            .clamp(self.min_part_size, self.max_part_size)
+    }
+
+    /// Fail with `S3Error::ObjectTooLarge` if an object can't hold `size` bytes
+    pub fn check_object_size(&self, size: u64) -> Result<(), S3Error> {
+        if size > self.max_object_size {
+            return Err(S3Error::ObjectTooLarge {
+                size,
+                max: self.max_object_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fail with `S3Error::TooManyParts` if an upload can't have a part `part_number`
+    pub fn check_part_number(&self, part_number: u32) -> Result<(), S3Error> {
+        if part_number > self.max_parts {
+            return Err(S3Error::TooManyParts(self.max_parts));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Capabilities, Operation, ProviderLimits, GIB, MIB, TIB};
+    use crate::command::Command;
+    use crate::error::S3Error;
+    use crate::region::Region;
+
+    #[test]
+    fn provider_profiles() {
+        let aws = Capabilities::for_region(&Region::UsEast1);
+        assert!(Operation::ALL.iter().all(|op| aws.supports(*op)));
+        assert!(!Capabilities::for_region(&Region::EuNorth1).supports(Operation::Torrent));
+
+        let r2 = Capabilities::for_region(&Region::R2 {
+            account_id: "account".to_string(),
+        });
+        assert!(r2.check(&Command::GetObject).is_ok());
+        assert!(matches!(
+            r2.check(&Command::GetObjectTagging),
+            Err(S3Error::UnsupportedOperation(Operation::Tagging))
+        ));
+
+        let custom = Capabilities::all().disable(Operation::Lifecycle);
+        assert!(custom.check(&Command::GetBucketLifecycle).is_err());
+        assert!(custom
+            .enable(Operation::Lifecycle)
+            .supports(Operation::Lifecycle));
+    }
+
+    #[test]
+    fn provider_limits() {
+        let aws = ProviderLimits::for_region(&Region::UsEast1);
+        assert_eq!(aws, ProviderLimits::default());
+        assert!(aws.check_part_size(5 * 1024 * 1024).is_ok());
+        assert!(matches!(
+            aws.check_part_size(1024),
+            Err(S3Error::InvalidPartSize(1024))
+        ));
+        assert!(aws.check_part_number(10_000).is_ok());
+        assert_eq!(aws.copy_part_size(6 * GIB), 512 * MIB);
+        assert_eq!(aws.copy_part_size(5 * TIB), 5 * TIB / 10_000 + 1);
+        assert!(matches!(
+            aws.check_part_number(10_001),
+            Err(S3Error::TooManyParts(10_000))
+        ));
+
+        let r2 = ProviderLimits::for_region(&Region::R2 {
+            account_id: "account".to_string(),
+        });
+        assert!(r2.max_object_size < aws.max_object_size);
+        assert!(aws.check_object_size(5 * TIB - GIB).is_ok());
+        assert!(matches!(
+            r2.check_object_size(5 * TIB - GIB),
+            Err(S3Error::ObjectTooLarge { .. })
+        ));
+
+        let gcs = ProviderLimits::for_region(&Region::Custom {
+            region: "auto".to_string(),
+            endpoint: "https://storage.googleapis.com".to_string(),
+        });
+        assert_eq!(gcs.max_keys_per_delete, 1);
+    }
+}
@@ -21,14 +21,18 @@
 //!
 use std::collections::HashMap;
 
+use crate::capabilities::Operation;
 use crate::error::S3Error;
 use crate::serde_types::{
     BucketLifecycleConfiguration, CompleteMultipartUploadData, CorsConfiguration,
+    DeleteObjectsRequest, GetObjectAttributesOptions, VersioningConfiguration, XmlDocument,
 };
 
+use crate::utils::{aws_chunked_len, Crc32c, STREAMING_UNSIGNED_PAYLOAD_TRAILER};
 use crate::EMPTY_PAYLOAD_SHA;
 use sha2::{Digest, Sha256};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HttpMethod {
     Delete,
     Get,
@@ -51,6 +55,7 @@ impl fmt::Display for HttpMethod {
     }
 }
 use crate::bucket_ops::BucketConfiguration;
+use bytes::Bytes;
 use http::HeaderMap;
 
 #[derive(Clone, Debug)]
@@ -73,6 +78,57 @@ impl<'a> Multipart<'a> {
             upload_id,
         }
     }
+
+    pub fn part_number(&self) -> u32 {
+        self.part_number
+    }
+}
+
+/// Body of a `PutObject`. The async backends have to own what they send and copy a borrowed
+/// body once per attempt, an owned one is handed to the HTTP client without being copied.
+#[derive(Clone, Debug)]
+pub enum Payload<'a> {
+    Borrowed(&'a [u8]),
+    Owned(Bytes),
+}
+
+impl Payload<'_> {
+    /// The body as `Bytes`, copied only when it is borrowed
+    pub fn into_bytes(self) -> Bytes {
+        match self {
+            Payload::Borrowed(content) => Bytes::copy_from_slice(content),
+            Payload::Owned(content) => content,
+        }
+    }
+}
+
+impl std::ops::Deref for Payload<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Payload::Borrowed(content) => content,
+            Payload::Owned(content) => content,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Payload<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<'a> From<&'a [u8]> for Payload<'a> {
+    fn from(content: &'a [u8]) -> Self {
+        Payload::Borrowed(content)
+    }
+}
+
+impl From<Bytes> for Payload<'_> {
+    fn from(content: Bytes) -> Self {
+        Payload::Owned(content)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -91,7 +147,15 @@ pub enum Command<'a> {
     },
     GetObjectTagging,
     PutObject {
+        content: Payload<'a>,
+        content_type: &'a str,
+        multipart: Option<Multipart<'a>>,
+    },
+    /// `PutObject`, with the body sent `aws-chunked` and a trailing CRC32C checksum
+    PutObjectWithChecksum {
         content: &'a [u8],
+        /// CRC32C of `content`, sent as the trailer
+        checksum: Crc32c,
         content_type: &'a str,
         multipart: Option<Multipart<'a>>,
     },
@@ -117,9 +181,19 @@ pub enum Command<'a> {
         start_after: Option<String>,
         max_keys: Option<usize>,
     },
+    ListObjectVersions {
+        prefix: String,
+        delimiter: Option<String>,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+        max_keys: Option<usize>,
+    },
     GetBucketLocation,
+    CreateSession,
     PresignGet {
         expiry_secs: u32,
+        /// Headers the URL is signed with, recipients have to send them unchanged
+        custom_headers: Option<HeaderMap>,
         custom_queries: Option<HashMap<String, String>>,
     },
     PresignPut {
@@ -130,6 +204,12 @@ pub enum Command<'a> {
     PresignDelete {
         expiry_secs: u32,
     },
+    /// A presigned `POST`, the multipart upload's `CreateMultipartUpload` and
+    /// `CompleteMultipartUpload` are told apart by `custom_queries`
+    PresignPost {
+        expiry_secs: u32,
+        custom_queries: Option<HashMap<String, String>>,
+    },
     InitiateMultipartUpload {
         content_type: &'a str,
     },
@@ -138,6 +218,13 @@ pub enum Command<'a> {
         content: &'a [u8],
         upload_id: &'a str,
     },
+    /// `UploadPartCopy`, a part copied server side from `from`, `bucket/key` as in `CopyObject`.
+    /// `range` holds the first and last byte copied, inclusive, the whole object without it.
+    UploadPartCopy {
+        from: &'a str,
+        range: Option<(u64, u64)>,
+        multipart: Multipart<'a>,
+    },
     AbortMultipartUpload {
         upload_id: &'a str,
     },
@@ -145,6 +232,10 @@ pub enum Command<'a> {
         upload_id: &'a str,
         data: CompleteMultipartUploadData,
     },
+    /// Delete up to `ProviderLimits::max_keys_per_delete` keys with one request
+    DeleteObjects {
+        request: DeleteObjectsRequest,
+    },
     CreateBucket {
         config: BucketConfiguration,
     },
@@ -165,13 +256,57 @@ pub enum Command<'a> {
         configuration: BucketLifecycleConfiguration,
     },
     DeleteBucketLifecycle,
+    PutBucketVersioning {
+        configuration: VersioningConfiguration,
+        mfa: Option<String>,
+    },
+    DeleteObjectVersion {
+        version_id: String,
+        mfa: Option<String>,
+    },
+    GetObjectVersion {
+        version_id: String,
+    },
     GetObjectAttributes {
         expected_bucket_owner: String,
-        version_id: Option<String>,
+        options: GetObjectAttributesOptions,
     },
 }
 
 impl<'a> Command<'a> {
+    /// Optional operation the command belongs to, see `Capabilities`
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Command::GetObjectTorrent => Some(Operation::Torrent),
+            Command::GetObjectAttributes { .. } => Some(Operation::ObjectAttributes),
+            Command::PutObjectTagging { .. }
+            | Command::GetObjectTagging
+            | Command::DeleteObjectTagging => Some(Operation::Tagging),
+            Command::GetBucketLifecycle
+            | Command::PutBucketLifecycle { .. }
+            | Command::DeleteBucketLifecycle => Some(Operation::Lifecycle),
+            Command::GetBucketCors { .. }
+            | Command::PutBucketCors { .. }
+            | Command::DeleteBucketCors { .. } => Some(Operation::Cors),
+            Command::PutBucketVersioning { .. }
+            | Command::DeleteObjectVersion { .. }
+            | Command::GetObjectVersion { .. }
+            | Command::ListObjectVersions { .. } => Some(Operation::Versioning),
+            _ => None,
+        }
+    }
+
+    /// Whether the command signs a URL for later use rather than being sent
+    pub fn is_presign(&self) -> bool {
+        matches!(
+            self,
+            Command::PresignGet { .. }
+                | Command::PresignPut { .. }
+                | Command::PresignDelete { .. }
+                | Command::PresignPost { .. }
+        )
+    }
+
     pub fn http_verb(&self) -> HttpMethod {
         match *self {
             Command::GetObject
@@ -181,29 +316,37 @@ impl<'a> Command<'a> {
             | Command::ListBuckets
             | Command::ListObjects { .. }
             | Command::ListObjectsV2 { .. }
+            | Command::ListObjectVersions { .. }
+            | Command::GetObjectVersion { .. }
             | Command::GetBucketLocation
+            | Command::CreateSession
             | Command::GetObjectTagging
             | Command::GetBucketLifecycle
             | Command::ListMultipartUploads { .. }
             | Command::PresignGet { .. } => HttpMethod::Get,
             Command::PutObject { .. }
+            | Command::PutObjectWithChecksum { .. }
             | Command::CopyObject { from: _ }
             | Command::PutObjectTagging { .. }
             | Command::PresignPut { .. }
             | Command::UploadPart { .. }
+            | Command::UploadPartCopy { .. }
             | Command::PutBucketCors { .. }
             | Command::CreateBucket { .. }
-            | Command::PutBucketLifecycle { .. } => HttpMethod::Put,
+            | Command::PutBucketLifecycle { .. }
+            | Command::PutBucketVersioning { .. } => HttpMethod::Put,
             Command::DeleteObject
+            | Command::DeleteObjectVersion { .. }
             | Command::DeleteObjectTagging
             | Command::AbortMultipartUpload { .. }
             | Command::PresignDelete { .. }
             | Command::DeleteBucket
             | Command::DeleteBucketCors { .. }
             | Command::DeleteBucketLifecycle => HttpMethod::Delete,
-            Command::InitiateMultipartUpload { .. } | Command::CompleteMultipartUpload { .. } => {
-                HttpMethod::Post
-            }
+            Command::InitiateMultipartUpload { .. }
+            | Command::CompleteMultipartUpload { .. }
+            | Command::DeleteObjects { .. }
+            | Command::PresignPost { .. } => HttpMethod::Post,
             Command::HeadObject => HttpMethod::Head,
             Command::GetObjectAttributes { .. } => HttpMethod::Get,
         }
@@ -212,10 +355,13 @@ impl<'a> Command<'a> {
     pub fn content_length(&self) -> Result<usize, S3Error> {
         let result = match &self {
             Command::CopyObject { from: _ } => 0,
+            Command::UploadPartCopy { .. } => 0,
             Command::PutObject { content, .. } => content.len(),
+            Command::PutObjectWithChecksum { content, .. } => aws_chunked_len(content.len()),
             Command::PutObjectTagging { tags } => tags.len(),
             Command::UploadPart { content, .. } => content.len(),
             Command::CompleteMultipartUpload { data, .. } => data.len(),
+            Command::DeleteObjects { request } => request.to_xml()?.len(),
             Command::CreateBucket { config } => {
                 if let Some(payload) = config.location_constraint_payload() {
                     Vec::from(payload).len()
@@ -223,14 +369,12 @@ impl<'a> Command<'a> {
                     0
                 }
             }
-            Command::PutBucketLifecycle { configuration } => {
-                quick_xml::se::to_string(configuration)?.as_bytes().len()
-            }
-            Command::PutBucketCors { configuration, .. } => {
-                configuration.to_string().as_bytes().len()
-            }
+            Command::PutBucketLifecycle { configuration } => configuration.to_xml()?.len(),
+            Command::PutBucketCors { configuration, .. } => configuration.to_xml()?.len(),
+            Command::PutBucketVersioning { configuration, .. } => configuration.to_xml()?.len(),
             Command::HeadObject => 0,
             Command::DeleteObject => 0,
+            Command::DeleteObjectVersion { .. } => 0,
             Command::DeleteObjectTagging => 0,
             Command::GetObject => 0,
             Command::GetObjectTorrent => 0,
@@ -239,10 +383,14 @@ impl<'a> Command<'a> {
             Command::ListMultipartUploads { .. } => 0,
             Command::ListObjects { .. } => 0,
             Command::ListObjectsV2 { .. } => 0,
+            Command::ListObjectVersions { .. } => 0,
+            Command::GetObjectVersion { .. } => 0,
             Command::GetBucketLocation => 0,
+            Command::CreateSession => 0,
             Command::PresignGet { .. } => 0,
             Command::PresignPut { .. } => 0,
             Command::PresignDelete { .. } => 0,
+            Command::PresignPost { .. } => 0,
             Command::InitiateMultipartUpload { .. } => 0,
             Command::AbortMultipartUpload { .. } => 0,
             Command::DeleteBucket => 0,
@@ -260,11 +408,15 @@ impl<'a> Command<'a> {
         match self {
             Command::InitiateMultipartUpload { content_type } => content_type.to_string(),
             Command::PutObject { content_type, .. } => content_type.to_string(),
+            Command::PutObjectWithChecksum { content_type, .. } => content_type.to_string(),
             Command::CompleteMultipartUpload { .. }
+            | Command::DeleteObjects { .. }
             | Command::PutBucketLifecycle { .. }
-            | Command::PutBucketCors { .. } => "application/xml".into(),
+            | Command::PutBucketCors { .. }
+            | Command::PutBucketVersioning { .. } => "application/xml".into(),
             Command::HeadObject => "text/plain".into(),
             Command::DeleteObject => "text/plain".into(),
+            Command::DeleteObjectVersion { .. } => "text/plain".into(),
             Command::DeleteObjectTagging => "text/plain".into(),
             Command::GetObject => "text/plain".into(),
             Command::GetObjectTorrent => "text/plain".into(),
@@ -273,10 +425,14 @@ impl<'a> Command<'a> {
             Command::ListMultipartUploads { .. } => "text/plain".into(),
             Command::ListObjects { .. } => "text/plain".into(),
             Command::ListObjectsV2 { .. } => "text/plain".into(),
+            Command::ListObjectVersions { .. } => "text/plain".into(),
+            Command::GetObjectVersion { .. } => "text/plain".into(),
             Command::GetBucketLocation => "text/plain".into(),
+            Command::CreateSession => "text/plain".into(),
             Command::PresignGet { .. } => "text/plain".into(),
             Command::PresignPut { .. } => "text/plain".into(),
             Command::PresignDelete { .. } => "text/plain".into(),
+            Command::PresignPost { .. } => "text/plain".into(),
             Command::AbortMultipartUpload { .. } => "text/plain".into(),
             Command::DeleteBucket => "text/plain".into(),
             Command::ListBuckets => "text/plain".into(),
@@ -287,6 +443,7 @@ impl<'a> Command<'a> {
             Command::CopyObject { .. } => "text/plain".into(),
             Command::PutObjectTagging { .. } => "text/plain".into(),
             Command::UploadPart { .. } => "text/plain".into(),
+            Command::UploadPartCopy { .. } => "text/plain".into(),
             Command::CreateBucket { .. } => "text/plain".into(),
             Command::GetObjectAttributes { .. } => "text/plain".into(),
         }
@@ -299,6 +456,7 @@ impl<'a> Command<'a> {
                 sha.update(content);
                 hex::encode(sha.finalize().as_slice())
             }
+            Command::PutObjectWithChecksum { .. } => STREAMING_UNSIGNED_PAYLOAD_TRAILER.into(),
             Command::PutObjectTagging { tags } => {
                 let mut sha = Sha256::default();
                 sha.update(tags.as_bytes());
@@ -320,16 +478,27 @@ impl<'a> Command<'a> {
             }
             Command::PutBucketLifecycle { configuration } => {
                 let mut sha = Sha256::default();
-                sha.update(quick_xml::se::to_string(configuration)?.as_bytes());
+                sha.update(configuration.to_xml()?.as_bytes());
                 hex::encode(sha.finalize().as_slice())
             }
             Command::PutBucketCors { configuration, .. } => {
                 let mut sha = Sha256::default();
-                sha.update(configuration.to_string().as_bytes());
+                sha.update(configuration.to_xml()?.as_bytes());
+                hex::encode(sha.finalize().as_slice())
+            }
+            Command::DeleteObjects { request } => {
+                let mut sha = Sha256::default();
+                sha.update(request.to_xml()?.as_bytes());
+                hex::encode(sha.finalize().as_slice())
+            }
+            Command::PutBucketVersioning { configuration, .. } => {
+                let mut sha = Sha256::default();
+                sha.update(configuration.to_xml()?.as_bytes());
                 hex::encode(sha.finalize().as_slice())
             }
             Command::HeadObject => EMPTY_PAYLOAD_SHA.into(),
             Command::DeleteObject => EMPTY_PAYLOAD_SHA.into(),
+            Command::DeleteObjectVersion { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::DeleteObjectTagging => EMPTY_PAYLOAD_SHA.into(),
             Command::GetObject => EMPTY_PAYLOAD_SHA.into(),
             Command::GetObjectTorrent => EMPTY_PAYLOAD_SHA.into(),
@@ -338,10 +507,14 @@ impl<'a> Command<'a> {
             Command::ListMultipartUploads { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::ListObjects { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::ListObjectsV2 { .. } => EMPTY_PAYLOAD_SHA.into(),
+            Command::ListObjectVersions { .. } => EMPTY_PAYLOAD_SHA.into(),
+            Command::GetObjectVersion { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::GetBucketLocation => EMPTY_PAYLOAD_SHA.into(),
+            Command::CreateSession => EMPTY_PAYLOAD_SHA.into(),
             Command::PresignGet { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::PresignPut { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::PresignDelete { .. } => EMPTY_PAYLOAD_SHA.into(),
+            Command::PresignPost { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::AbortMultipartUpload { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::DeleteBucket => EMPTY_PAYLOAD_SHA.into(),
             Command::ListBuckets => EMPTY_PAYLOAD_SHA.into(),
@@ -351,6 +524,7 @@ impl<'a> Command<'a> {
             Command::DeleteBucketLifecycle { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::CopyObject { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::UploadPart { .. } => EMPTY_PAYLOAD_SHA.into(),
+            Command::UploadPartCopy { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::InitiateMultipartUpload { .. } => EMPTY_PAYLOAD_SHA.into(),
             Command::GetObjectAttributes { .. } => EMPTY_PAYLOAD_SHA.into(),
         };
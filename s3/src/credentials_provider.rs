@@ -0,0 +1,129 @@
+//! Credentials loaded on first use.
+//!
+//! `Bucket::new` takes credentials that are already resolved, which for instance profiles or
+//! web identities means a round trip before the application is up. A bucket made with
+//! `Bucket::new_with_credentials_provider` instead asks its [`CredentialsProvider`] the first
+//! time a request needs signing, and again whenever the loaded credentials have expired. The
+//! credentials are shared by every clone of the bucket, so they are loaded once.
+//!
+//! [`ProfileCredentialsProvider`] reads a credentials file again once a set time has passed,
+//! so keys rotated on disk take effect without calling `Bucket::credentials_refresh`.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use crate::creds::Credentials;
+use crate::error::S3Error;
+use crate::utils::{blocking, now_utc};
+
+#[maybe_async::maybe_async]
+pub trait CredentialsProvider: fmt::Debug + Send + Sync {
+    /// Resolve credentials, called on first use and after the previous ones expired
+    async fn load(&self) -> Result<Credentials, S3Error>;
+}
+
+/// The lookup of `Credentials::default`, environment, profile, then instance metadata. The
+/// async backends run it on their blocking thread pool.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultCredentialsProvider;
+
+#[maybe_async::maybe_async]
+impl CredentialsProvider for DefaultCredentialsProvider {
+    async fn load(&self) -> Result<Credentials, S3Error> {
+        Ok(blocking(Credentials::default).await?)
+    }
+}
+
+/// A profile of an AWS credentials file, read again every `ttl` so that keys rotated on disk,
+/// such as mounted secrets, take effect on their own. The async backends read it on their
+/// blocking thread pool.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use s3::bucket::Bucket;
+/// use s3::credentials_provider::ProfileCredentialsProvider;
+/// use s3::region::Region;
+///
+/// let provider = ProfileCredentialsProvider::new(Duration::from_secs(300))
+///     .with_path("/var/run/secrets/aws/credentials");
+/// let bucket = Bucket::new_with_credentials_provider("rust-s3-test", Region::UsEast1, provider)?;
+/// # Ok::<(), s3::error::S3Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProfileCredentialsProvider {
+    path: Option<PathBuf>,
+    profile: Option<String>,
+    ttl: Duration,
+}
+
+impl ProfileCredentialsProvider {
+    /// The `default` profile of `~/.aws/credentials`, read again every `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        ProfileCredentialsProvider {
+            path: None,
+            profile: None,
+            ttl,
+        }
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Read the credentials file at `path` rather than `~/.aws/credentials`
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+#[maybe_async::maybe_async]
+impl CredentialsProvider for ProfileCredentialsProvider {
+    async fn load(&self) -> Result<Credentials, S3Error> {
+        let path = self.path.clone();
+        let profile = self.profile.clone();
+        let mut credentials = blocking(move || match path {
+            Some(path) => Credentials::from_profile_file(path, profile.as_deref()),
+            None => Credentials::from_profile(profile.as_deref()),
+        })
+        .await?;
+        // Expiring the credentials is what makes the bucket load them again
+        credentials.expiration = Some((now_utc() + self.ttl).into());
+        Ok(credentials)
+    }
+}
+
+/// Provider of a bucket along with whether its credentials were loaded, shared by clones
+#[derive(Clone, Debug)]
+pub(crate) struct DeferredCredentials {
+    pub(crate) provider: Arc<dyn CredentialsProvider>,
+    loaded: Arc<AtomicBool>,
+}
+
+impl DeferredCredentials {
+    pub(crate) fn new(provider: Arc<dyn CredentialsProvider>) -> Self {
+        DeferredCredentials {
+            provider,
+            loaded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether `current` has to be loaded, or loaded again because it expired
+    pub(crate) fn needs_load(&self, current: &Credentials, now: OffsetDateTime) -> bool {
+        !self.loaded.load(Ordering::Acquire)
+            || current
+                .expiration
+                .is_some_and(|expiration| expiration.0 <= now)
+    }
+
+    pub(crate) fn set_loaded(&self) {
+        self.loaded.store(true, Ordering::Release)
+    }
+}
@@ -14,3 +14,22 @@ where
         ))),
     }
 }
+
+pub fn rfc3339_deserializer<'de, D>(d: D) -> Result<time::OffsetDateTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    time::OffsetDateTime::parse(&s, &time::format_description::well_known::Rfc3339)
+        .map_err(|e| D::Error::custom(format!("got {}, but expected an RFC 3339 date: {}", s, e)))
+}
+
+pub fn rfc3339_serializer<S>(date: &time::OffsetDateTime, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let date = date
+        .format(&time::format_description::well_known::Rfc3339)
+        .map_err(serde::ser::Error::custom)?;
+    s.serialize_str(&date)
+}
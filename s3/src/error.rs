@@ -1,5 +1,9 @@
 use thiserror::Error;
 
+/// A backend error that doesn't implement `std::error::Error` itself, boxed to keep it as the
+/// source of an `S3Error`
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum S3Error {
@@ -7,8 +11,43 @@ pub enum S3Error {
     Utf8(#[from] std::str::Utf8Error),
     #[error("Max expiration for presigned URLs is one week, or 604.800 seconds, got {0} instead")]
     MaxExpiry(u32),
+    #[error("Parts must be between 5 MiB and 5 GiB, got {0} bytes")]
+    InvalidPartSize(usize),
+    #[error("Pages hold between 1 and 1000 entries, got {0}")]
+    InvalidPageSize(usize),
+    #[error("Timeouts have to be longer than zero, got {0:?}")]
+    InvalidTimeout(std::time::Duration),
+    #[error("Byte range {start}..={end} ends before it starts")]
+    InvalidRange { start: u64, end: u64 },
+    #[error("a bucket pool needs at least one bucket")]
+    EmptyBucketPool,
+    #[error("no shard at index {shard}, the pool has {shards}")]
+    NoSuchShard { shard: usize, shards: usize },
+    #[error("stream was announced as {expected} bytes, {read} were read")]
+    StreamSizeMismatch { expected: u64, read: u64 },
+    #[error("Objects hold at most {max} bytes, got {size}")]
+    ObjectTooLarge { size: u64, max: u64 },
+    /// A key a `DeleteObjects` request reported it could not delete
+    #[error("{code}: {message}")]
+    KeyNotDeleted { code: String, message: String },
+    /// The `DeleteObjects` request for a key failed as a whole
+    #[error("batch request failed: {0}")]
+    BatchRequestFailed(String),
+    #[error("Uploads hold at most {0} parts")]
+    TooManyParts(u32),
     #[error("Got HTTP {0} with content '{1}'")]
     HttpFailWithBody(u16, String),
+    /// A failed request answered with an `<Error>` document, `code` is what to match on. The
+    /// identifiers are boxed to keep `S3Error` small.
+    #[error("Got HTTP {status} with {code}: {message}")]
+    AwsError {
+        status: u16,
+        code: String,
+        message: String,
+        request_id: Option<Box<str>>,
+        host_id: Option<Box<str>>,
+        resource: Option<Box<str>>,
+    },
     #[error("Http request returned a non 2** code")]
     HttpFail,
     #[error("aws-creds: {0}")]
@@ -21,7 +60,6 @@ pub enum S3Error {
     UrlParse(#[from] url::ParseError),
     #[error("io: {0}")]
     Io(#[from] std::io::Error),
-    #[cfg(feature = "with-tokio")]
     #[error("http: {0}")]
     Http(#[from] http::Error),
     #[cfg(feature = "with-tokio")]
@@ -44,7 +82,7 @@ pub enum S3Error {
     InvalidHeaderName(#[from] http::header::InvalidHeaderName),
     #[cfg(feature = "with-async-std")]
     #[error("surf: {0}")]
-    Surf(String),
+    SurfClient(#[source] BoxError),
     #[cfg(feature = "sync")]
     #[error("attohttpc: {0}")]
     Atto(#[from] attohttpc::Error),
@@ -60,14 +98,237 @@ pub enum S3Error {
     RLCredentials,
     #[error("Time format error: {0}")]
     TimeFormatError(#[from] time::error::Format),
+    #[error("Time parse error: {0}")]
+    TimeParseError(#[from] time::error::Parse),
     #[error("fmt error: {0}")]
     FmtError(#[from] std::fmt::Error),
     #[error("serde error: {0}")]
     SerdeError(#[from] serde_json::Error),
     #[error("post policy error: {0}")]
     PostPolicyError(#[from] crate::post_policy::PostPolicyError),
+    #[error("presign error: {0}")]
+    PresignError(#[from] crate::presign::PresignError),
+    #[cfg(feature = "reports")]
+    #[error("report error: {0}")]
+    ReportError(#[from] crate::reports::ReportError),
+    #[error("invalid metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("object {0} already exists with another idempotency token")]
+    IdempotencyConflict(String),
+    #[error("object {0} changed while it was being read")]
+    ObjectChanged(String),
+    #[error("object key {0:?} has a . or .. segment, which URLs resolve away")]
+    DotSegmentKey(String),
+    #[error("invalid bucket name {name:?}: {reason}")]
+    InvalidBucketName { name: String, reason: &'static str },
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    #[error("presign commands sign a URL instead of sending a request")]
+    PresignCommand,
+    #[error("{0} is not supported by this provider")]
+    UnsupportedOperation(crate::capabilities::Operation),
+    #[error("not supported on directory buckets: {0}")]
+    DirectoryBucketUnsupported(&'static str),
+    #[error("Multi-Region Access Points need SigV4A signatures, enable the `sigv4a` feature")]
+    SigV4ARequired,
+    #[cfg(feature = "with-async-std")]
+    #[error("proxies are not supported by the async-std backend")]
+    ProxyUnsupported,
+    #[cfg(feature = "archive")]
+    #[error("archive error: {0}")]
+    ArchiveError(#[from] crate::archive::ArchiveError),
+    #[cfg(feature = "sigv4a")]
+    #[error("sigv4a: {0}")]
+    SigV4AError(#[from] p256::ecdsa::Error),
     #[error("Could not get read lock on credentials")]
     CredentialsReadLock,
     #[error("Could not get write lock on credentials")]
     CredentialsWriteLock,
 }
+
+impl S3Error {
+    /// Error of a request that failed with `status`, an `AwsError` when `body` is an `<Error>`
+    /// document and `HttpFailWithBody` otherwise, `HEAD` responses among them
+    pub fn from_response(status: u16, body: String) -> S3Error {
+        match quick_xml::de::from_str::<crate::serde_types::AwsError>(&body) {
+            Ok(error) => S3Error::AwsError {
+                status,
+                code: error.code,
+                message: error.message,
+                request_id: error.request_id.map(String::into_boxed_str),
+                host_id: error.host_id.map(String::into_boxed_str),
+                resource: error.resource.map(String::into_boxed_str),
+            },
+            Err(_) => S3Error::HttpFailWithBody(status, body),
+        }
+    }
+
+    /// HTTP status of a request that failed with a response
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            S3Error::HttpFailWithBody(status, _) | S3Error::AwsError { status, .. } => {
+                Some(*status)
+            }
+            _ => None,
+        }
+    }
+
+    /// Error code of an `AwsError`, `NoSuchKey` or `AccessDenied` for instance
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            S3Error::AwsError { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn is_no_such_key(&self) -> bool {
+        self.code() == Some("NoSuchKey")
+    }
+
+    pub fn is_access_denied(&self) -> bool {
+        self.code() == Some("AccessDenied")
+    }
+
+    /// Whether S3 asked to reduce the request rate
+    pub fn is_slow_down(&self) -> bool {
+        self.code() == Some("SlowDown")
+    }
+
+    /// Whether sending the request again may succeed: throttling and server errors, timeouts
+    /// and dropped connections, looked for along the whole source chain. The error code is
+    /// checked as well, for errors S3 reports in the body of a `200 OK`
+    pub fn is_transient(&self) -> bool {
+        if matches!(
+            self.code(),
+            Some("InternalError" | "SlowDown" | "ServiceUnavailable" | "RequestTimeout")
+        ) {
+            return true;
+        }
+        if let Some(status) = self.status_code() {
+            return crate::utils::is_transient_status(status);
+        }
+        #[cfg(feature = "with-tokio")]
+        if let S3Error::Reqwest(e) = self {
+            if e.is_timeout() || e.is_connect() {
+                return true;
+            }
+        }
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(error) = source {
+            if let Some(io) = error.downcast_ref::<std::io::Error>() {
+                use std::io::ErrorKind::*;
+                if matches!(
+                    io.kind(),
+                    TimedOut | ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof
+                ) {
+                    return true;
+                }
+            }
+            source = error.source();
+        }
+        false
+    }
+}
+
+#[cfg(feature = "with-async-std")]
+impl From<surf::Error> for S3Error {
+    fn from(e: surf::Error) -> Self {
+        S3Error::SurfClient(e.into_inner().into())
+    }
+}
+
+/// For readers and writers over objects, an `S3Error::Io` is unwrapped, anything else becomes
+/// the source of an `Other` error
+impl From<S3Error> for std::io::Error {
+    fn from(e: S3Error) -> Self {
+        match e {
+            S3Error::Io(e) => e,
+            e => std::io::Error::other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::S3Error;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn sources_are_kept() {
+        let reset = S3Error::Io(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+        assert!(reset.source().is_some());
+        assert!(reset.is_transient());
+
+        let wrapped = io::Error::from(S3Error::HttpFailWithBody(503, "SlowDown".to_string()));
+        assert_eq!(wrapped.kind(), io::ErrorKind::Other);
+        let inner = wrapped
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<S3Error>()
+            .unwrap();
+        assert!(inner.is_transient());
+
+        assert!(!S3Error::HttpFailWithBody(403, String::new()).is_transient());
+        assert!(!S3Error::DeadlineExceeded.is_transient());
+    }
+
+    #[test]
+    fn late_errors_are_transient() {
+        let late = |code: &str| {
+            S3Error::from_response(
+                200,
+                format!(
+                    "<Error><Code>{}</Code><Message>late</Message></Error>",
+                    code
+                ),
+            )
+        };
+        assert!(late("InternalError").is_transient());
+        assert!(late("SlowDown").is_transient());
+        assert!(!late("NoSuchUpload").is_transient());
+    }
+
+    #[test]
+    fn error_documents_are_parsed() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error>
+  <Code>NoSuchKey</Code>
+  <Message>The resource you requested does not exist</Message>
+  <Resource>/mybucket/myfoto.jpg</Resource>
+  <RequestId>4442587FB7D0A2F9</RequestId>
+</Error>"#;
+        let error = S3Error::from_response(404, body.to_string());
+        assert!(error.is_no_such_key());
+        assert!(!error.is_access_denied());
+        assert_eq!(error.status_code(), Some(404));
+        match error {
+            S3Error::AwsError {
+                message,
+                request_id,
+                host_id,
+                resource,
+                ..
+            } => {
+                assert_eq!(message, "The resource you requested does not exist");
+                assert_eq!(request_id.as_deref(), Some("4442587FB7D0A2F9"));
+                assert_eq!(host_id, None);
+                assert_eq!(resource.as_deref(), Some("/mybucket/myfoto.jpg"));
+            }
+            error => panic!("expected an AwsError, got {:?}", error),
+        }
+
+        let slow_down = S3Error::from_response(
+            503,
+            "<Error><Code>SlowDown</Code><Message>Please reduce your request rate.</Message></Error>"
+                .to_string(),
+        );
+        assert!(slow_down.is_slow_down());
+        assert!(slow_down.is_transient());
+
+        // HEAD responses carry no body
+        let head = S3Error::from_response(404, String::new());
+        assert!(matches!(head, S3Error::HttpFailWithBody(404, _)));
+        assert_eq!(head.code(), None);
+    }
+}
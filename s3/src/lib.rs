@@ -15,17 +15,35 @@ pub use bucket_ops::BucketConfiguration;
 pub use post_policy::{PostPolicy, PostPolicyChecksum, PostPolicyField, PostPolicyValue};
 pub use region::Region;
 
+#[cfg(feature = "archive")]
+pub mod archive;
 pub mod bucket;
 pub mod bucket_ops;
+pub mod bucket_pool;
+pub mod capabilities;
 pub mod command;
+pub mod credentials_provider;
 pub mod deserializer;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
 pub mod post_policy;
+pub mod presign;
+pub mod proxy;
+#[cfg(feature = "reports")]
+pub mod reports;
 pub mod serde_types;
+pub mod signer;
 pub mod signing;
+#[cfg(feature = "sigv4a")]
+pub mod sigv4a;
+#[cfg(feature = "test-support")]
+pub mod test_support;
 
 pub mod error;
 pub mod request;
 pub mod utils;
+#[cfg(feature = "writers")]
+pub mod writers;
 
 const LONG_DATETIME: &[time::format_description::FormatItem<'static>] =
     time::macros::format_description!("[year][month][day]T[hour][minute][second]Z");
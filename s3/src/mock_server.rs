@@ -0,0 +1,961 @@
+//! An in-memory S3 server for examples and tests that have no endpoint to talk to, behind the
+//! `mock-server` feature.
+//!
+//! [`MockServer::start`] listens on a free local port until it is dropped, buckets handed out by
+//! [`MockServer::bucket`] are path style and signed with throwaway credentials, which the server
+//! does not check. It keeps objects in memory and understands what the examples exercise:
+//!
+//! - `PutObject` and `GetObject`
+//! - multipart uploads
+//! - `ListObjectsV2` with prefix and continuation tokens
+//! - presigned POST form uploads
+//!
+//! Anything else is answered with `501 NotImplemented`. [`MockServer::fail_next`] answers the
+//! next requests with an error status, to see retries at work. [`MockServer::discard_content`]
+//! keeps only the size of what is uploaded, for benchmarks moving more than fits in memory.
+//!
+//! ```rust
+//! use s3::mock_server::MockServer;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), s3::error::S3Error> {
+//! let server = MockServer::start().await?;
+//! let bucket = server.bucket("rust-s3")?;
+//! bucket.put_object("/hello.txt", b"hello").await?;
+//! assert_eq!(bucket.get_object("/hello.txt").await?.as_slice(), b"hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use percent_encoding::percent_decode_str;
+use time::OffsetDateTime;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::bucket::Bucket;
+use crate::creds::Credentials;
+use crate::error::S3Error;
+use crate::region::Region;
+
+/// An S3 endpoint on `127.0.0.1` serving from memory, stopped when dropped
+#[derive(Debug)]
+pub struct MockServer {
+    endpoint: String,
+    state: Arc<Mutex<State>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Listen on a free local port, requires a tokio runtime
+    pub async fn start() -> Result<MockServer, S3Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State::default()));
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = shared.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, state).await {
+                        log::debug!("Mock server connection closed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(MockServer {
+            endpoint,
+            state,
+            task,
+        })
+    }
+
+    /// `http://127.0.0.1:<port>`
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Region pointing at the server
+    pub fn region(&self) -> Region {
+        Region::Custom {
+            region: "us-east-1".to_string(),
+            endpoint: self.endpoint.clone(),
+        }
+    }
+
+    /// Create bucket `name` on the server, if it does not exist yet, and a path style `Bucket`
+    /// for it
+    pub fn bucket(&self, name: &str) -> Result<Box<Bucket>, S3Error> {
+        self.state().buckets.entry(name.to_string()).or_default();
+        let credentials = Credentials::new(Some("mock"), Some("mock"), None, None, None)?;
+        Ok(Bucket::new(name, self.region(), credentials)?.with_path_style())
+    }
+
+    /// Answer the next `requests` requests with `status`, `503 SlowDown` and `500 InternalError`
+    /// are retried by the client, most others are not
+    pub fn fail_next(&self, requests: usize, status: u16) {
+        self.state().failures = (requests, status);
+    }
+
+    /// Requests served so far, injected failures included
+    pub fn requests(&self) -> usize {
+        self.state().requests
+    }
+
+    /// Throw away the content of uploads from now on, objects read back empty and carry a fixed
+    /// ETag. Spares the memory and hashing of large uploads when only the client is measured.
+    pub fn discard_content(&self) {
+        self.state().discard = true;
+    }
+
+    /// Multipart uploads initiated and neither completed nor aborted
+    pub fn open_uploads(&self) -> usize {
+        self.state().uploads.len()
+    }
+
+    /// Content of object `key` in `bucket`, without the leading `/`
+    pub fn object(&self, bucket: &str, key: &str) -> Option<Vec<u8>> {
+        let state = self.state();
+        let object = state.buckets.get(bucket)?.get(key)?;
+        Some(object.content.clone())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    buckets: HashMap<String, BTreeMap<String, StoredObject>>,
+    uploads: HashMap<String, Upload>,
+    next_upload: u64,
+    requests: usize,
+    /// Requests left to fail and the status they fail with
+    failures: (usize, u16),
+    /// Content of uploads is not kept, see `MockServer::discard_content`
+    discard: bool,
+}
+
+#[derive(Debug)]
+struct StoredObject {
+    content: Vec<u8>,
+    e_tag: String,
+    last_modified: OffsetDateTime,
+}
+
+#[derive(Debug)]
+struct Upload {
+    bucket: String,
+    key: String,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct MockRequest {
+    method: String,
+    bucket: String,
+    key: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn has_query(&self, name: &str) -> bool {
+        self.query.iter().any(|(key, _)| key == name)
+    }
+}
+
+#[derive(Debug)]
+struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    fn new(status: u16) -> Self {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn xml(status: u16, body: String) -> Self {
+        MockResponse::new(status)
+            .header("content-type", "application/xml")
+            .body(body.into_bytes())
+    }
+
+    fn error(status: u16, code: &str, message: &str) -> Self {
+        MockResponse::xml(
+            status,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>",
+                code,
+                escape(message)
+            ),
+        )
+    }
+
+    fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    while let Some(request) = read_request(&mut stream).await? {
+        let response = state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .handle(&request);
+        write_response(stream.get_mut(), response).await?;
+        if request.header("connection") == Some("close") {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Next request on the connection, `None` once the client closed it
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Option<MockRequest>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = decode(path.trim_start_matches('/'));
+    let (bucket, key) = path.split_once('/').unwrap_or((&path, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = MockRequest {
+        method,
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        query,
+        headers,
+        body: Vec::new(),
+    };
+    if request
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.contains("chunked"))
+    {
+        request.body = read_chunked(reader).await?;
+    } else if let Some(length) = request.header("content-length") {
+        let length = length.parse().map_err(std::io::Error::other)?;
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body).await?;
+    }
+    // Checksummed uploads frame the payload as chunks followed by a trailer
+    if request
+        .header("content-encoding")
+        .is_some_and(|encoding| encoding.contains("aws-chunked"))
+    {
+        request.body = read_chunked(&mut request.body.as_slice()).await?;
+    }
+    Ok(Some(request))
+}
+
+/// Payload of a `chunked` or `aws-chunked` body, chunk signatures and trailers are skipped
+async fn read_chunked<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let size = line.trim_end().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size.trim(), 16).map_err(std::io::Error::other)?;
+        if size == 0 {
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await?;
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf).await?;
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: MockResponse) -> std::io::Result<()> {
+    let reason = http::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if response.status != 204 {
+        head.push_str(&format!("content-length: {}\r\n", response.body.len()));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.flush().await
+}
+
+impl State {
+    fn handle(&mut self, request: &MockRequest) -> MockResponse {
+        self.requests += 1;
+        let response = if self.failures.0 > 0 {
+            self.failures.0 -= 1;
+            let code = match self.failures.1 {
+                503 => "SlowDown",
+                500 => "InternalError",
+                _ => "InjectedFailure",
+            };
+            MockResponse::error(self.failures.1, code, "Failure injected by the mock server")
+        } else if request.bucket.is_empty() {
+            MockResponse::error(501, "NotImplemented", "Bucket listings are not supported")
+        } else if request.key.is_empty() {
+            self.handle_bucket(request)
+        } else {
+            self.handle_object(request)
+        };
+        response.header("x-amz-request-id", format!("mock-{}", self.requests))
+    }
+
+    fn handle_bucket(&mut self, request: &MockRequest) -> MockResponse {
+        match request.method.as_str() {
+            "GET" if request.query("list-type") == Some("2") => self.list_objects(request),
+            "POST"
+                if request.header("content-type").is_some_and(|content_type| {
+                    content_type.starts_with("multipart/form-data")
+                }) =>
+            {
+                self.post_form(request)
+            }
+            _ => not_implemented(request),
+        }
+    }
+
+    fn handle_object(&mut self, request: &MockRequest) -> MockResponse {
+        if !self.buckets.contains_key(&request.bucket) {
+            return no_such_bucket();
+        }
+        match request.method.as_str() {
+            "PUT" if request.has_query("uploadId") => self.upload_part(request),
+            "PUT" if request.query.is_empty() => self.put_object(request),
+            "POST" if request.has_query("uploads") => self.initiate_upload(request),
+            "POST" if request.has_query("uploadId") => self.complete_upload(request),
+            "DELETE" if request.has_query("uploadId") => match self.take_upload(request) {
+                Ok(_) => MockResponse::new(204),
+                Err(response) => response,
+            },
+            "GET" if request.query.is_empty() => self.get_object(request),
+            _ => not_implemented(request),
+        }
+    }
+
+    fn object(&self, bucket: &str, key: &str) -> Option<&StoredObject> {
+        self.buckets.get(bucket)?.get(key)
+    }
+
+    /// Store `content` as object `key`, unless content is discarded
+    fn store(&mut self, bucket: &str, key: &str, content: Vec<u8>, e_tag: String) -> String {
+        let (content, e_tag) = if self.discard {
+            (Vec::new(), "\"discarded\"".to_string())
+        } else {
+            (content, e_tag)
+        };
+        self.buckets.entry(bucket.to_string()).or_default().insert(
+            key.to_string(),
+            StoredObject {
+                content,
+                e_tag: e_tag.clone(),
+                last_modified: OffsetDateTime::now_utc(),
+            },
+        );
+        e_tag
+    }
+
+    fn put_object(&mut self, request: &MockRequest) -> MockResponse {
+        let e_tag = self.e_tag(&request.body);
+        let e_tag = self.store(&request.bucket, &request.key, request.body.clone(), e_tag);
+        MockResponse::new(200).header("etag", e_tag)
+    }
+
+    fn get_object(&self, request: &MockRequest) -> MockResponse {
+        let Some(object) = self.object(&request.bucket, &request.key) else {
+            return no_such_key();
+        };
+        MockResponse::new(200)
+            .header("etag", object.e_tag.clone())
+            .header("content-type", "binary/octet-stream")
+            .body(object.content.clone())
+    }
+
+    /// Quoted MD5 of `content`, not computed while content is discarded
+    fn e_tag(&self, content: &[u8]) -> String {
+        if self.discard {
+            return "\"discarded\"".to_string();
+        }
+        format!("\"{:x}\"", md5::compute(content))
+    }
+
+    fn initiate_upload(&mut self, request: &MockRequest) -> MockResponse {
+        self.next_upload += 1;
+        let upload_id = format!("mock-upload-{}", self.next_upload);
+        self.uploads.insert(
+            upload_id.clone(),
+            Upload {
+                bucket: request.bucket.clone(),
+                key: request.key.clone(),
+                parts: BTreeMap::new(),
+            },
+        );
+        MockResponse::xml(
+            200,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+                escape(&request.bucket),
+                escape(&request.key),
+                upload_id
+            ),
+        )
+    }
+
+    fn upload_part(&mut self, request: &MockRequest) -> MockResponse {
+        let Some(part_number) = request
+            .query("partNumber")
+            .and_then(|number| number.parse::<u32>().ok())
+        else {
+            return MockResponse::error(400, "InvalidArgument", "Missing partNumber");
+        };
+        let e_tag = self.e_tag(&request.body);
+        let content = if self.discard {
+            Vec::new()
+        } else {
+            request.body.clone()
+        };
+        let upload_id = request.query("uploadId").unwrap_or_default();
+        let Some(upload) = self.uploads.get_mut(upload_id) else {
+            return no_such_upload();
+        };
+        upload.parts.insert(part_number, content);
+        MockResponse::new(200).header("etag", e_tag)
+    }
+
+    fn complete_upload(&mut self, request: &MockRequest) -> MockResponse {
+        let upload = match self.take_upload(request) {
+            Ok(upload) => upload,
+            Err(response) => return response,
+        };
+        let body = String::from_utf8_lossy(&request.body);
+        let mut numbers: Vec<u32> = xml_values(&body, "PartNumber")
+            .iter()
+            .filter_map(|number| number.parse().ok())
+            .collect();
+        if numbers.is_empty() {
+            numbers = upload.parts.keys().copied().collect();
+        }
+        let mut content = Vec::new();
+        let mut digests = Vec::new();
+        for number in &numbers {
+            let Some(part) = upload.parts.get(number) else {
+                // The upload stays open for the client to complete or abort
+                let upload_id = request.query("uploadId").unwrap_or_default().to_string();
+                self.uploads.insert(upload_id, upload);
+                return MockResponse::error(400, "InvalidPart", "A part was not uploaded");
+            };
+            digests.extend_from_slice(&md5::compute(part).0);
+            content.extend_from_slice(part);
+        }
+        let e_tag = format!("\"{:x}-{}\"", md5::compute(&digests), numbers.len());
+        let e_tag = self.store(&upload.bucket, &upload.key, content, e_tag);
+        MockResponse::xml(
+            200,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<CompleteMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>",
+                escape(&upload.bucket),
+                escape(&upload.key),
+                escape(&e_tag)
+            ),
+        )
+    }
+
+    fn take_upload(&mut self, request: &MockRequest) -> Result<Upload, MockResponse> {
+        let upload_id = request.query("uploadId").unwrap_or_default();
+        match self.uploads.remove(upload_id) {
+            Some(upload) if upload.bucket == request.bucket && upload.key == request.key => {
+                Ok(upload)
+            }
+            Some(upload) => {
+                self.uploads.insert(upload_id.to_string(), upload);
+                Err(no_such_upload())
+            }
+            None => Err(no_such_upload()),
+        }
+    }
+
+    fn list_objects(&self, request: &MockRequest) -> MockResponse {
+        let Some(objects) = self.buckets.get(&request.bucket) else {
+            return no_such_bucket();
+        };
+        let prefix = request.query("prefix").unwrap_or_default();
+        let max_keys: usize = request
+            .query("max-keys")
+            .and_then(|max_keys| max_keys.parse().ok())
+            .unwrap_or(1000);
+        let after = request
+            .query("continuation-token")
+            .or_else(|| request.query("start-after"))
+            .unwrap_or_default();
+
+        let mut listed = objects
+            .range::<str, _>((std::ops::Bound::Excluded(after), std::ops::Bound::Unbounded))
+            .filter(|(key, _)| key.starts_with(prefix))
+            .peekable();
+        let mut contents = String::new();
+        let mut count = 0;
+        let mut last = "";
+        while count < max_keys {
+            let Some((key, object)) = listed.next() else {
+                break;
+            };
+            contents.push_str(&format!(
+                "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                escape(key),
+                rfc3339(object.last_modified),
+                escape(&object.e_tag),
+                object.content.len()
+            ));
+            count += 1;
+            last = key;
+        }
+        let truncated = listed.peek().is_some();
+
+        let mut body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{}</MaxKeys><IsTruncated>{}</IsTruncated>",
+            escape(&request.bucket),
+            escape(prefix),
+            count,
+            max_keys,
+            truncated
+        );
+        if let Some(token) = request.query("continuation-token") {
+            body.push_str(&format!(
+                "<ContinuationToken>{}</ContinuationToken>",
+                escape(token)
+            ));
+        }
+        if truncated {
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                escape(last)
+            ));
+        }
+        body.push_str(&contents);
+        body.push_str("</ListBucketResult>");
+        MockResponse::xml(200, body)
+    }
+
+    /// Browser form upload to a presigned POST, the policy and its signature are not checked
+    fn post_form(&mut self, request: &MockRequest) -> MockResponse {
+        let content_type = request.header("content-type").unwrap_or_default();
+        let Some(fields) = form_fields(content_type, &request.body) else {
+            return MockResponse::error(400, "MalformedPOSTRequest", "The body is not a form");
+        };
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|field| field.name.eq_ignore_ascii_case(name))
+        };
+        let (Some(key), Some(file)) = (field("key"), field("file")) else {
+            return MockResponse::error(
+                400,
+                "InvalidArgument",
+                "The form needs a key and a file field",
+            );
+        };
+        let key = String::from_utf8_lossy(&key.value)
+            .replace("${filename}", file.filename.as_deref().unwrap_or_default());
+        let e_tag = self.e_tag(&file.value);
+        let e_tag = self.store(&request.bucket, &key, file.value.clone(), e_tag);
+        let status = field("success_action_status")
+            .and_then(|field| String::from_utf8_lossy(&field.value).parse().ok())
+            .filter(|status| matches!(status, 200 | 201))
+            .unwrap_or(204);
+        MockResponse::new(status)
+            .header("etag", e_tag)
+            .header("location", format!("/{}/{}", request.bucket, key))
+    }
+}
+
+/// A field of a `multipart/form-data` body
+struct FormField {
+    name: String,
+    filename: Option<String>,
+    value: Vec<u8>,
+}
+
+fn form_fields(content_type: &str, body: &[u8]) -> Option<Vec<FormField>> {
+    let boundary = content_type.split_once("boundary=")?.1.trim_matches('"');
+    let delimiter = format!("--{}", boundary);
+    let mut fields = Vec::new();
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    loop {
+        if rest.starts_with(b"--") {
+            return Some(fields);
+        }
+        let end = find(rest, delimiter.as_bytes())?;
+        let part = rest[..end].strip_prefix(b"\r\n")?;
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        let split = find(part, b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let disposition = headers
+            .lines()
+            .find(|line| line.to_ascii_lowercase().starts_with("content-disposition"))?;
+        let parameter = |name: &str| {
+            disposition.split(';').find_map(|parameter| {
+                let (key, value) = parameter.trim().split_once('=')?;
+                (key == name).then(|| value.trim_matches('"').to_string())
+            })
+        };
+        fields.push(FormField {
+            name: parameter("name")?,
+            filename: parameter("filename"),
+            value: part[split + 4..].to_vec(),
+        });
+        rest = &rest[end + delimiter.len()..];
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Text of every `<tag>` element in `xml`
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        values.push(unescape(&rest[..end]));
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+fn rfc3339(at: OffsetDateTime) -> String {
+    at.format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+fn no_such_bucket() -> MockResponse {
+    MockResponse::error(404, "NoSuchBucket", "The specified bucket does not exist")
+}
+
+fn no_such_key() -> MockResponse {
+    MockResponse::error(404, "NoSuchKey", "The specified key does not exist")
+}
+
+fn no_such_upload() -> MockResponse {
+    MockResponse::error(404, "NoSuchUpload", "The specified upload does not exist")
+}
+
+fn not_implemented(request: &MockRequest) -> MockResponse {
+    MockResponse::error(
+        501,
+        "NotImplemented",
+        &format!(
+            "{} /{}/{} is not supported",
+            request.method, request.bucket, request.key
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{form_fields, MockServer};
+    use crate::utils::{
+        AdaptiveUploadConfig, PartSize, PutStreamOptions, UploadEvent, UploadEvents,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn form_bodies() {
+        let body = b"--xyz\r\nContent-Disposition: form-data; name=\"key\"\r\n\r\nup/${filename}\r\n--xyz\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--xyz--\r\n";
+        let fields = form_fields("multipart/form-data; boundary=xyz", body).unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].name, "key");
+        assert_eq!(fields[0].value, b"up/${filename}");
+        assert_eq!(fields[1].filename.as_deref(), Some("a.txt"));
+        assert_eq!(fields[1].value, b"hello");
+    }
+
+    #[tokio::test]
+    async fn objects_round_trip() {
+        let server = MockServer::start().await.unwrap();
+        let bucket = server.bucket("rust-s3").unwrap();
+
+        let response = bucket.put_object("/dir/a b.txt", b"hello").await.unwrap();
+        assert_eq!(response.status_code(), 200);
+        bucket.put_object("/other.txt", b"other").await.unwrap();
+        assert_eq!(server.object("rust-s3", "dir/a b.txt").unwrap(), b"hello");
+        let response = bucket.get_object("/dir/a b.txt").await.unwrap();
+        assert_eq!(response.as_slice(), b"hello");
+
+        let pages = bucket.list("dir/".to_string(), None).await.unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].contents.len(), 1);
+        assert_eq!(pages[0].contents[0].key, "dir/a b.txt");
+        assert_eq!(pages[0].contents[0].size, 5);
+    }
+
+    #[tokio::test]
+    async fn multipart_uploads_are_assembled() {
+        let server = MockServer::start().await.unwrap();
+        let bucket = server.bucket("rust-s3").unwrap();
+        let content: Vec<u8> = (0..11 * 1024 * 1024).map(|i| i as u8).collect();
+
+        let options = PutStreamOptions {
+            multipart_threshold: 5 * 1024 * 1024,
+            part_size: PartSize::mib(5),
+            ..Default::default()
+        };
+        bucket
+            .put_object_stream_with_options(&mut content.as_slice(), "/big.bin", options)
+            .await
+            .unwrap();
+        assert_eq!(server.object("rust-s3", "big.bin").unwrap(), content);
+        let pages = bucket.list("big".to_string(), None).await.unwrap();
+        let e_tag = pages[0].contents[0].e_tag.clone().unwrap();
+        assert!(e_tag.ends_with("-3\""), "{}", e_tag);
+    }
+
+    #[tokio::test]
+    async fn discarded_content_is_not_kept() {
+        let server = MockServer::start().await.unwrap();
+        let bucket = server.bucket("rust-s3").unwrap();
+        server.discard_content();
+
+        let content = vec![1u8; 11 * 1024 * 1024];
+        bucket
+            .put_object_stream(&mut content.as_slice(), "/big.bin")
+            .await
+            .unwrap();
+        assert_eq!(server.object("rust-s3", "big.bin").unwrap(), b"");
+        assert_eq!(server.open_uploads(), 0);
+    }
+
+    #[tokio::test]
+    async fn stream_uploads_report_progress() {
+        let server = MockServer::start().await.unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let bucket = server
+            .bucket("rust-s3")
+            .unwrap()
+            .with_upload_events(UploadEvents::new(move |event| {
+                sink.lock().unwrap().push(event)
+            }));
+        let content: Vec<u8> = (0..11 * 1024 * 1024).map(|i| i as u8).collect();
+        let chunks = content
+            .chunks(1024 * 1024)
+            .map(|chunk| Ok::<_, std::io::Error>(bytes::Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+
+        let options = PutStreamOptions {
+            multipart_threshold: 5 * 1024 * 1024,
+            part_size: PartSize::mib(5),
+            ..Default::default()
+        };
+        bucket
+            .put_object_stream_from_stream("/stream.bin", futures::stream::iter(chunks), options)
+            .await
+            .unwrap();
+        assert_eq!(server.object("rust-s3", "stream.bin").unwrap(), content);
+
+        let events = events.lock().unwrap();
+        let started = events
+            .iter()
+            .filter(|event| matches!(event, UploadEvent::PartStarted { .. }))
+            .count();
+        let completed = events
+            .iter()
+            .filter(|event| matches!(event, UploadEvent::PartCompleted { .. }))
+            .count();
+        assert_eq!((started, completed), (3, 3));
+        assert_eq!(
+            events.last(),
+            Some(&UploadEvent::Completed {
+                key: "stream.bin".to_string(),
+                bytes: content.len(),
+                parts: 3,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn adaptive_uploads_are_assembled() {
+        let server = MockServer::start().await.unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let bucket = server
+            .bucket("rust-s3")
+            .unwrap()
+            .with_upload_events(UploadEvents::new(move |event| {
+                sink.lock().unwrap().push(event)
+            }));
+        let content: Vec<u8> = (0..12 * 1024 * 1024).map(|i| i as u8).collect();
+
+        let config = AdaptiveUploadConfig {
+            min_part_size: PartSize::mib(5),
+            ..Default::default()
+        };
+        bucket
+            .put_object_stream_adaptive(
+                &mut content.as_slice(),
+                "/adaptive.bin",
+                "text/plain",
+                config,
+            )
+            .await
+            .unwrap();
+        assert_eq!(server.object("rust-s3", "adaptive.bin").unwrap(), content);
+        let completed = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| matches!(event, UploadEvent::PartCompleted { .. }))
+            .count();
+        assert!(completed >= 2);
+    }
+
+    #[tokio::test]
+    async fn failed_checksum_uploads_are_aborted() {
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+        use tokio::io::{AsyncRead, ReadBuf};
+
+        struct Broken;
+
+        impl AsyncRead for Broken {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &mut ReadBuf<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Err(std::io::Error::other("broken")))
+            }
+        }
+
+        let server = MockServer::start().await.unwrap();
+        let bucket = server.bucket("rust-s3").unwrap();
+        let content: Vec<u8> = (0..crate::bucket::CHUNK_SIZE + 1)
+            .map(|i| i as u8)
+            .collect();
+
+        bucket
+            .put_object_stream_with_checksum(&mut content.as_slice(), "/ok.bin", "text/plain")
+            .await
+            .unwrap();
+        assert_eq!(server.object("rust-s3", "ok.bin").unwrap(), content);
+
+        // The first part is read, the reader fails on the second
+        let mut reader = tokio::io::AsyncReadExt::chain(content.as_slice(), Broken);
+        let result = bucket
+            .put_object_stream_with_checksum(&mut reader, "/broken.bin", "text/plain")
+            .await;
+        assert!(matches!(result, Err(crate::error::S3Error::Io(_))));
+        assert_eq!(server.open_uploads(), 0);
+        assert!(server.object("rust-s3", "broken.bin").is_none());
+    }
+
+    #[tokio::test]
+    async fn injected_failures_are_retried() {
+        let server = MockServer::start().await.unwrap();
+        let bucket = server.bucket("rust-s3").unwrap().with_retries(1);
+
+        server.fail_next(1, 503);
+        bucket.put_object("/retried.txt", b"hello").await.unwrap();
+        assert_eq!(server.requests(), 2);
+        assert_eq!(server.object("rust-s3", "retried.txt").unwrap(), b"hello");
+    }
+}
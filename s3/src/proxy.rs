@@ -0,0 +1,215 @@
+//! Proxy configuration shared by the HTTP backends.
+//!
+//! Unless a [`ProxyConfig`] is set on a `Bucket`, the standard `HTTP_PROXY`, `HTTPS_PROXY`,
+//! `ALL_PROXY` and `NO_PROXY` environment variables (or their lower-case forms) are honoured.
+//! `NO_PROXY` holds a comma separated list of hosts, a leading `.` or a bare domain matches
+//! its subdomains too, `*` disables proxying altogether. The blocking backend reads the
+//! environment once, when the `Bucket` is created. The `async-std` backend can't go through a
+//! proxy, it ignores the environment and `Bucket::with_proxy_config` fails there.
+
+use base64::engine::general_purpose;
+use base64::Engine;
+use url::Url;
+
+use crate::error::S3Error;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    http: Option<Url>,
+    https: Option<Url>,
+    no_proxy: Vec<String>,
+    basic_auth: Option<(String, String)>,
+}
+
+impl ProxyConfig {
+    /// Proxy both `http` and `https` endpoints through `proxy`
+    pub fn new(proxy: &str) -> Result<Self, S3Error> {
+        let proxy = Url::parse(proxy)?;
+        Ok(ProxyConfig {
+            http: Some(proxy.clone()),
+            https: Some(proxy),
+            ..Default::default()
+        })
+    }
+
+    /// Read the standard proxy environment variables
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        let parse = |value: Option<String>| value.and_then(|value| parse_proxy_url(&value));
+        let all = parse(var("ALL_PROXY"));
+        ProxyConfig {
+            http: parse(var("HTTP_PROXY")).or_else(|| all.clone()),
+            https: parse(var("HTTPS_PROXY")).or(all),
+            no_proxy: var("NO_PROXY")
+                .map(|no_proxy| split_no_proxy(&no_proxy))
+                .unwrap_or_default(),
+            basic_auth: None,
+        }
+    }
+
+    pub fn http_proxy(mut self, proxy: &str) -> Result<Self, S3Error> {
+        self.http = Some(Url::parse(proxy)?);
+        Ok(self)
+    }
+
+    pub fn https_proxy(mut self, proxy: &str) -> Result<Self, S3Error> {
+        self.https = Some(Url::parse(proxy)?);
+        Ok(self)
+    }
+
+    /// Bypass the proxy for `hosts`, in `NO_PROXY` format
+    pub fn no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy.extend(split_no_proxy(hosts));
+        self
+    }
+
+    /// Authenticate against the proxy, credentials in the proxy URL are used otherwise
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.basic_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none()
+    }
+
+    /// Proxy to connect to for `url`, with credentials set on it
+    pub fn proxy_for(&self, url: &Url) -> Option<Url> {
+        let proxy = match url.scheme() {
+            "https" => self.https.as_ref(),
+            _ => self.http.as_ref(),
+        }?;
+        if self.bypass(url) {
+            return None;
+        }
+        let mut proxy = proxy.clone();
+        if let Some((username, password)) = &self.basic_auth {
+            // Only fails for URLs that can't carry credentials, which can't be proxies either
+            proxy.set_username(username).ok()?;
+            proxy.set_password(Some(password)).ok()?;
+        }
+        Some(proxy)
+    }
+
+    /// Value of the `Proxy-Authorization` header for `proxy`
+    pub fn authorization(proxy: &Url) -> Option<String> {
+        if proxy.username().is_empty() {
+            return None;
+        }
+        let username = percent_encoding::percent_decode_str(proxy.username()).decode_utf8_lossy();
+        let password = percent_encoding::percent_decode_str(proxy.password().unwrap_or_default())
+            .decode_utf8_lossy();
+        let credentials = general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        Some(format!("Basic {}", credentials))
+    }
+
+    fn bypass(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+            None => return false,
+        };
+        let port = url.port_or_known_default();
+        self.no_proxy.iter().any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let (pattern, entry_port) = match entry.rsplit_once(':') {
+                Some((pattern, port)) if !pattern.contains(':') => (pattern, port.parse().ok()),
+                _ => (entry.as_str(), None),
+            };
+            if entry_port.is_some() && entry_port != port {
+                return false;
+            }
+            let domain = pattern.trim_start_matches('.');
+            host.eq_ignore_ascii_case(domain)
+                || (host.len() > domain.len()
+                    && host[host.len() - domain.len()..].eq_ignore_ascii_case(domain)
+                    && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+        })
+    }
+}
+
+// Keeps the proxy password out of logs
+impl std::fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |url: &Option<Url>| {
+            url.as_ref().map(|url| {
+                let mut url = url.clone();
+                let _ = url.set_password(None);
+                url.to_string()
+            })
+        };
+        f.debug_struct("ProxyConfig")
+            .field("http", &redact(&self.http))
+            .field("https", &redact(&self.https))
+            .field("no_proxy", &self.no_proxy)
+            .field(
+                "basic_auth",
+                &self.basic_auth.as_ref().map(|(user, _)| user),
+            )
+            .finish()
+    }
+}
+
+// Proxies are commonly given without a scheme, `proxy.internal:3128`
+fn parse_proxy_url(value: &str) -> Option<Url> {
+    Url::parse(value)
+        .ok()
+        .filter(|url| url.has_host())
+        .or_else(|| Url::parse(&format!("http://{}", value)).ok())
+}
+
+fn split_no_proxy(hosts: &str) -> Vec<String> {
+    hosts
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProxyConfig;
+    use url::Url;
+
+    #[test]
+    fn no_proxy_exclusions() {
+        let config = ProxyConfig::new("http://proxy.internal:3128")
+            .unwrap()
+            .no_proxy("localhost, .internal,minio.example.com:9000");
+        let proxy_for = |url: &str| config.proxy_for(&Url::parse(url).unwrap());
+
+        assert!(proxy_for("https://s3.amazonaws.com/bucket").is_some());
+        assert!(proxy_for("http://localhost:9000/bucket").is_none());
+        assert!(proxy_for("https://minio.internal/bucket").is_none());
+        assert!(proxy_for("https://notinternal/bucket").is_some());
+        assert!(proxy_for("http://minio.example.com:9000/bucket").is_none());
+        assert!(proxy_for("https://minio.example.com/bucket").is_some());
+
+        let config = config.no_proxy("*");
+        assert!(config
+            .proxy_for(&Url::parse("https://s3.amazonaws.com").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn proxy_basic_auth() {
+        let config = ProxyConfig::new("http://proxy.internal:3128")
+            .unwrap()
+            .basic_auth("user", "p@ss");
+        let proxy = config
+            .proxy_for(&Url::parse("https://s3.amazonaws.com").unwrap())
+            .unwrap();
+        assert_eq!(
+            ProxyConfig::authorization(&proxy).unwrap(),
+            "Basic dXNlcjpwQHNz"
+        );
+        assert!(!format!("{:?}", config).contains("p@ss"));
+    }
+}
@@ -0,0 +1,345 @@
+//! Readers for S3 Inventory and S3 Storage Lens exports.
+//!
+//! Both services deliver a `manifest.json` listing the data files of a report. Read it with
+//! `Bucket::get_inventory_manifest` or `Bucket::get_storage_lens_manifest`, then fetch and parse
+//! the files it lists with `Bucket::get_inventory_records` or `Bucket::get_storage_lens_records`.
+//! Gzip compressed files are inflated transparently. Only CSV reports are parsed, ORC and
+//! Parquet ones fail with [`ReportError::UnsupportedFormat`].
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use percent_encoding::percent_decode_str;
+use thiserror::Error;
+
+use crate::error::S3Error;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ReportError {
+    #[error("{0:?} reports can't be parsed, only CSV")]
+    UnsupportedFormat(ReportFormat),
+    #[error("unterminated quoted field on line {0}")]
+    MalformedCsv(usize),
+    #[error("invalid {column} on line {line}: {value}")]
+    InvalidValue {
+        column: String,
+        line: usize,
+        value: String,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[serde(rename = "CSV")]
+    Csv,
+    #[serde(rename = "ORC")]
+    Orc,
+    #[serde(rename = "Parquet")]
+    Parquet,
+}
+
+/// Data file of a report, relative to the destination bucket
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReportFile {
+    pub key: String,
+    pub size: u64,
+    #[serde(rename = "MD5checksum", alias = "md5Checksum")]
+    pub md5_checksum: String,
+}
+
+/// `manifest.json` of an S3 Inventory report
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryManifest {
+    pub source_bucket: String,
+    pub destination_bucket: String,
+    pub version: String,
+    pub creation_timestamp: String,
+    pub file_format: ReportFormat,
+    /// Comma separated field names of the CSV files, `Bucket, Key, Size, ...`
+    pub file_schema: String,
+    pub files: Vec<ReportFile>,
+}
+
+impl InventoryManifest {
+    /// Parse one of the report's `files`, gzip compressed or not
+    pub fn parse_records(&self, data: &[u8]) -> Result<Vec<InventoryRecord>, S3Error> {
+        let columns = csv_columns(self.file_format, &self.file_schema)?;
+        let mut records = Vec::new();
+        for (line, row) in parse_csv(&decompress(data)?)?.into_iter().enumerate() {
+            let mut fields: HashMap<String, String> = columns.iter().cloned().zip(row).collect();
+            let mut take = |column: &str| fields.remove(column).filter(|value| !value.is_empty());
+            let key = take("Key").unwrap_or_default();
+            let key = percent_decode_str(&key.replace('+', " "))
+                .decode_utf8_lossy()
+                .into_owned();
+            records.push(InventoryRecord {
+                bucket: take("Bucket").unwrap_or_default(),
+                key,
+                version_id: take("VersionId"),
+                is_latest: parse_field("IsLatest", take("IsLatest"), line)?,
+                is_delete_marker: parse_field("IsDeleteMarker", take("IsDeleteMarker"), line)?,
+                size: parse_field("Size", take("Size"), line)?,
+                last_modified_date: take("LastModifiedDate"),
+                e_tag: take("ETag"),
+                storage_class: take("StorageClass"),
+                other: fields,
+            });
+        }
+        Ok(records)
+    }
+}
+
+/// Object listed in an S3 Inventory report, fields missing from the report's schema are `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryRecord {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub is_latest: Option<bool>,
+    pub is_delete_marker: Option<bool>,
+    pub size: Option<u64>,
+    pub last_modified_date: Option<String>,
+    pub e_tag: Option<String>,
+    pub storage_class: Option<String>,
+    /// Remaining fields by schema name, `ReplicationStatus`, `EncryptionStatus`...
+    pub other: HashMap<String, String>,
+}
+
+/// `manifest.json` of an S3 Storage Lens metrics export
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageLensManifest {
+    pub source_account_id: String,
+    pub config_id: String,
+    pub destination_bucket: String,
+    pub report_version: String,
+    pub report_date: String,
+    pub report_format: ReportFormat,
+    /// Comma separated column names of the CSV files, `version_number,configuration_id,...`
+    pub report_schema: String,
+    pub report_files: Vec<ReportFile>,
+}
+
+impl StorageLensManifest {
+    /// Parse one of the export's `report_files`, gzip compressed or not
+    pub fn parse_records(&self, data: &[u8]) -> Result<Vec<StorageLensRecord>, S3Error> {
+        let columns = csv_columns(self.report_format, &self.report_schema)?;
+        let mut records = Vec::new();
+        for (line, row) in parse_csv(&decompress(data)?)?.into_iter().enumerate() {
+            // Some exports repeat the schema as a header row
+            if row == columns {
+                continue;
+            }
+            let mut fields: HashMap<String, String> = columns.iter().cloned().zip(row).collect();
+            let mut take = |column: &str| fields.remove(column).unwrap_or_default();
+            let metric_value = take("metric_value");
+            records.push(StorageLensRecord {
+                version_number: take("version_number"),
+                configuration_id: take("configuration_id"),
+                report_date: take("report_date"),
+                aws_account_number: take("aws_account_number"),
+                aws_region: take("aws_region"),
+                storage_class: take("storage_class"),
+                record_type: take("record_type"),
+                record_value: take("record_value"),
+                bucket_name: take("bucket_name"),
+                metric_name: take("metric_name"),
+                metric_value: parse_field("metric_value", Some(metric_value), line)?
+                    .unwrap_or_default(),
+            });
+        }
+        Ok(records)
+    }
+}
+
+/// A single metric of a Storage Lens export
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageLensRecord {
+    pub version_number: String,
+    pub configuration_id: String,
+    pub report_date: String,
+    pub aws_account_number: String,
+    pub aws_region: String,
+    pub storage_class: String,
+    pub record_type: String,
+    pub record_value: String,
+    pub bucket_name: String,
+    pub metric_name: String,
+    pub metric_value: f64,
+}
+
+fn csv_columns(format: ReportFormat, schema: &str) -> Result<Vec<String>, ReportError> {
+    if format != ReportFormat::Csv {
+        return Err(ReportError::UnsupportedFormat(format));
+    }
+    Ok(schema
+        .split(',')
+        .map(|column| column.trim().to_string())
+        .collect())
+}
+
+fn parse_field<T: std::str::FromStr>(
+    column: &str,
+    value: Option<String>,
+    line: usize,
+) -> Result<Option<T>, ReportError> {
+    match value {
+        Some(value) => match value.parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(_) => Err(ReportError::InvalidValue {
+                column: column.to_string(),
+                line: line + 1,
+                value,
+            }),
+        },
+        None => Ok(None),
+    }
+}
+
+// Report files are usually gzip compressed, recognized by the gzip magic number
+fn decompress(data: &[u8]) -> Result<String, S3Error> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut inflated = String::new();
+        flate2::read::MultiGzDecoder::new(data).read_to_string(&mut inflated)?;
+        Ok(inflated)
+    } else {
+        Ok(String::from_utf8(data.to_vec())?)
+    }
+}
+
+// RFC 4180, fields optionally quoted with `""` escaping a quote, blank lines are skipped
+fn parse_csv(data: &str) -> Result<Vec<Vec<String>>, ReportError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                line += 1;
+                if !row.is_empty() || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(ReportError::MalformedCsv(line));
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InventoryManifest, ReportFormat, StorageLensManifest};
+    use std::io::Write;
+
+    #[test]
+    fn inventory_report() {
+        let manifest: InventoryManifest = serde_json::from_str(
+            r#"{
+                "sourceBucket": "example-source-bucket",
+                "destinationBucket": "arn:aws:s3:::example-inventory-destination-bucket",
+                "version": "2016-11-30",
+                "creationTimestamp": "1514944800000",
+                "fileFormat": "CSV",
+                "fileSchema": "Bucket, Key, VersionId, IsLatest, IsDeleteMarker, Size, LastModifiedDate, ETag, StorageClass, ReplicationStatus",
+                "files": [
+                    {
+                        "key": "Inventory/example-source-bucket/2016-11-06T21-32Z/files/939c6d46-85a9-4ba8-aa8f-3a2e7a2c3c4a.csv.gz",
+                        "size": 2147483647,
+                        "MD5checksum": "f11166069f1990abeb9c97ace9cdfabc"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.file_format, ReportFormat::Csv);
+
+        let csv = "\"example-source-bucket\",\"photos/2017+summer%2C+beach.jpg\",\"\",\"true\",\"false\",\"1024\",\"2017-07-01T10:00:00.000Z\",\"d41d8cd98f00b204e9800998ecf8427e\",\"STANDARD\",\"\"\n\
+                   \"example-source-bucket\",\"notes.txt\",\"3HL4kqtJlcpXroDTDmJ+rmSpXd3dIbrHY\",\"false\",\"true\",\"\",\"2017-07-02T10:00:00.000Z\",\"\",\"GLACIER\",\"COMPLETED\"\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(csv.as_bytes()).unwrap();
+        let records = manifest.parse_records(&encoder.finish().unwrap()).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].key, "photos/2017 summer, beach.jpg");
+        assert_eq!(records[0].version_id, None);
+        assert_eq!(records[0].is_latest, Some(true));
+        assert_eq!(records[0].size, Some(1024));
+        assert_eq!(records[0].storage_class.as_deref(), Some("STANDARD"));
+        assert_eq!(records[1].is_delete_marker, Some(true));
+        assert_eq!(records[1].size, None);
+        assert_eq!(records[1].other["ReplicationStatus"], "COMPLETED");
+
+        let parquet = InventoryManifest {
+            file_format: ReportFormat::Parquet,
+            ..manifest
+        };
+        assert!(parquet.parse_records(csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn storage_lens_report() {
+        let manifest: StorageLensManifest = serde_json::from_str(
+            r#"{
+                "sourceAccountId": "123456789012",
+                "configId": "my-dashboard-configuration-id",
+                "destinationBucket": "arn:aws:s3:::destination-bucket",
+                "reportVersion": "V_1",
+                "reportDate": "2020-11-03",
+                "reportFormat": "CSV",
+                "reportSchema": "version_number,configuration_id,report_date,aws_account_number,aws_region,storage_class,record_type,record_value,bucket_name,metric_name,metric_value",
+                "reportFiles": [
+                    {
+                        "key": "DestinationPrefix/StorageLens/123456789012/my-dashboard-configuration-id/V_1/reports/dt=2020-11-03/a38f6bc4-2e3d-4355-ac8a-e2fdcf3de158.csv",
+                        "size": 1603959,
+                        "md5Checksum": "2177e775870def72b8d84febe1ad3574"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.report_files[0].md5_checksum,
+            "2177e775870def72b8d84febe1ad3574"
+        );
+
+        let csv = format!(
+            "{}\r\nV_1,my-dashboard-configuration-id,2020-11-03,123456789012,us-east-1,STANDARD,BUCKET,,example-bucket,StorageBytes,1024.5\r\n",
+            manifest.report_schema
+        );
+        let records = manifest.parse_records(csv.as_bytes()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].bucket_name, "example-bucket");
+        assert_eq!(records[0].record_value, "");
+        assert_eq!(records[0].metric_name, "StorageBytes");
+        assert_eq!(records[0].metric_value, 1024.5);
+
+        assert!(manifest
+            .parse_records(b"V_1,id,2020-11-03,1,us-east-1,STANDARD,BUCKET,,b,StorageBytes,many")
+            .is_err());
+    }
+}
@@ -1,24 +1,27 @@
 use async_std::io::Write as AsyncWrite;
 use async_std::io::{ReadExt, WriteExt};
-use async_std::stream::StreamExt;
 use bytes::Bytes;
-use futures::FutureExt;
-use std::collections::HashMap;
 
 use crate::bucket::Bucket;
 use crate::command::Command;
 use crate::error::S3Error;
-use crate::utils::now_utc;
 use time::OffsetDateTime;
 
 use crate::command::HttpMethod;
-use crate::request::{Request, ResponseData, ResponseDataStream};
+use crate::request::redirect::{is_redirect, redirected_bucket};
+use crate::request::{MaybeSend, Request, RequestOptions, ResponseData, ResponseDataStream};
+use crate::serde_types::HeadObjectResult;
 
 use http::HeaderMap;
 use maybe_async::maybe_async;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use surf::http::headers::{HeaderName, HeaderValue};
 use surf::http::Method;
 
+// Size of the reads backing `response_data_to_stream`
+const STREAM_READ_SIZE: usize = 64 * 1024;
+
 // Temporary structure for making a request
 pub struct SurfRequest<'a> {
     pub bucket: &'a Bucket,
@@ -26,9 +29,12 @@ pub struct SurfRequest<'a> {
     pub command: Command<'a>,
     pub datetime: OffsetDateTime,
     pub sync: bool,
+    /// Per request headers and query parameters, on top of the bucket's
+    pub options: Option<&'a RequestOptions>,
 }
 
-#[maybe_async]
+#[cfg_attr(not(feature = "no-send"), maybe_async)]
+#[cfg_attr(feature = "no-send", maybe_async(?Send))]
 impl<'a> Request for SurfRequest<'a> {
     type Response = surf::Response;
     type HeaderMap = HeaderMap;
@@ -37,8 +43,8 @@ impl<'a> Request for SurfRequest<'a> {
         self.datetime
     }
 
-    fn bucket(&self) -> Bucket {
-        self.bucket.clone()
+    fn bucket(&self) -> &Bucket {
+        self.bucket
     }
 
     fn command(&self) -> Command {
@@ -49,78 +55,76 @@ impl<'a> Request for SurfRequest<'a> {
         self.path.to_string()
     }
 
-    async fn response(&self) -> Result<surf::Response, S3Error> {
-        // Build headers
-        let headers = self.headers().await?;
-
-        let request = match self.command.http_verb() {
-            HttpMethod::Get => surf::Request::builder(Method::Get, self.url()?),
-            HttpMethod::Delete => surf::Request::builder(Method::Delete, self.url()?),
-            HttpMethod::Put => surf::Request::builder(Method::Put, self.url()?),
-            HttpMethod::Post => surf::Request::builder(Method::Post, self.url()?),
-            HttpMethod::Head => surf::Request::builder(Method::Head, self.url()?),
-        };
+    fn options(&self) -> Option<&RequestOptions> {
+        self.options
+    }
 
-        let mut request = request.body(self.request_body()?);
+    async fn response(&self) -> Result<surf::Response, S3Error> {
+        let mut response = self.execute().await?;
 
-        for (name, value) in headers.iter() {
-            request = request.header(
-                HeaderName::from_bytes(AsRef::<[u8]>::as_ref(&name).to_vec())
-                    .expect("Could not parse heaeder name"),
-                HeaderValue::from_bytes(AsRef::<[u8]>::as_ref(&value).to_vec())
-                    .expect("Could not parse header value"),
-            );
+        // Follow S3 redirects, signing each hop against the host it points to
+        let mut redirected: Option<Bucket> = None;
+        for _ in 0..self.bucket.max_redirects() {
+            if !is_redirect(response.status().into()) {
+                break;
+            }
+            let bucket = match redirected_bucket(
+                redirected.as_ref().unwrap_or(self.bucket),
+                response.header("location").map(|value| value.as_str()),
+                response
+                    .header("x-amz-bucket-region")
+                    .map(|value| value.as_str()),
+            ) {
+                Some(bucket) => redirected.insert(bucket),
+                None => break,
+            };
+            let request = SurfRequest {
+                datetime: bucket.now(),
+                bucket,
+                path: self.path,
+                command: self.command.clone(),
+                sync: self.sync,
+                options: self.options,
+            };
+            response = request.execute().await?;
         }
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| S3Error::Surf(e.to_string()))?;
+        self.record_response(response.status().into(), |name| {
+            response
+                .header(name)
+                .map(|values| values.as_str().to_string())
+        });
 
         if cfg!(feature = "fail-on-err") && !response.status().is_success() {
-            return Err(S3Error::HttpFail);
+            let status = response.status().into();
+            let text = response.body_string().await?;
+            return Err(S3Error::from_response(status, text));
         }
 
         Ok(response)
     }
 
     async fn response_data(&self, etag: bool) -> Result<ResponseData, S3Error> {
-        let mut response = crate::retry! {self.response().await}?;
-        let status_code = response.status();
-
-        let response_headers = response
-            .header_names()
-            .zip(response.header_values())
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect::<HashMap<String, String>>();
-
-        let body_vec = if etag {
-            if let Some(etag) = response.header("ETag") {
-                Bytes::from(etag.as_str().to_string())
-            } else {
-                Bytes::from("")
-            }
-        } else {
-            let body = match response.body_bytes().await {
-                Ok(bytes) => Ok(Bytes::from(bytes)),
-                Err(e) => Err(S3Error::Surf(e.to_string())),
-            };
-            body?
-        };
-        Ok(ResponseData::new(
-            body_vec,
-            status_code.into(),
-            response_headers,
-        ))
+        crate::retry!(
+            self.read_response_data(etag).await,
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )
     }
 
-    async fn response_data_to_writer<T: AsyncWrite + Send + Unpin + ?Sized>(
+    async fn response_data_to_writer<T: AsyncWrite + MaybeSend + Unpin + ?Sized>(
         &self,
         writer: &mut T,
     ) -> Result<u16, S3Error> {
         let mut buffer = Vec::new();
 
-        let response = crate::retry! {self.response().await}?;
+        let response = crate::retry!(
+            self.response().await,
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )?;
 
         let status_code = response.status();
 
@@ -134,41 +138,65 @@ impl<'a> Request for SurfRequest<'a> {
     }
 
     async fn response_header(&self) -> Result<(HeaderMap, u16), S3Error> {
-        let mut header_map = HeaderMap::new();
-        let response = crate::retry! {self.response().await}?;
+        let response = crate::retry!(
+            self.response().await,
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )?;
         let status_code = response.status();
-
-        for (name, value) in response.iter() {
-            header_map.insert(
-                http::header::HeaderName::from_lowercase(
-                    name.to_string().to_ascii_lowercase().as_ref(),
-                )?,
-                value.as_str().parse()?,
-            );
-        }
-        Ok((header_map, status_code.into()))
+        Ok((header_map(&response)?, status_code.into()))
     }
 
     async fn response_data_to_stream(&self) -> Result<ResponseDataStream, S3Error> {
-        let mut response = crate::retry! {self.response().await}?;
-        let status_code = response.status();
+        let response = crate::retry!(
+            self.response().await,
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )?;
+        body_stream(response)
+    }
+}
 
-        let body = response
-            .take_body()
-            .bytes()
-            .filter_map(|n| n.ok())
-            .fold(vec![], |mut b, n| {
-                b.push(n);
-                b
-            })
-            .then(|b| async move { Ok(Bytes::from(b)) })
-            .into_stream();
-
-        Ok(ResponseDataStream {
-            bytes: Box::pin(body),
-            status_code: status_code.into(),
-        })
+/// Stream the body of `response` as it is consumed rather than collecting it upfront
+fn body_stream(response: surf::Response) -> Result<ResponseDataStream, S3Error> {
+    let status_code = response.status();
+    let headers = HeadObjectResult::from(&header_map(&response)?);
+
+    let read_size = Arc::new(AtomicUsize::new(STREAM_READ_SIZE));
+    let body = futures::stream::unfold(Some((response, read_size.clone())), |state| async move {
+        let (mut response, read_size) = state?;
+        let mut buffer = vec![0; read_size.load(Ordering::Relaxed)];
+        match response.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok(Bytes::from(buffer)), Some((response, read_size))))
+            }
+            Err(e) => Some((Err(S3Error::Io(e)), None)),
+        }
+    });
+
+    Ok(
+        ResponseDataStream::new(Box::pin(body), status_code.into(), headers)
+            .with_read_size(read_size),
+    )
+}
+
+/// Headers of `response`, every value of a repeated header kept
+fn header_map(response: &surf::Response) -> Result<HeaderMap, S3Error> {
+    let mut header_map = HeaderMap::new();
+    for (name, values) in response.iter() {
+        let name = http::header::HeaderName::from_bytes(name.as_str().as_bytes())?;
+        for value in values.iter() {
+            header_map.append(
+                name.clone(),
+                http::HeaderValue::from_bytes(value.as_str().as_bytes())?,
+            );
+        }
     }
+    Ok(header_map)
 }
 
 impl<'a> SurfRequest<'a> {
@@ -177,21 +205,94 @@ impl<'a> SurfRequest<'a> {
         path: &'b str,
         command: Command<'b>,
     ) -> Result<SurfRequest<'b>, S3Error> {
+        bucket.capabilities().check(&command)?;
         bucket.credentials_refresh().await?;
         Ok(SurfRequest {
             bucket,
             path,
             command,
-            datetime: now_utc(),
+            datetime: bucket.now(),
             sync: false,
+            options: None,
         })
     }
+
+    /// Send the request once and read the body, or the ETag header when `etag` is set. Read
+    /// inside the retries, so that an error in the body of a `200 OK` is retried too.
+    async fn read_response_data(&self, etag: bool) -> Result<ResponseData, S3Error> {
+        let mut response = self.response().await?;
+        let status_code = response.status();
+
+        let response_headers = header_map(&response)?;
+
+        let body_vec = if etag {
+            if let Some(etag) = response.header("ETag") {
+                Bytes::from(etag.as_str().to_string())
+            } else {
+                Bytes::from("")
+            }
+        } else {
+            Bytes::from(response.body_bytes().await?)
+        };
+        self.error_in_body(ResponseData::new(
+            body_vec,
+            status_code.into(),
+            response_headers,
+        ))
+    }
+
+    /// Sign and send the request once, without redirect or error handling
+    async fn execute(&self) -> Result<surf::Response, S3Error> {
+        // Build headers
+        let headers = self.headers().await?;
+
+        let request = match self.command.http_verb() {
+            HttpMethod::Get => surf::Request::builder(Method::Get, self.url()?),
+            HttpMethod::Delete => surf::Request::builder(Method::Delete, self.url()?),
+            HttpMethod::Put => surf::Request::builder(Method::Put, self.url()?),
+            HttpMethod::Post => surf::Request::builder(Method::Post, self.url()?),
+            HttpMethod::Head => surf::Request::builder(Method::Head, self.url()?),
+        };
+
+        let body = self.request_body()?.into_bytes();
+        let len = body.len();
+        let mut request = request.body(surf::Body::from_reader(
+            async_std::io::Cursor::new(body),
+            Some(len),
+        ));
+
+        for (name, value) in headers.iter() {
+            request = request.header(
+                HeaderName::from_bytes(AsRef::<[u8]>::as_ref(&name).to_vec())
+                    .expect("Could not parse heaeder name"),
+                HeaderValue::from_bytes(AsRef::<[u8]>::as_ref(&value).to_vec())
+                    .expect("Could not parse header value"),
+            );
+        }
+
+        // In flight requests are cut short at the deadline
+        let response = match self.bucket.time_left() {
+            Some(time_left) => async_std::future::timeout(time_left, request.send())
+                .await
+                .map_err(|_| S3Error::DeadlineExceeded)?,
+            None => request.send().await,
+        };
+        match response {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                self.bucket.check_deadline()?;
+                Err(e.into())
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::bucket::Bucket;
     use crate::command::Command;
+    use crate::error::S3Error;
+    use crate::proxy::ProxyConfig;
     use crate::request::async_std_backend::SurfRequest;
     use crate::request::Request;
     use anyhow::Result;
@@ -205,6 +306,53 @@ mod tests {
         Credentials::new(Some(access_key), Some(secert_key), None, None, None).unwrap()
     }
 
+    #[test]
+    fn proxies_are_refused_when_configured() -> Result<()> {
+        let bucket = Bucket::new(
+            "my-first-bucket",
+            "custom-region".parse()?,
+            fake_credentials(),
+        )?;
+        let proxy_config = ProxyConfig::new("http://proxy.internal:3128")?;
+        assert!(matches!(
+            bucket.with_proxy_config(proxy_config),
+            Err(S3Error::ProxyUnsupported)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn header_map_keeps_repeated_headers() -> Result<()> {
+        let mut response = surf::http::Response::new(200);
+        response.append_header("x-amz-meta-tag", "a");
+        response.append_header("x-amz-meta-tag", "b");
+        let headers = super::header_map(&surf::Response::from(response))?;
+        let values = headers.get_all("x-amz-meta-tag").iter().collect::<Vec<_>>();
+        assert_eq!(values, ["a", "b"]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn stream_reads_at_most_max_chunk_size() -> Result<()> {
+        use async_std::stream::StreamExt;
+        use std::sync::atomic::Ordering;
+
+        let mut response = surf::http::Response::new(200);
+        response.set_body(vec![7u8; 10]);
+        let mut stream = super::body_stream(surf::Response::from(response))?.with_max_chunk_size(4);
+        let mut sizes = Vec::new();
+        while let Some(chunk) = stream.bytes().next().await {
+            sizes.push(chunk?.len());
+        }
+        assert_eq!(sizes, vec![4, 4, 2]);
+        let read_size = stream
+            .read_size
+            .as_ref()
+            .map(|size| size.load(Ordering::Relaxed));
+        assert_eq!(read_size, Some(4));
+        Ok(())
+    }
+
     #[async_std::test]
     async fn url_uses_https_by_default() -> Result<()> {
         let region = "custom-region".parse()?;
@@ -1,21 +1,23 @@
 extern crate base64;
+#[cfg(feature = "md5")]
 extern crate md5;
 
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use attohttpc::header::HeaderName;
 
 use crate::bucket::Bucket;
 use crate::command::Command;
 use crate::error::S3Error;
-use crate::utils::now_utc;
 use bytes::Bytes;
-use std::collections::HashMap;
 use time::OffsetDateTime;
 
 use crate::command::HttpMethod;
-use crate::request::{Request, ResponseData};
+use crate::request::redirect::{is_redirect, redirected_bucket};
+use crate::request::{Request, RequestOptions, ResponseData};
+
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
 
 // Temporary structure for making a request
 pub struct AttoRequest<'a> {
@@ -24,6 +26,8 @@ pub struct AttoRequest<'a> {
     pub command: Command<'a>,
     pub datetime: OffsetDateTime,
     pub sync: bool,
+    /// Per request headers and query parameters, on top of the bucket's
+    pub options: Option<&'a RequestOptions>,
 }
 
 impl<'a> Request for AttoRequest<'a> {
@@ -34,8 +38,8 @@ impl<'a> Request for AttoRequest<'a> {
         self.datetime
     }
 
-    fn bucket(&self) -> Bucket {
-        self.bucket.clone()
+    fn bucket(&self) -> &Bucket {
+        self.bucket
     }
 
     fn command(&self) -> Command {
@@ -46,110 +50,221 @@ impl<'a> Request for AttoRequest<'a> {
         self.path.to_string()
     }
 
-    fn response(&self) -> Result<Self::Response, S3Error> {
-        // Build headers
-        let headers = match self.headers() {
-            Ok(headers) => headers,
-            Err(e) => return Err(e),
-        };
-
-        let mut session = attohttpc::Session::new();
+    fn options(&self) -> Option<&RequestOptions> {
+        self.options
+    }
 
-        for (name, value) in headers.iter() {
-            session.header(HeaderName::from_bytes(name.as_ref())?, value.to_str()?);
-        }
+    fn response(&self) -> Result<Self::Response, S3Error> {
+        let mut response = self.execute()?;
 
-        if let Some(timeout) = self.bucket.request_timeout {
-            session.timeout(timeout)
+        // Follow S3 redirects, signing each hop against the host it points to
+        let mut redirected: Option<Bucket> = None;
+        for _ in 0..self.bucket.max_redirects() {
+            if !is_redirect(response.status().as_u16()) {
+                break;
+            }
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            };
+            let bucket = match redirected_bucket(
+                redirected.as_ref().unwrap_or(self.bucket),
+                header("location"),
+                header("x-amz-bucket-region"),
+            ) {
+                Some(bucket) => redirected.insert(bucket),
+                None => break,
+            };
+            let request = AttoRequest {
+                datetime: bucket.now(),
+                bucket,
+                path: self.path,
+                command: self.command.clone(),
+                sync: self.sync,
+                options: self.options,
+            };
+            response = request.execute()?;
         }
 
-        let request = match self.command.http_verb() {
-            HttpMethod::Get => session.get(self.url()?),
-            HttpMethod::Delete => session.delete(self.url()?),
-            HttpMethod::Put => session.put(self.url()?),
-            HttpMethod::Post => session.post(self.url()?),
-            HttpMethod::Head => session.head(self.url()?),
-        };
-
-        let response = request.bytes(&self.request_body()?).send()?;
+        self.record_response(response.status().as_u16(), |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        });
 
         if cfg!(feature = "fail-on-err") && !response.status().is_success() {
             let status = response.status().as_u16();
             let text = response.text()?;
-            return Err(S3Error::HttpFailWithBody(status, text));
+            return Err(S3Error::from_response(status, text));
         }
 
         Ok(response)
     }
 
     fn response_data(&self, etag: bool) -> Result<ResponseData, S3Error> {
-        let response = crate::retry! {self.response()}?;
-        let status_code = response.status().as_u16();
-
-        let response_headers = response
-            .headers()
-            .iter()
-            .map(|(k, v)| {
-                (
-                    k.to_string(),
-                    v.to_str()
-                        .unwrap_or("could-not-decode-header-value")
-                        .to_string(),
-                )
-            })
-            .collect::<HashMap<String, String>>();
-
-        let body_vec = if etag {
-            if let Some(etag) = response.headers().get("ETag") {
-                Bytes::from(etag.to_str()?.to_string())
-            } else {
-                Bytes::from("")
-            }
-        } else {
-            Bytes::from(response.bytes()?)
-        };
-        Ok(ResponseData::new(body_vec, status_code, response_headers))
+        crate::retry!(
+            self.read_response_data(etag),
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )
     }
 
     fn response_data_to_writer<T: Write + ?Sized>(&self, writer: &mut T) -> Result<u16, S3Error> {
-        let mut response = crate::retry! {self.response()}?;
+        let mut response = crate::retry!(
+            self.response(),
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )?;
 
         let status_code = response.status();
-        io::copy(&mut response, writer)?;
+        write_body(&mut response, writer)?;
 
         Ok(status_code.as_u16())
     }
 
     fn response_header(&self) -> Result<(Self::HeaderMap, u16), S3Error> {
-        let response = crate::retry! {self.response()}?;
+        let response = crate::retry!(
+            self.response(),
+            |attempt, e| self.retrying(attempt, e),
+            self.max_retries(),
+            self.bucket.time_left()
+        )?;
         let status_code = response.status().as_u16();
         let headers = response.headers().clone();
         Ok((headers, status_code))
     }
 }
 
+/// Copy a response body to `writer` through a fixed size buffer, so that downloads of any size
+/// are written as they arrive without holding more than `STREAM_BUFFER_SIZE` bytes in memory.
+fn write_body<R: Read, W: Write + ?Sized>(body: &mut R, writer: &mut W) -> io::Result<u64> {
+    let mut buffer = vec![0; STREAM_BUFFER_SIZE];
+    let mut written = 0;
+    loop {
+        let read = match body.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        writer.write_all(&buffer[..read])?;
+        written += read as u64;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Proxy settings for a request to `url`, resolved with the same rules as the async backends
+pub(crate) fn proxy_settings(bucket: &Bucket, url: &url::Url) -> attohttpc::ProxySettings {
+    let builder = attohttpc::ProxySettings::builder();
+    match bucket.proxy_config().proxy_for(url) {
+        Some(proxy) => builder.http_proxy(proxy.clone()).https_proxy(proxy).build(),
+        None => builder.build(),
+    }
+}
+
 impl<'a> AttoRequest<'a> {
     pub fn new<'b>(
         bucket: &'b Bucket,
         path: &'b str,
         command: Command<'b>,
     ) -> Result<AttoRequest<'b>, S3Error> {
+        bucket.capabilities().check(&command)?;
         bucket.credentials_refresh()?;
         Ok(AttoRequest {
             bucket,
             path,
             command,
-            datetime: now_utc(),
+            datetime: bucket.now(),
             sync: false,
+            options: None,
         })
     }
+
+    /// Send the request once and read the body, or the ETag header when `etag` is set. Read
+    /// inside the retries, so that an error in the body of a `200 OK` is retried too.
+    fn read_response_data(&self, etag: bool) -> Result<ResponseData, S3Error> {
+        let response = self.response()?;
+        let status_code = response.status().as_u16();
+
+        // Rebuilt as an `http::HeaderMap`, which `ResponseData` keeps whatever the backend
+        let response_headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    http::HeaderName::from_bytes(name.as_str().as_bytes()).ok()?,
+                    http::HeaderValue::from_bytes(value.as_bytes()).ok()?,
+                ))
+            })
+            .collect::<http::HeaderMap>();
+
+        let body_vec = if etag {
+            if let Some(etag) = response.headers().get("ETag") {
+                Bytes::from(etag.to_str()?.to_string())
+            } else {
+                Bytes::from("")
+            }
+        } else {
+            Bytes::from(response.bytes()?)
+        };
+        self.error_in_body(ResponseData::new(body_vec, status_code, response_headers))
+    }
+
+    /// Sign and send the request once, without redirect or error handling
+    fn execute(&self) -> Result<attohttpc::Response, S3Error> {
+        // Build headers
+        let headers = self.headers()?;
+
+        let mut session = attohttpc::Session::new();
+        session.follow_redirects(false);
+
+        for (name, value) in headers.iter() {
+            session.header(HeaderName::from_bytes(name.as_ref())?, value.to_str()?);
+        }
+
+        // In flight requests are cut short at the deadline
+        let timeout = match (self.bucket.request_timeout, self.bucket.time_left()) {
+            (Some(timeout), Some(time_left)) => Some(timeout.min(time_left)),
+            (timeout, time_left) => timeout.or(time_left),
+        };
+        if let Some(timeout) = timeout {
+            session.timeout(timeout)
+        }
+
+        let url = self.url()?;
+        session.proxy_settings(proxy_settings(self.bucket, &url));
+
+        let request = match self.command.http_verb() {
+            HttpMethod::Get => session.get(url),
+            HttpMethod::Delete => session.delete(url),
+            HttpMethod::Put => session.put(url),
+            HttpMethod::Post => session.post(url),
+            HttpMethod::Head => session.head(url),
+        };
+
+        match request.bytes(&self.request_body()?).send() {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                self.bucket.check_deadline()?;
+                Err(e.into())
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::bucket::Bucket;
     use crate::command::Command;
-    use crate::request::blocking::AttoRequest;
+    use crate::proxy::ProxyConfig;
+    use crate::request::blocking::{write_body, AttoRequest, STREAM_BUFFER_SIZE};
     use crate::request::Request;
     use anyhow::Result;
     use awscreds::Credentials;
@@ -162,6 +277,59 @@ mod tests {
         Credentials::new(Some(access_key), Some(secert_key), None, None, None).unwrap()
     }
 
+    #[test]
+    fn configured_proxy_replaces_the_environment() -> Result<()> {
+        let bucket = Bucket::new(
+            "my-first-bucket",
+            "custom-region".parse()?,
+            fake_credentials(),
+        )?
+        .with_proxy_config(ProxyConfig::new("http://proxy.internal:3128")?.no_proxy("localhost"))?;
+        let proxy = bucket
+            .proxy_config()
+            .proxy_for(&"https://my-first-bucket.custom-region/".parse()?);
+        assert_eq!(proxy, Some("http://proxy.internal:3128".parse()?));
+        assert_eq!(
+            bucket
+                .proxy_config()
+                .proxy_for(&"http://localhost/".parse()?),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn body_is_written_in_bounded_chunks() -> Result<()> {
+        struct Recorder {
+            written: usize,
+            largest_write: usize,
+        }
+
+        impl std::io::Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.written += buf.len();
+                self.largest_write = self.largest_write.max(buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let body = vec![7u8; 3 * STREAM_BUFFER_SIZE + 17];
+        let mut recorder = Recorder {
+            written: 0,
+            largest_write: 0,
+        };
+        let written = write_body(&mut body.as_slice(), &mut recorder)?;
+
+        assert_eq!(written as usize, body.len());
+        assert_eq!(recorder.written, body.len());
+        assert!(recorder.largest_write <= STREAM_BUFFER_SIZE);
+        Ok(())
+    }
+
     #[test]
     fn url_uses_https_by_default() -> Result<()> {
         let region = "custom-region".parse()?;
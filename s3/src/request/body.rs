@@ -0,0 +1,160 @@
+//! [`http_body::Body`] over an object stream, for proxying objects through hyper or axum.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use http::HeaderValue;
+use http_body::{Frame, SizeHint};
+
+use crate::error::S3Error;
+use crate::request::{DataStream, ResponseDataStream};
+
+/// Body of an object as it is read from s3.
+///
+/// Chunks are handed on as they arrive without copying, and the next one is only read once the
+/// consumer polls for it, so a slow client holds back the read from s3 instead of buffering the
+/// object. When `Content-Length` is known it is reported as an exact size hint.
+pub struct ResponseBody {
+    bytes: DataStream,
+    remaining: Option<u64>,
+}
+
+impl ResponseBody {
+    /// Bytes left to be read, if `Content-Length` was known
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+}
+
+impl http_body::Body for ResponseBody {
+    type Data = Bytes;
+    type Error = S3Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, S3Error>>> {
+        match self.bytes.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(remaining) = self.remaining.as_mut() {
+                    *remaining = remaining.saturating_sub(chunk.len() as u64);
+                }
+                Poll::Ready(Some(Ok(Frame::data(chunk))))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.remaining {
+            Some(remaining) => SizeHint::with_exact(remaining),
+            None => SizeHint::default(),
+        }
+    }
+}
+
+impl ResponseDataStream {
+    /// Body of the object, sized by its `Content-Length`
+    pub fn into_body(self) -> ResponseBody {
+        ResponseBody {
+            remaining: self
+                .headers
+                .content_length
+                .and_then(|length| u64::try_from(length).ok()),
+            bytes: self.bytes,
+        }
+    }
+
+    /// Response to hand to a client, with the status of the s3 response and its
+    /// `Content-Length`, `Content-Type`, `ETag` and `Last-Modified` headers passed through.
+    ///
+    /// # Example:
+    ///
+    /// ```rust,no_run
+    /// use s3::bucket::Bucket;
+    /// use s3::creds::Credentials;
+    /// use anyhow::Result;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    ///
+    /// let bucket_name = "rust-s3-test";
+    /// let region = "us-east-1".parse()?;
+    /// let credentials = Credentials::default()?;
+    /// let bucket = Bucket::new(bucket_name, region, credentials)?;
+    ///
+    /// // Return `response` from a hyper service or an axum handler
+    /// let response = bucket.get_object_stream("/test.file").await?.into_http_response()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_http_response(self) -> Result<http::Response<ResponseBody>, S3Error> {
+        let mut response = http::Response::builder().status(self.status_code);
+        let headers = &self.headers;
+        if let Some(length) = headers.content_length {
+            response = response.header(CONTENT_LENGTH, length);
+        }
+        let passed_through = [
+            (CONTENT_TYPE, &headers.content_type),
+            (ETAG, &headers.e_tag),
+            (LAST_MODIFIED, &headers.last_modified),
+        ];
+        for (name, value) in passed_through {
+            if let Some(value) = value {
+                response = response.header(name, HeaderValue::from_str(value)?);
+            }
+        }
+        Ok(response.body(self.into_body())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::request::ResponseDataStream;
+    use crate::serde_types::HeadObjectResult;
+    use bytes::Bytes;
+    use http_body::Body;
+    use std::pin::Pin;
+
+    #[tokio::test]
+    async fn response_body_passes_headers_through() {
+        let chunks = vec![
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ];
+        let stream = ResponseDataStream::new(
+            Box::pin(futures::stream::iter(chunks)),
+            206,
+            HeadObjectResult {
+                content_length: Some(11),
+                content_type: Some("text/plain".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let response = stream.into_http_response().unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.headers()["content-length"], "11");
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert!(response.headers().get("etag").is_none());
+
+        let mut body = response.into_body();
+        assert_eq!(body.size_hint().exact(), Some(11));
+        let mut content = Vec::new();
+        while let Some(frame) =
+            futures::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await
+        {
+            content.extend_from_slice(&frame.unwrap().into_data().unwrap());
+        }
+        assert_eq!(content, b"hello world");
+        assert!(body.is_end_stream());
+    }
+}
@@ -2,8 +2,19 @@
 pub mod async_std_backend;
 #[cfg(feature = "sync")]
 pub mod blocking;
+#[cfg(all(
+    feature = "body",
+    any(feature = "with-tokio", feature = "with-async-std")
+))]
+pub mod body;
+pub(crate) mod redirect;
 pub mod request_trait;
 #[cfg(feature = "with-tokio")]
 pub mod tokio_backend;
 
+#[cfg(all(
+    feature = "body",
+    any(feature = "with-tokio", feature = "with-async-std")
+))]
+pub use body::ResponseBody;
 pub use request_trait::*;
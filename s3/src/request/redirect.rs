@@ -0,0 +1,124 @@
+//! S3 redirects, followed by every backend. The HTTP clients never follow them, as that would
+//! replay a signature computed for the original host, each hop is signed again instead.
+
+use crate::bucket::Bucket;
+use crate::region::Region;
+
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 3;
+
+pub(crate) fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 307 | 308)
+}
+
+/// Bucket to re-sign a redirected request against, from the `Location` and
+/// `x-amz-bucket-region` headers of the redirect. Freshly created buckets are answered with
+/// 307 pointing at the regional endpoint, requests sent to the wrong region with 301 and the
+/// `x-amz-bucket-region` header.
+pub(crate) fn redirected_bucket(
+    bucket: &Bucket,
+    location: Option<&str>,
+    bucket_region: Option<&str>,
+) -> Option<Bucket> {
+    let mut redirected = bucket.clone();
+    let location = location.and_then(|location| url::Url::parse(location).ok());
+
+    if let Some(location) = location {
+        let host = location.host_str()?;
+        let host = if bucket.is_path_style() {
+            host
+        } else if let Some(host) = host.strip_prefix(&format!("{}.", bucket.name)) {
+            host
+        } else {
+            redirected.set_path_style();
+            host
+        };
+        let endpoint = match location.port() {
+            Some(port) => format!("{}://{}:{}", location.scheme(), host, port),
+            None => format!("{}://{}", location.scheme(), host),
+        };
+        let region = bucket_region
+            .or_else(|| aws_region_from_host(host))
+            .map(|region| region.to_string())
+            .unwrap_or_else(|| bucket.region.to_string());
+        redirected.region = Region::Custom { region, endpoint };
+    } else if let Region::Custom { region, endpoint } = &bucket.region {
+        // Custom endpoints serve every region themselves, only the signing region changes
+        let bucket_region = bucket_region?;
+        if bucket_region == region {
+            return None;
+        }
+        redirected.region = Region::Custom {
+            region: bucket_region.to_string(),
+            endpoint: endpoint.clone(),
+        };
+    } else {
+        let region: Region = bucket_region?.parse().ok()?;
+        if region == bucket.region {
+            return None;
+        }
+        redirected.region = region;
+    }
+    Some(redirected)
+}
+
+/// Signing region of a regional AWS endpoint, `s3-us-west-2.amazonaws.com` or `s3.us-west-2.amazonaws.com`
+fn aws_region_from_host(host: &str) -> Option<&str> {
+    let region = host
+        .strip_suffix(".amazonaws.com")?
+        .strip_prefix("s3")?
+        .trim_start_matches(['-', '.']);
+    if region.is_empty() || region.contains('.') {
+        None
+    } else {
+        Some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_redirect, redirected_bucket};
+    use crate::bucket::Bucket;
+    use crate::creds::Credentials;
+    use crate::region::Region;
+
+    #[test]
+    fn redirect_is_resigned_against_location_host() {
+        let bucket = Bucket::new(
+            "my-bucket",
+            Region::UsEast1,
+            Credentials::anonymous().unwrap(),
+        )
+        .unwrap();
+
+        let location = "https://my-bucket.s3-us-west-2.amazonaws.com/my/path";
+        let redirected = redirected_bucket(&bucket, Some(location), None).unwrap();
+        assert_eq!(redirected.host(), "my-bucket.s3-us-west-2.amazonaws.com");
+        assert_eq!(redirected.region.to_string(), "us-west-2");
+
+        let redirected = redirected_bucket(&bucket, None, Some("eu-central-1")).unwrap();
+        assert_eq!(redirected.region, Region::EuCentral1);
+
+        assert!(redirected_bucket(&bucket, None, None).is_none());
+        assert!(is_redirect(307));
+        assert!(!is_redirect(304));
+    }
+
+    #[test]
+    fn custom_endpoint_is_kept_on_region_redirect() {
+        let region = Region::Custom {
+            region: "us-east-1".to_string(),
+            endpoint: "http://localhost:9000".to_string(),
+        };
+        let bucket = Bucket::new("my-bucket", region, Credentials::anonymous().unwrap()).unwrap();
+
+        let redirected = redirected_bucket(&bucket, None, Some("eu-central-1")).unwrap();
+        assert_eq!(
+            redirected.region,
+            Region::Custom {
+                region: "eu-central-1".to_string(),
+                endpoint: "http://localhost:9000".to_string(),
+            }
+        );
+        assert!(redirected_bucket(&bucket, None, Some("us-east-1")).is_none());
+    }
+}
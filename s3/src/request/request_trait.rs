@@ -1,24 +1,39 @@
+#[cfg(feature = "md5")]
 use base64::engine::general_purpose;
+#[cfg(feature = "md5")]
 use base64::Engine;
 use hmac::Mac;
-use quick_xml::se::to_string;
 use std::collections::HashMap;
 #[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
 use std::pin::Pin;
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+use std::sync::Arc;
 use time::format_description::well_known::Rfc2822;
 use time::OffsetDateTime;
 use url::Url;
 
-use crate::bucket::Bucket;
-use crate::command::Command;
+use crate::bucket::{Bucket, Query};
+use crate::command::{Command, Payload};
+use crate::creds::Credentials;
 use crate::error::S3Error;
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+use crate::serde_types::HeadObjectResult;
+use crate::serde_types::XmlDocument;
+use crate::signer::{SigV4, SigningRequest, SigningScope};
 use crate::signing;
+#[cfg(not(feature = "md5"))]
+use crate::utils::Crc32c;
+use crate::utils::{
+    aws_chunked_body, is_error_document, RequestRecord, UploadEvent, CRC32C_TRAILER,
+};
 use crate::LONG_DATETIME;
 use bytes::Bytes;
 use http::header::{
-    HeaderName, ACCEPT, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, DATE, HOST, RANGE,
+    HeaderName, ACCEPT, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, DATE, HOST, RANGE,
 };
-use http::HeaderMap;
+use http::{HeaderMap, HeaderValue};
 use std::fmt::Write as _;
 
 #[cfg(feature = "with-async-std")]
@@ -27,12 +42,45 @@ use async_std::stream::Stream;
 #[cfg(feature = "with-tokio")]
 use tokio_stream::Stream;
 
+/// Headers and query parameters for a single request, merged over the `Bucket`'s extra headers
+/// and query when the request is built. Per call values like trace ids go here rather than on
+/// a `Bucket` cloned with `with_extra_headers`.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    pub headers: HeaderMap,
+    pub query: Query,
+    /// Retries for this request alone, in place of the bucket's
+    pub retries: Option<u8>,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        RequestOptions::default()
+    }
+
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Add a query pair, a key added more than once is sent repeated
+    pub fn query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.append(key, value);
+        self
+    }
+
+    pub fn retries(mut self, retries: u8) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+}
+
 #[derive(Debug)]
 
 pub struct ResponseData {
     bytes: Bytes,
     status_code: u16,
-    headers: HashMap<String, String>,
+    headers: HeaderMap,
 }
 
 #[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
@@ -44,15 +92,156 @@ pub type StreamItem = Result<Bytes, S3Error>;
 pub struct ResponseDataStream {
     pub bytes: DataStream,
     pub status_code: u16,
+    pub(crate) headers: HeadObjectResult,
+    /// Size of the reads of backends that read the body into buffers of their own
+    pub(crate) read_size: Option<Arc<AtomicUsize>>,
 }
 
 #[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
 impl ResponseDataStream {
+    pub(crate) fn new(
+        bytes: DataStream,
+        status_code: u16,
+        headers: HeadObjectResult,
+    ) -> ResponseDataStream {
+        ResponseDataStream {
+            bytes,
+            status_code,
+            headers,
+            read_size: None,
+        }
+    }
+
+    /// Let `with_max_chunk_size` size the backend reads through `read_size`
+    #[cfg(feature = "with-async-std")]
+    pub(crate) fn with_read_size(mut self, read_size: Arc<AtomicUsize>) -> ResponseDataStream {
+        self.read_size = Some(read_size);
+        self
+    }
+
     pub fn bytes(&mut self) -> &mut DataStream {
         &mut self.bytes
     }
+
+    /// Parsed response headers, `Content-Length` and `ETag` among others
+    pub fn headers(&self) -> &HeadObjectResult {
+        &self.headers
+    }
+
+    /// Yield chunks of at most `max_chunk_size` bytes.
+    ///
+    /// Backends reading the body into buffers of their own (`async-std`) read at most that
+    /// much at a time, chunks handed over by the HTTP client (`tokio`) are split without
+    /// copying. As the stream is only read on demand a slow consumer holds back the network
+    /// read instead of buffering the body.
+    pub fn with_max_chunk_size(self, max_chunk_size: usize) -> ResponseDataStream {
+        use futures::StreamExt;
+
+        let max_chunk_size = max_chunk_size.max(1);
+        if let Some(read_size) = &self.read_size {
+            read_size.store(max_chunk_size, Ordering::Relaxed);
+        }
+        let bytes = self.bytes.flat_map(move |item| {
+            let chunks: Vec<StreamItem> = match item {
+                Ok(mut bytes) => {
+                    let mut chunks = Vec::with_capacity(bytes.len() / max_chunk_size + 1);
+                    while bytes.len() > max_chunk_size {
+                        chunks.push(Ok(bytes.split_to(max_chunk_size)));
+                    }
+                    chunks.push(Ok(bytes));
+                    chunks
+                }
+                Err(e) => vec![Err(e)],
+            };
+            futures::stream::iter(chunks)
+        });
+        ResponseDataStream {
+            bytes: Box::pin(bytes),
+            status_code: self.status_code,
+            headers: self.headers,
+            read_size: self.read_size,
+        }
+    }
+
+    /// Read the body through `tokio::io::AsyncRead`, stream errors surface as `io::Error`s
+    #[cfg(feature = "codec")]
+    pub fn into_async_read(self) -> impl tokio::io::AsyncRead + Send + Unpin {
+        use futures::TryStreamExt;
+
+        tokio_util::io::StreamReader::new(self.bytes.map_err(std::io::Error::from))
+    }
+
+    /// Decode the body into frames with any `tokio-util` decoder.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use s3::bucket::Bucket;
+    /// use s3::creds::Credentials;
+    /// use tokio_stream::StreamExt;
+    /// use tokio_util::codec::AnyDelimiterCodec;
+    /// use anyhow::Result;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// let bucket = Bucket::new("rust-s3-test", "us-east-1".parse()?, Credentials::default()?)?;
+    ///
+    /// let codec = AnyDelimiterCodec::new(b"\0".to_vec(), b"\0".to_vec());
+    /// let mut records = bucket.get_object_stream("/records.bin").await?.framed(codec);
+    /// while let Some(record) = records.next().await {
+    ///     println!("{:?}", record?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "codec")]
+    pub fn framed<D: tokio_util::codec::Decoder>(
+        self,
+        decoder: D,
+    ) -> tokio_util::codec::FramedRead<impl tokio::io::AsyncRead + Send + Unpin, D> {
+        tokio_util::codec::FramedRead::new(self.into_async_read(), decoder)
+    }
+
+    /// Lines of the body without their `\n` or `\r\n`, a line longer than `max_length`
+    /// bytes fails with `S3Error::Io` rather than being buffered whole.
+    #[cfg(feature = "codec")]
+    pub fn newline_delimited(self, max_length: usize) -> FrameStream<String> {
+        use futures::TryStreamExt;
+        use tokio_util::codec::{LinesCodec, LinesCodecError};
+
+        let lines = self
+            .framed(LinesCodec::new_with_max_length(max_length))
+            .map_err(move |e| match e {
+                LinesCodecError::Io(e) => S3Error::Io(e),
+                LinesCodecError::MaxLineLengthExceeded => S3Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line longer than {} bytes", max_length),
+                )),
+            });
+        Box::pin(lines)
+    }
+
+    /// Frames of the body as read by `codec`, `LengthDelimitedCodec::new()` reads frames
+    /// behind a 4 byte big endian length.
+    #[cfg(feature = "codec")]
+    pub fn length_delimited(
+        self,
+        codec: tokio_util::codec::LengthDelimitedCodec,
+    ) -> FrameStream<Bytes> {
+        use futures::TryStreamExt;
+
+        let frames = self
+            .framed(codec)
+            .map_ok(|frame| frame.freeze())
+            .map_err(S3Error::Io);
+        Box::pin(frames)
+    }
 }
 
+/// Frames decoded from a `ResponseDataStream`
+#[cfg(feature = "codec")]
+pub type FrameStream<T> = Pin<Box<dyn Stream<Item = Result<T, S3Error>> + Send>>;
+
 impl From<ResponseData> for Vec<u8> {
     fn from(data: ResponseData) -> Vec<u8> {
         data.to_vec()
@@ -60,7 +249,7 @@ impl From<ResponseData> for Vec<u8> {
 }
 
 impl ResponseData {
-    pub fn new(bytes: Bytes, status_code: u16, headers: HashMap<String, String>) -> ResponseData {
+    pub fn new(bytes: Bytes, status_code: u16, headers: HeaderMap) -> ResponseData {
         ResponseData {
             bytes,
             status_code,
@@ -100,8 +289,16 @@ impl ResponseData {
         std::str::from_utf8(self.as_slice()).map(|s| s.to_string())
     }
 
-    pub fn headers(&self) -> HashMap<String, String> {
-        self.headers.clone()
+    /// Response headers, looked up without regard to case and with repeated headers kept,
+    /// `get_all` returns every value of a header
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Value of the response header `name`, the first one if it was repeated. `None` for values
+    /// that aren't visible ASCII.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
     }
 }
 
@@ -119,7 +316,20 @@ impl fmt::Display for ResponseData {
     }
 }
 
-#[maybe_async::maybe_async]
+/// `Send` unless the `no-send` feature is on, for values such as writers that requests hold
+/// across awaits, so single threaded executors can pass `!Send` ones.
+#[cfg(not(feature = "no-send"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(feature = "no-send"))]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+#[cfg(feature = "no-send")]
+pub trait MaybeSend {}
+#[cfg(feature = "no-send")]
+impl<T: ?Sized> MaybeSend for T {}
+
+#[cfg_attr(not(feature = "no-send"), maybe_async::maybe_async)]
+#[cfg_attr(feature = "no-send", maybe_async::maybe_async(?Send))]
 pub trait Request {
     type Response;
     type HeaderMap;
@@ -127,12 +337,12 @@ pub trait Request {
     async fn response(&self) -> Result<Self::Response, S3Error>;
     async fn response_data(&self, etag: bool) -> Result<ResponseData, S3Error>;
     #[cfg(feature = "with-tokio")]
-    async fn response_data_to_writer<T: tokio::io::AsyncWrite + Send + Unpin + ?Sized>(
+    async fn response_data_to_writer<T: tokio::io::AsyncWrite + MaybeSend + Unpin + ?Sized>(
         &self,
         writer: &mut T,
     ) -> Result<u16, S3Error>;
     #[cfg(feature = "with-async-std")]
-    async fn response_data_to_writer<T: async_std::io::Write + Send + Unpin + ?Sized>(
+    async fn response_data_to_writer<T: async_std::io::Write + MaybeSend + Unpin + ?Sized>(
         &self,
         writer: &mut T,
     ) -> Result<u16, S3Error>;
@@ -145,9 +355,100 @@ pub trait Request {
     async fn response_data_to_stream(&self) -> Result<ResponseDataStream, S3Error>;
     async fn response_header(&self) -> Result<(Self::HeaderMap, u16), S3Error>;
     fn datetime(&self) -> OffsetDateTime;
-    fn bucket(&self) -> Bucket;
+    fn bucket(&self) -> &Bucket;
     fn command(&self) -> Command;
     fn path(&self) -> String;
+    fn options(&self) -> Option<&RequestOptions>;
+
+    /// Credentials the request is signed with, those of the current session on directory buckets
+    async fn signing_credentials(&self) -> Result<Credentials, S3Error> {
+        if let Command::CreateSession = self.command() {
+            return self.bucket().credentials().await;
+        }
+        self.bucket().session_credentials().await
+    }
+
+    /// The signed request as text, method and URL, then every header by name and the payload
+    /// hash, for snapshot tests of how each command is built
+    #[cfg(test)]
+    async fn render(&self) -> Result<String, S3Error> {
+        let headers = self.headers().await?;
+        let mut rendered = format!("{} {}\n", self.command().http_verb(), self.url()?);
+        let mut names: Vec<&str> = headers.keys().map(HeaderName::as_str).collect();
+        names.sort_unstable();
+        for name in names {
+            for value in headers.get_all(name) {
+                writeln!(rendered, "{}: {}", name, value.to_str()?)
+                    .expect("Could not write to rendered");
+            }
+        }
+        writeln!(rendered, "payload: {}", self.command().sha256()?)
+            .expect("Could not write to rendered");
+        Ok(rendered)
+    }
+
+    /// `CopyObject`, `UploadPartCopy` and `CompleteMultipartUpload` can fail after S3 has sent
+    /// `200 OK`, the error is then the body, an `<Error>` document turned into an `Err` here
+    fn error_in_body(&self, response_data: ResponseData) -> Result<ResponseData, S3Error> {
+        let fails_late = matches!(
+            self.command(),
+            Command::CopyObject { .. }
+                | Command::UploadPartCopy { .. }
+                | Command::CompleteMultipartUpload { .. }
+        );
+        if fails_late
+            && response_data.status_code() == 200
+            && is_error_document(response_data.as_slice())
+        {
+            return Err(S3Error::from_response(200, response_data.to_string()?));
+        }
+        Ok(response_data)
+    }
+
+    /// Retries allowed, those of the request options if set, else the bucket's
+    fn max_retries(&self) -> u8 {
+        self.options()
+            .and_then(|options| options.retries)
+            .unwrap_or_else(|| self.bucket().retries())
+    }
+
+    /// Count the retry on the bucket's request trace and report retried part uploads to the
+    /// bucket's upload events
+    fn retrying(&self, attempt: u64, error: &S3Error) {
+        self.bucket().record_retry();
+        if let Command::PutObject {
+            multipart: Some(multipart),
+            ..
+        } = self.command()
+        {
+            self.bucket().emit_upload_event(|| UploadEvent::Retrying {
+                key: self.path(),
+                number: multipart.part_number(),
+                attempt,
+                error: error.to_string(),
+            });
+        }
+    }
+
+    /// Add the response to the bucket's request trace, `header` looks up response headers
+    fn record_response(&self, status: u16, header: impl Fn(&str) -> Option<String>) {
+        self.bucket().record_request(|| RequestRecord {
+            method: self.command().http_verb(),
+            path: self.path(),
+            status,
+            request_id: header("x-amz-request-id"),
+            extended_request_id: header("x-amz-id-2"),
+            at: self.datetime(),
+        });
+    }
+
+    fn signing_service(&self) -> &'static str {
+        if self.bucket().is_directory_bucket() {
+            "s3express"
+        } else {
+            "s3"
+        }
+    }
 
     async fn signing_key(&self) -> Result<Vec<u8>, S3Error> {
         signing::signing_key(
@@ -157,34 +458,42 @@ pub trait Request {
                 .secret_key()
                 .await?
                 .expect("Secret key must be provided to sign headers, found None"),
-            &self.bucket().region(),
+            self.bucket().region_ref(),
             "s3",
         )
     }
 
-    fn request_body(&self) -> Result<Vec<u8>, S3Error> {
+    /// Body of the request, object content is borrowed from the command rather than copied
+    fn request_body(&self) -> Result<Payload<'_>, S3Error> {
         let result = if let Command::PutObject { content, .. } = self.command() {
-            Vec::from(content)
+            content
+        } else if let Command::PutObjectWithChecksum {
+            content, checksum, ..
+        } = self.command()
+        {
+            Payload::Owned(aws_chunked_body(content, &checksum).into())
         } else if let Command::PutObjectTagging { tags } = self.command() {
-            Vec::from(tags)
+            Payload::Borrowed(tags.as_bytes())
         } else if let Command::UploadPart { content, .. } = self.command() {
-            Vec::from(content)
+            Payload::Borrowed(content)
         } else if let Command::CompleteMultipartUpload { data, .. } = &self.command() {
-            let body = data.to_string();
-            body.as_bytes().to_vec()
+            Payload::Owned(data.to_string().into())
         } else if let Command::CreateBucket { config } = &self.command() {
             if let Some(payload) = config.location_constraint_payload() {
-                Vec::from(payload)
+                Payload::Owned(payload.into())
             } else {
-                Vec::new()
+                Payload::Owned(Bytes::new())
             }
         } else if let Command::PutBucketLifecycle { configuration, .. } = &self.command() {
-            quick_xml::se::to_string(configuration)?.as_bytes().to_vec()
+            Payload::Owned(configuration.to_xml()?.into())
         } else if let Command::PutBucketCors { configuration, .. } = &self.command() {
-            let cors = configuration.to_string();
-            cors.as_bytes().to_vec()
+            Payload::Owned(configuration.to_xml()?.into())
+        } else if let Command::DeleteObjects { request } = &self.command() {
+            Payload::Owned(request.to_xml()?.into())
+        } else if let Command::PutBucketVersioning { configuration, .. } = &self.command() {
+            Payload::Owned(configuration.to_xml()?.into())
         } else {
-            Vec::new()
+            Payload::Owned(Bytes::new())
         };
         Ok(result)
     }
@@ -194,7 +503,12 @@ pub trait Request {
     }
 
     fn string_to_sign(&self, request: &str) -> Result<String, S3Error> {
-        signing::string_to_sign(&self.datetime(), &self.bucket().region(), request)
+        signing::string_to_sign_for_service(
+            &self.datetime(),
+            self.bucket().region_ref(),
+            self.signing_service(),
+            request,
+        )
     }
 
     fn host_header(&self) -> String {
@@ -203,17 +517,29 @@ pub trait Request {
 
     #[maybe_async::async_impl]
     async fn presigned(&self) -> Result<String, S3Error> {
+        if self.bucket().is_directory_bucket() {
+            return Err(S3Error::DirectoryBucketUnsupported("presigned URLs"));
+        }
+        #[cfg(not(feature = "sigv4a"))]
+        if self.bucket().multi_region_access_point().is_some() {
+            return Err(S3Error::SigV4ARequired);
+        }
         let (expiry, custom_headers, custom_queries) = match self.command() {
             Command::PresignGet {
                 expiry_secs,
+                custom_headers,
                 custom_queries,
-            } => (expiry_secs, None, custom_queries),
+            } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignPut {
                 expiry_secs,
                 custom_headers,
                 custom_queries,
             } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignDelete { expiry_secs } => (expiry_secs, None, None),
+            Command::PresignPost {
+                expiry_secs,
+                custom_queries,
+            } => (expiry_secs, None, custom_queries),
             _ => unreachable!(),
         };
 
@@ -228,17 +554,29 @@ pub trait Request {
 
     #[maybe_async::sync_impl]
     async fn presigned(&self) -> Result<String, S3Error> {
+        if self.bucket().is_directory_bucket() {
+            return Err(S3Error::DirectoryBucketUnsupported("presigned URLs"));
+        }
+        #[cfg(not(feature = "sigv4a"))]
+        if self.bucket().multi_region_access_point().is_some() {
+            return Err(S3Error::SigV4ARequired);
+        }
         let (expiry, custom_headers, custom_queries) = match self.command() {
             Command::PresignGet {
                 expiry_secs,
+                custom_headers,
                 custom_queries,
-            } => (expiry_secs, None, custom_queries),
+            } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignPut {
                 expiry_secs,
                 custom_headers,
-                ..
-            } => (expiry_secs, custom_headers, None),
+                custom_queries,
+            } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignDelete { expiry_secs } => (expiry_secs, None, None),
+            Command::PresignPost {
+                expiry_secs,
+                custom_queries,
+            } => (expiry_secs, None, custom_queries),
             _ => unreachable!(),
         };
 
@@ -263,6 +601,14 @@ pub trait Request {
         }
         let canonical_request = self.presigned_canonical_request(&headers).await?;
         let string_to_sign = self.string_to_sign(&canonical_request)?;
+        if let Some(presign_signer) = self.bucket().presign_signer() {
+            let scope = SigningScope {
+                datetime: self.datetime(),
+                region: self.bucket().region_ref(),
+                service: "s3",
+            };
+            return presign_signer.sign(&scope, &string_to_sign).await;
+        }
         let mut hmac = signing::HmacSha256::new_from_slice(&self.signing_key().await?)?;
         hmac.update(string_to_sign.as_bytes());
         let signature = hex::encode(hmac.finalize().into_bytes());
@@ -274,14 +620,19 @@ pub trait Request {
         let (expiry, custom_headers, custom_queries) = match self.command() {
             Command::PresignGet {
                 expiry_secs,
+                custom_headers,
                 custom_queries,
-            } => (expiry_secs, None, custom_queries),
+            } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignPut {
                 expiry_secs,
                 custom_headers,
                 custom_queries,
             } => (expiry_secs, custom_headers, custom_queries),
             Command::PresignDelete { expiry_secs } => (expiry_secs, None, None),
+            Command::PresignPost {
+                expiry_secs,
+                custom_queries,
+            } => (expiry_secs, None, custom_queries),
             _ => unreachable!(),
         };
 
@@ -302,22 +653,21 @@ pub trait Request {
         custom_headers: Option<&HeaderMap>,
         custom_queries: Option<&HashMap<String, String>>,
     ) -> Result<Url, S3Error> {
-        let bucket = self.bucket();
-        let token = if let Some(security_token) = bucket.security_token().await? {
-            Some(security_token)
-        } else {
-            bucket.session_token().await?
-        };
+        let credentials = self.bucket().credentials().await?;
+        let token = credentials
+            .security_token
+            .as_ref()
+            .or(credentials.session_token.as_ref());
         let url = Url::parse(&format!(
             "{}{}{}",
             self.url()?,
             &signing::authorization_query_params_no_sig(
-                &self.bucket().access_key().await?.unwrap_or_default(),
+                credentials.access_key.as_deref().unwrap_or_default(),
                 &self.datetime(),
-                &self.bucket().region(),
+                self.bucket().region_ref(),
                 expiry,
                 custom_headers,
-                token.as_ref()
+                token
             )?,
             &signing::flatten_queries(custom_queries)?,
         ))?;
@@ -332,22 +682,21 @@ pub trait Request {
         custom_headers: Option<&HeaderMap>,
         custom_queries: Option<&HashMap<String, String>>,
     ) -> Result<Url, S3Error> {
-        let bucket = self.bucket();
-        let token = if let Some(security_token) = bucket.security_token()? {
-            Some(security_token)
-        } else {
-            bucket.session_token()?
-        };
+        let credentials = self.bucket().credentials()?;
+        let token = credentials
+            .security_token
+            .as_ref()
+            .or(credentials.session_token.as_ref());
         let url = Url::parse(&format!(
             "{}{}{}",
             self.url()?,
             &signing::authorization_query_params_no_sig(
-                &self.bucket().access_key()?.unwrap_or_default(),
+                credentials.access_key.as_deref().unwrap_or_default(),
                 &self.datetime(),
-                &self.bucket().region(),
+                self.bucket().region_ref(),
                 expiry,
                 custom_headers,
-                token.as_ref()
+                token
             )?,
             &signing::flatten_queries(custom_queries)?,
         ))?;
@@ -366,14 +715,13 @@ pub trait Request {
             return Ok(Url::parse(&url_str)?);
         }
 
-        let path = if self.path().starts_with('/') {
-            self.path()[1..].to_string()
-        } else {
-            self.path()[..].to_string()
-        };
+        let path = self.path();
+        if signing::has_dot_segment(&path) {
+            return Err(S3Error::DotSegmentKey(path));
+        }
 
         url_str.push('/');
-        url_str.push_str(&signing::uri_encode(&path, false));
+        url_str.push_str(&signing::encode_key(&path));
 
         // Append to url_path
         #[allow(clippy::collapsible_match)]
@@ -388,11 +736,16 @@ pub trait Request {
                 write!(url_str, "?uploadId={}", upload_id).expect("Could not write to url_str");
             }
             Command::GetObjectTorrent => url_str.push_str("?torrent"),
-            Command::PutObject { multipart, .. } => {
+            Command::CreateSession => url_str.push_str("?session"),
+            Command::PutObject { multipart, .. }
+            | Command::PutObjectWithChecksum { multipart, .. } => {
                 if let Some(multipart) = multipart {
                     url_str.push_str(&multipart.query_string())
                 }
             }
+            Command::UploadPartCopy { multipart, .. } => {
+                url_str.push_str(&multipart.query_string())
+            }
             Command::GetBucketLifecycle
             | Command::PutBucketLifecycle { .. }
             | Command::DeleteBucketLifecycle => {
@@ -403,8 +756,20 @@ pub trait Request {
             | Command::DeleteBucketCors { .. } => {
                 url_str.push_str("?cors");
             }
-            Command::GetObjectAttributes { version_id, .. } => {
-                if let Some(version_id) = version_id {
+            Command::PutBucketVersioning { .. } => url_str.push_str("?versioning"),
+            Command::DeleteObjects { .. } => url_str.push_str("?delete"),
+            Command::ListObjectVersions { .. } => url_str.push_str("?versions"),
+            Command::DeleteObjectVersion { version_id, .. }
+            | Command::GetObjectVersion { version_id } => {
+                write!(
+                    url_str,
+                    "?versionId={}",
+                    signing::uri_encode(&version_id, true)
+                )
+                .expect("Could not write to url_str");
+            }
+            Command::GetObjectAttributes { options, .. } => {
+                if let Some(version_id) = options.version_id {
                     url_str.push_str(&format!("?attributes&versionId={}", version_id));
                 } else {
                     url_str.push_str("?attributes&versionId=null");
@@ -422,6 +787,7 @@ pub trait Request {
             Command::PresignGet { .. } => {}
             Command::PresignPut { .. } => {}
             Command::PresignDelete { .. } => {}
+            Command::PresignPost { .. } => {}
             Command::DeleteBucket => {}
             Command::ListBuckets => {}
             Command::CopyObject { .. } => {}
@@ -432,8 +798,14 @@ pub trait Request {
 
         let mut url = Url::parse(&url_str)?;
 
-        for (key, value) in &self.bucket().extra_query {
-            url.query_pairs_mut().append_pair(key, value);
+        let request_query = self.options().map(|options| &options.query);
+        for (key, value) in self
+            .bucket()
+            .extra_query
+            .iter()
+            .chain(request_query.into_iter().flat_map(Query::iter))
+        {
+            signing::append_query(&mut url, key, value);
         }
 
         if let Command::ListObjectsV2 {
@@ -444,19 +816,20 @@ pub trait Request {
             max_keys,
         } = self.command().clone()
         {
-            let mut query_pairs = url.query_pairs_mut();
-            delimiter.map(|d| query_pairs.append_pair("delimiter", &d));
+            if let Some(delimiter) = delimiter {
+                signing::append_query(&mut url, "delimiter", &delimiter);
+            }
 
-            query_pairs.append_pair("prefix", &prefix);
-            query_pairs.append_pair("list-type", "2");
+            signing::append_query(&mut url, "prefix", &prefix);
+            signing::append_query(&mut url, "list-type", "2");
             if let Some(token) = continuation_token {
-                query_pairs.append_pair("continuation-token", &token);
+                signing::append_query(&mut url, "continuation-token", &token);
             }
             if let Some(start_after) = start_after {
-                query_pairs.append_pair("start-after", &start_after);
+                signing::append_query(&mut url, "start-after", &start_after);
             }
             if let Some(max_keys) = max_keys {
-                query_pairs.append_pair("max-keys", &max_keys.to_string());
+                signing::append_query(&mut url, "max-keys", &max_keys.to_string());
             }
         }
 
@@ -467,15 +840,40 @@ pub trait Request {
             max_keys,
         } = self.command().clone()
         {
-            let mut query_pairs = url.query_pairs_mut();
-            delimiter.map(|d| query_pairs.append_pair("delimiter", &d));
+            if let Some(delimiter) = delimiter {
+                signing::append_query(&mut url, "delimiter", &delimiter);
+            }
 
-            query_pairs.append_pair("prefix", &prefix);
+            signing::append_query(&mut url, "prefix", &prefix);
             if let Some(marker) = marker {
-                query_pairs.append_pair("marker", &marker);
+                signing::append_query(&mut url, "marker", &marker);
             }
             if let Some(max_keys) = max_keys {
-                query_pairs.append_pair("max-keys", &max_keys.to_string());
+                signing::append_query(&mut url, "max-keys", &max_keys.to_string());
+            }
+        }
+
+        if let Command::ListObjectVersions {
+            prefix,
+            delimiter,
+            key_marker,
+            version_id_marker,
+            max_keys,
+        } = self.command().clone()
+        {
+            if let Some(delimiter) = delimiter {
+                signing::append_query(&mut url, "delimiter", &delimiter);
+            }
+
+            signing::append_query(&mut url, "prefix", &prefix);
+            if let Some(key_marker) = key_marker {
+                signing::append_query(&mut url, "key-marker", &key_marker);
+            }
+            if let Some(version_id_marker) = version_id_marker {
+                signing::append_query(&mut url, "version-id-marker", &version_id_marker);
+            }
+            if let Some(max_keys) = max_keys {
+                signing::append_query(&mut url, "max-keys", &max_keys.to_string());
             }
         }
 
@@ -486,22 +884,27 @@ pub trait Request {
                 key_marker,
                 max_uploads,
             } => {
-                let mut query_pairs = url.query_pairs_mut();
-                delimiter.map(|d| query_pairs.append_pair("delimiter", d));
+                if let Some(delimiter) = delimiter {
+                    signing::append_query(&mut url, "delimiter", delimiter);
+                }
                 if let Some(prefix) = prefix {
-                    query_pairs.append_pair("prefix", prefix);
+                    signing::append_query(&mut url, "prefix", prefix);
                 }
                 if let Some(key_marker) = key_marker {
-                    query_pairs.append_pair("key-marker", &key_marker);
+                    signing::append_query(&mut url, "key-marker", &key_marker);
                 }
                 if let Some(max_uploads) = max_uploads {
-                    query_pairs.append_pair("max-uploads", max_uploads.to_string().as_str());
+                    signing::append_query(
+                        &mut url,
+                        "max-uploads",
+                        max_uploads.to_string().as_str(),
+                    );
                 }
             }
             Command::PutObjectTagging { .. }
             | Command::GetObjectTagging
             | Command::DeleteObjectTagging => {
-                url.query_pairs_mut().append_pair("tagging", "");
+                signing::append_query(&mut url, "tagging", "");
             }
             _ => {}
         }
@@ -520,44 +923,53 @@ pub trait Request {
 
     #[maybe_async::maybe_async]
     async fn authorization(&self, headers: &HeaderMap) -> Result<String, S3Error> {
-        let canonical_request = self.canonical_request(headers)?;
-        let string_to_sign = self.string_to_sign(&canonical_request)?;
-        let mut hmac = signing::HmacSha256::new_from_slice(&self.signing_key().await?)?;
-        hmac.update(string_to_sign.as_bytes());
-        let signature = hex::encode(hmac.finalize().into_bytes());
-        let signed_header = signing::signed_header_string(headers);
-        signing::authorization_header(
-            &self
-                .bucket()
-                .access_key()
-                .await?
-                .expect("No access_key provided"),
-            &self.datetime(),
-            &self.bucket().region(),
-            &signed_header,
-            &signature,
-        )
+        let credentials = self.signing_credentials().await?;
+        let url = self.url()?;
+        let payload_sha256 = self.command().sha256()?;
+        SigV4.authorization(&SigningRequest {
+            method: self.command().http_verb(),
+            url: &url,
+            headers,
+            payload_sha256: &payload_sha256,
+            datetime: self.datetime(),
+            region: self.bucket().region_ref(),
+            service: self.signing_service(),
+            credentials: &credentials,
+        })
     }
 
     #[maybe_async::maybe_async]
     async fn headers(&self) -> Result<HeaderMap, S3Error> {
+        // Every attempt builds its headers, so this is where one past the deadline is stopped
+        self.bucket().check_deadline()?;
+
         // Generate this once, but it's used in more than one place.
         let sha256 = self.command().sha256()?;
 
-        // Start with extra_headers, that way our headers replace anything with
-        // the same name.
+        // Start with extra_headers, per request ones over the bucket's, that way our
+        // headers replace anything with the same name.
 
         let mut headers = HeaderMap::new();
 
-        for (k, v) in self.bucket().extra_headers.iter() {
+        let request_headers = self.options().map(|options| &options.headers);
+        for (k, v) in self
+            .bucket()
+            .extra_headers
+            .iter()
+            .chain(request_headers.into_iter().flatten())
+        {
             if k.as_str().starts_with("x-amz-meta-") {
                 // metadata is invalid on any multipart command other than initiate
                 match self.command() {
                     Command::UploadPart { .. }
+                    | Command::UploadPartCopy { .. }
                     | Command::AbortMultipartUpload { .. }
                     | Command::CompleteMultipartUpload { .. }
                     | Command::PutObject {
                         multipart: Some(_), ..
+                    }
+                    | Command::PutObjectWithChecksum {
+                        multipart: Some(_), ..
                     } => continue,
                     _ => (),
                 }
@@ -573,9 +985,20 @@ pub trait Request {
             Command::CopyObject { from } => {
                 headers.insert(HeaderName::from_static("x-amz-copy-source"), from.parse()?);
             }
+            Command::UploadPartCopy { from, range, .. } => {
+                headers.insert(HeaderName::from_static("x-amz-copy-source"), from.parse()?);
+                if let Some((first, last)) = range {
+                    headers.insert(
+                        HeaderName::from_static("x-amz-copy-source-range"),
+                        format!("bytes={}-{}", first, last).parse()?,
+                    );
+                }
+            }
             Command::ListObjects { .. } => {}
             Command::ListObjectsV2 { .. } => {}
+            Command::ListObjectVersions { .. } => {}
             Command::GetObject => {}
+            Command::GetObjectVersion { .. } => {}
             Command::GetObjectTagging => {}
             Command::GetBucketLocation => {}
             _ => {
@@ -595,31 +1018,42 @@ pub trait Request {
             self.long_date()?.parse()?,
         );
 
-        if let Some(session_token) = self.bucket().session_token().await? {
+        let credentials = self.signing_credentials().await?;
+        // Directory bucket sessions carry their token in a header of their own
+        let token_header = match self.command() {
+            Command::CreateSession => "x-amz-security-token",
+            _ if self.bucket().is_directory_bucket() => "x-amz-s3session-token",
+            _ => "x-amz-security-token",
+        };
+        if let Some(session_token) = &credentials.session_token {
             headers.insert(
-                HeaderName::from_static("x-amz-security-token"),
+                HeaderName::from_static(token_header),
                 session_token.parse()?,
             );
-        } else if let Some(security_token) = self.bucket().security_token().await? {
+        } else if let Some(security_token) = &credentials.security_token {
             headers.insert(
-                HeaderName::from_static("x-amz-security-token"),
+                HeaderName::from_static(token_header),
                 security_token.parse()?,
             );
         }
 
         if let Command::PutObjectTagging { tags } = self.command() {
-            let digest = md5::compute(tags);
-            let hash = general_purpose::STANDARD.encode(digest.as_ref());
-            headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
+            insert_integrity_header(&mut headers, tags.as_bytes())?;
         } else if let Command::PutObject { content, .. } = self.command() {
-            let digest = md5::compute(content);
-            let hash = general_purpose::STANDARD.encode(digest.as_ref());
-            headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
+            insert_content_md5(&mut headers, &content)?;
         } else if let Command::UploadPart { content, .. } = self.command() {
-            let digest = md5::compute(content);
-            let hash = general_purpose::STANDARD.encode(digest.as_ref());
-            headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
-        } else if let Command::GetObject {} = self.command() {
+            insert_content_md5(&mut headers, content)?;
+        } else if let Command::PutObjectWithChecksum { content, .. } = self.command() {
+            headers.insert(CONTENT_ENCODING, "aws-chunked".parse()?);
+            headers.insert(
+                HeaderName::from_static("x-amz-decoded-content-length"),
+                content.len().to_string().parse()?,
+            );
+            headers.insert(
+                HeaderName::from_static("x-amz-trailer"),
+                CRC32C_TRAILER.parse()?,
+            );
+        } else if let Command::GetObject | Command::GetObjectVersion { .. } = self.command() {
             headers.insert(ACCEPT, "application/octet-stream".to_string().parse()?);
         // headers.insert(header::ACCEPT_CHARSET, HeaderValue::from_str("UTF-8")?);
         } else if let Command::GetObjectRange { start, end } = self.command() {
@@ -635,19 +1069,29 @@ pub trait Request {
         } else if let Command::CreateBucket { ref config } = self.command() {
             config.add_headers(&mut headers)?;
         } else if let Command::PutBucketLifecycle { ref configuration } = self.command() {
-            let digest = md5::compute(to_string(configuration)?.as_bytes());
-            let hash = general_purpose::STANDARD.encode(digest.as_ref());
-            headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
+            insert_integrity_header(&mut headers, configuration.to_xml()?.as_bytes())?;
             headers.remove("x-amz-content-sha256");
+        } else if let Command::PutBucketVersioning {
+            ref configuration,
+            ref mfa,
+        } = self.command()
+        {
+            insert_integrity_header(&mut headers, configuration.to_xml()?.as_bytes())?;
+            if let Some(mfa) = mfa {
+                headers.insert(HeaderName::from_static("x-amz-mfa"), mfa.parse()?);
+            }
+        } else if let Command::DeleteObjects { request } = self.command() {
+            // S3 refuses batch deletes without an integrity header
+            insert_integrity_header(&mut headers, request.to_xml()?.as_bytes())?;
+        } else if let Command::DeleteObjectVersion { mfa: Some(mfa), .. } = self.command() {
+            headers.insert(HeaderName::from_static("x-amz-mfa"), mfa.parse()?);
         } else if let Command::PutBucketCors {
             expected_bucket_owner,
             configuration,
             ..
         } = self.command()
         {
-            let digest = md5::compute(configuration.to_string().as_bytes());
-            let hash = general_purpose::STANDARD.encode(digest.as_ref());
-            headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
+            insert_integrity_header(&mut headers, configuration.to_xml()?.as_bytes())?;
 
             headers.insert(
                 HeaderName::from_static("x-amz-expected-bucket-owner"),
@@ -671,7 +1115,7 @@ pub trait Request {
             );
         } else if let Command::GetObjectAttributes {
             expected_bucket_owner,
-            ..
+            options,
         } = self.command()
         {
             headers.insert(
@@ -680,14 +1124,37 @@ pub trait Request {
             );
             headers.insert(
                 HeaderName::from_static("x-amz-object-attributes"),
-                "ETag".parse()?,
+                options.attributes_header().parse()?,
             );
+            if let Some(max_parts) = options.max_parts {
+                headers.insert(
+                    HeaderName::from_static("x-amz-max-parts"),
+                    max_parts.to_string().parse()?,
+                );
+            }
+            if let Some(part_number_marker) = options.part_number_marker {
+                headers.insert(
+                    HeaderName::from_static("x-amz-part-number-marker"),
+                    part_number_marker.to_string().parse()?,
+                );
+            }
         }
 
         // This must be last, as it signs the other headers, omitted if no secret key is provided
-        if self.bucket().secret_key().await?.is_some() {
-            let authorization = self.authorization(&headers).await?;
-            headers.insert(AUTHORIZATION, authorization.parse()?);
+        if credentials.secret_key.is_some() {
+            let url = self.url()?;
+            let unsigned = headers.clone();
+            let request = SigningRequest {
+                method: self.command().http_verb(),
+                url: &url,
+                headers: &unsigned,
+                payload_sha256: &sha256,
+                datetime: self.datetime(),
+                region: self.bucket().region_ref(),
+                service: self.signing_service(),
+                credentials: &credentials,
+            };
+            self.bucket().signer().sign(&request, &mut headers)?;
         }
 
         // The format of RFC2822 is somewhat malleable, so including it in
@@ -701,3 +1168,30 @@ pub trait Request {
         Ok(headers)
     }
 }
+
+/// `Content-MD5` of `body`, left out without the `md5` feature as the signed payload hash
+/// already covers the body
+fn insert_content_md5(headers: &mut HeaderMap, body: &[u8]) -> Result<(), S3Error> {
+    #[cfg(feature = "md5")]
+    {
+        let digest = md5::compute(body);
+        let hash = general_purpose::STANDARD.encode(digest.as_ref());
+        headers.insert(HeaderName::from_static("content-md5"), hash.parse()?);
+    }
+    #[cfg(not(feature = "md5"))]
+    let _ = (headers, body);
+    Ok(())
+}
+
+/// Integrity header for requests S3 refuses without one, `Content-MD5` or without the `md5`
+/// feature a CRC32C checksum, which S3 takes in its place
+fn insert_integrity_header(headers: &mut HeaderMap, body: &[u8]) -> Result<(), S3Error> {
+    #[cfg(feature = "md5")]
+    insert_content_md5(headers, body)?;
+    #[cfg(not(feature = "md5"))]
+    headers.insert(
+        HeaderName::from_static(CRC32C_TRAILER),
+        Crc32c::checksum(body).to_base64().parse()?,
+    );
+    Ok(())
+}
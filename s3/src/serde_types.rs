@@ -1,4 +1,55 @@
-#[derive(Deserialize, Debug)]
+use crate::command::HttpMethod;
+use crate::error::S3Error;
+use crate::region::error::RegionError;
+use crate::region::Region;
+use crate::request::ResponseData;
+use crate::signing::uri_encode;
+use crate::utils::PageSize;
+use base64::engine::general_purpose;
+use base64::Engine;
+use http::header::{
+    HeaderName, CONTENT_TYPE, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE,
+};
+use http::HeaderMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+/// Namespace of the S3 API, declared on the root element of request documents.
+pub const S3_XML_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+const XML_PREAMBLE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>";
+
+/// Configuration documents sent as XML request bodies.
+///
+/// Implementors only name their root element, `to_xml` takes care of the XML declaration
+/// and of declaring the S3 namespace on the root, so every document is emitted the same way.
+pub trait XmlDocument: Serialize {
+    const ROOT: &'static str;
+
+    fn to_xml(&self) -> Result<String, quick_xml::de::DeError> {
+        #[derive(Serialize)]
+        struct Namespaced<'a, T: ?Sized + Serialize> {
+            #[serde(rename = "@xmlns")]
+            xmlns: &'static str,
+            #[serde(flatten)]
+            document: &'a T,
+        }
+
+        let body = quick_xml::se::to_string_with_root(
+            Self::ROOT,
+            &Namespaced {
+                xmlns: S3_XML_NAMESPACE,
+                document: self,
+            },
+        )?;
+        Ok(format!("{}{}", XML_PREAMBLE, body))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct InitiateMultipartUploadResponse {
     #[serde(rename = "Bucket")]
     _bucket: String,
@@ -6,16 +57,95 @@ pub struct InitiateMultipartUploadResponse {
     pub key: String,
     #[serde(rename = "UploadId")]
     pub upload_id: String,
+    /// Status code of the response, the fields below are read from its headers
+    #[serde(rename = "StatusCode", skip_deserializing)]
+    pub status_code: u16,
+    /// `x-amz-request-id`, what AWS support asks for when an upload goes wrong
+    #[serde(rename = "RequestId", skip_deserializing)]
+    pub request_id: Option<String>,
+    /// `x-amz-id-2`
+    #[serde(rename = "HostId", skip_deserializing)]
+    pub extended_request_id: Option<String>,
+    /// Encryption S3 applies to the parts, `x-amz-server-side-encryption`
+    #[serde(rename = "ServerSideEncryption", skip_deserializing)]
+    pub server_side_encryption: Option<ServerSideEncryption>,
+    /// `x-amz-server-side-encryption-aws-kms-key-id`
+    #[serde(rename = "SSEKMSKeyId", skip_deserializing)]
+    pub ssekms_key_id: Option<String>,
 }
 
-/// Owner information for the object
+impl InitiateMultipartUploadResponse {
+    /// Parse the body of a successful `CreateMultipartUpload` and fill in its headers
+    pub(crate) fn from_response_data(
+        response_data: &ResponseData,
+    ) -> Result<InitiateMultipartUploadResponse, S3Error> {
+        let mut msg: InitiateMultipartUploadResponse =
+            quick_xml::de::from_str(response_data.as_str()?)?;
+        msg.status_code = response_data.status_code();
+        msg.request_id = response_data.header("x-amz-request-id").map(String::from);
+        msg.extended_request_id = response_data.header("x-amz-id-2").map(String::from);
+        msg.server_side_encryption = response_data
+            .header("x-amz-server-side-encryption")
+            .map(ServerSideEncryption::from);
+        msg.ssekms_key_id = response_data
+            .header("x-amz-server-side-encryption-aws-kms-key-id")
+            .map(String::from);
+        Ok(msg)
+    }
+}
+
+/// Result of `Bucket::abort_upload`, `AbortMultipartUpload` has no body
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct AbortMultipartUploadResponse {
+    #[serde(rename = "StatusCode")]
+    pub status_code: u16,
+    /// `x-amz-request-id`
+    #[serde(rename = "RequestId")]
+    pub request_id: Option<String>,
+    /// `x-amz-id-2`
+    #[serde(rename = "HostId")]
+    pub extended_request_id: Option<String>,
+}
+
+impl From<&ResponseData> for AbortMultipartUploadResponse {
+    fn from(response_data: &ResponseData) -> Self {
+        AbortMultipartUploadResponse {
+            status_code: response_data.status_code(),
+            request_id: response_data.header("x-amz-request-id").map(String::from),
+            extended_request_id: response_data.header("x-amz-id-2").map(String::from),
+        }
+    }
+}
+
+/// Response of `CreateSession` on a directory bucket
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateSessionResult {
+    #[serde(rename = "Credentials")]
+    pub credentials: SessionCredentials,
+}
+
+/// Short lived credentials for requests to a directory bucket
 #[derive(Deserialize, Debug, Clone)]
+pub struct SessionCredentials {
+    #[serde(rename = "AccessKeyId")]
+    pub access_key_id: String,
+    #[serde(rename = "SecretAccessKey")]
+    pub secret_access_key: String,
+    #[serde(rename = "SessionToken")]
+    pub session_token: String,
+    /// RFC 3339 timestamp
+    #[serde(rename = "Expiration")]
+    pub expiration: String,
+}
+
+/// Owner information for the object
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Owner {
     #[serde(rename = "DisplayName")]
     /// Object owner's name.
     pub display_name: Option<String>,
-    #[serde(rename = "ID")]
-    /// Object owner's ID.
+    #[serde(rename = "ID", default)]
+    /// Object owner's ID, empty when the provider leaves it out
     pub id: String,
 }
 
@@ -46,94 +176,737 @@ pub struct Owner {
 //    <StorageClass>string</StorageClass>
 //    <ObjectSize>long</ObjectSize>
 // </GetObjectAttributesOutput>
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct GetObjectAttributesOutput {
     #[serde(rename = "ETag")]
-    pub etag: String,
+    pub etag: Option<String>,
     #[serde(rename = "Checksum")]
-    pub checksum: Checksum,
+    pub checksum: Option<Checksum>,
     #[serde(rename = "ObjectParts")]
-    pub object_parts: ObjectParts,
+    pub object_parts: Option<ObjectParts>,
     #[serde(rename = "StorageClass")]
-    pub storage_class: String,
+    pub storage_class: Option<String>,
     #[serde(rename = "ObjectSize")]
-    pub object_size: u64,
+    pub object_size: Option<u64>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Only the checksum of the algorithm the object was uploaded with is present
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Checksum {
     #[serde(rename = "ChecksumCRC32")]
-    pub checksum_crc32: String,
+    pub checksum_crc32: Option<String>,
     #[serde(rename = "ChecksumCRC32C")]
-    pub checksum_crc32c: String,
+    pub checksum_crc32c: Option<String>,
     #[serde(rename = "ChecksumSHA1")]
-    pub checksum_sha1: String,
+    pub checksum_sha1: Option<String>,
     #[serde(rename = "ChecksumSHA256")]
-    pub checksum_sha256: String,
+    pub checksum_sha256: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ObjectParts {
-    #[serde(rename = "IsTruncated")]
+    #[serde(rename = "IsTruncated", default)]
     pub is_truncated: bool,
     #[serde(rename = "MaxParts")]
-    pub max_parts: i32,
+    pub max_parts: Option<i32>,
     #[serde(rename = "NextPartNumberMarker")]
-    pub next_part_number_marker: i32,
+    pub next_part_number_marker: Option<i32>,
     #[serde(rename = "PartNumberMarker")]
-    pub part_number_marker: i32,
-    #[serde(rename = "Part")]
+    pub part_number_marker: Option<i32>,
+    #[serde(rename = "Part", default)]
     pub part: Vec<AttributesPart>,
     #[serde(rename = "PartsCount")]
-    pub parts_count: u64,
+    pub parts_count: Option<u64>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct AttributesPart {
     #[serde(rename = "ChecksumCRC32")]
-    pub checksum_crc32: String,
+    pub checksum_crc32: Option<String>,
     #[serde(rename = "ChecksumCRC32C")]
-    pub checksum_crc32c: String,
+    pub checksum_crc32c: Option<String>,
     #[serde(rename = "ChecksumSHA1")]
-    pub checksum_sha1: String,
+    pub checksum_sha1: Option<String>,
     #[serde(rename = "ChecksumSHA256")]
-    pub checksum_sha256: String,
+    pub checksum_sha256: Option<String>,
     #[serde(rename = "PartNumber")]
     pub part_number: i32,
     #[serde(rename = "Size")]
     pub size: u64,
 }
 
+/// Attributes `Bucket::get_object_attributes_with_options` can ask for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectAttribute {
+    ETag,
+    Checksum,
+    ObjectParts,
+    StorageClass,
+    ObjectSize,
+}
+
+impl fmt::Display for ObjectAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectAttribute::ETag => write!(f, "ETag"),
+            ObjectAttribute::Checksum => write!(f, "Checksum"),
+            ObjectAttribute::ObjectParts => write!(f, "ObjectParts"),
+            ObjectAttribute::StorageClass => write!(f, "StorageClass"),
+            ObjectAttribute::ObjectSize => write!(f, "ObjectSize"),
+        }
+    }
+}
+
+/// Attributes to fetch with `Bucket::get_object_attributes_with_options`, and the page of
+/// parts to return when asking for `ObjectParts`.
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::{GetObjectAttributesOptions, ObjectAttribute};
+/// use s3::utils::PageSize;
+///
+/// let options = GetObjectAttributesOptions::new(&[ObjectAttribute::ETag, ObjectAttribute::ObjectParts])
+///     .max_parts(PageSize::of(100))
+///     .part_number_marker(100);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetObjectAttributesOptions {
+    pub attributes: Vec<ObjectAttribute>,
+    pub version_id: Option<String>,
+    pub max_parts: Option<PageSize>,
+    pub part_number_marker: Option<u32>,
+}
+
+impl GetObjectAttributesOptions {
+    pub fn new(attributes: &[ObjectAttribute]) -> Self {
+        let mut options = GetObjectAttributesOptions::default();
+        for attribute in attributes {
+            if !options.attributes.contains(attribute) {
+                options.attributes.push(*attribute);
+            }
+        }
+        options
+    }
+
+    pub fn version_id(mut self, version_id: impl Into<String>) -> Self {
+        self.version_id = Some(version_id.into());
+        self
+    }
+
+    pub fn max_parts(mut self, max_parts: PageSize) -> Self {
+        self.max_parts = Some(max_parts);
+        self
+    }
+
+    pub fn part_number_marker(mut self, part_number_marker: u32) -> Self {
+        self.part_number_marker = Some(part_number_marker);
+        self
+    }
+
+    /// Value of the `x-amz-object-attributes` header
+    pub fn attributes_header(&self) -> String {
+        self.attributes
+            .iter()
+            .map(ObjectAttribute::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Where a listing with `Bucket::list_with_options` or `Bucket::list_stream` starts, and how
+/// its keys are grouped and paged.
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::ListObjectsOptions;
+/// use s3::utils::PageSize;
+///
+/// // Resume after the last key processed, 100 keys at a time
+/// let options = ListObjectsOptions::new()
+///     .start_after("logs/2024-03-01.log")
+///     .max_keys(PageSize::of(100));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListObjectsOptions {
+    pub delimiter: Option<String>,
+    /// Key the listing starts after, only sent with the first page
+    pub start_after: Option<String>,
+    /// Keys per page, not a limit on the whole listing
+    pub max_keys: Option<PageSize>,
+}
+
+impl ListObjectsOptions {
+    pub fn new() -> Self {
+        ListObjectsOptions::default()
+    }
+
+    pub fn delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+
+    pub fn start_after(mut self, start_after: impl Into<String>) -> Self {
+        self.start_after = Some(start_after.into());
+        self
+    }
+
+    /// Keys per page, sent with every page of the listing
+    pub fn max_keys(mut self, max_keys: PageSize) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+}
+
+/// What a copy does with the tags of the source object. Without a directive providers fall
+/// back to their own default, which isn't the same everywhere.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaggingDirective {
+    /// Keep the source object's tags
+    Copy,
+    /// Tag the copy with these tags instead, an empty set leaves it untagged
+    Replace(Vec<(String, String)>),
+}
+
+/// Options for `Bucket::copy_object_internal_with_options` and `Bucket::copy_object_from`
+///
+/// Giving metadata or a content type replaces all metadata of the source object
+/// (`x-amz-metadata-directive: REPLACE`), what isn't given is dropped rather than copied.
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::{CopyObjectOptions, Metadata, StorageClass};
+///
+/// let mut metadata = Metadata::new();
+/// metadata.insert("stage", "archived").unwrap();
+/// let options = CopyObjectOptions::new()
+///     .replace_tags([("stage", "archived")])
+///     .replace_metadata(metadata)
+///     .content_type("application/pdf")
+///     .storage_class(StorageClass::Glacier);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopyObjectOptions {
+    pub tagging: Option<TaggingDirective>,
+    pub metadata: Option<Metadata>,
+    pub content_type: Option<String>,
+    pub storage_class: Option<StorageClass>,
+    pub server_side_encryption: Option<ServerSideEncryption>,
+    /// KMS key of `ServerSideEncryption::AwsKms` and `AwsKmsDsse`, the account's default without
+    pub ssekms_key_id: Option<String>,
+}
+
+impl CopyObjectOptions {
+    pub fn new() -> Self {
+        CopyObjectOptions::default()
+    }
+
+    pub fn copy_tags(mut self) -> Self {
+        self.tagging = Some(TaggingDirective::Copy);
+        self
+    }
+
+    pub fn replace_tags<I, K, V>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let tags = tags
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        self.tagging = Some(TaggingDirective::Replace(tags));
+        self
+    }
+
+    pub fn replace_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn storage_class(mut self, storage_class: StorageClass) -> Self {
+        self.storage_class = Some(storage_class);
+        self
+    }
+
+    pub fn server_side_encryption(mut self, encryption: ServerSideEncryption) -> Self {
+        self.server_side_encryption = Some(encryption);
+        self
+    }
+
+    /// Encrypt the copy with the KMS key `key_id`
+    pub fn sse_kms(mut self, key_id: impl Into<String>) -> Self {
+        self.server_side_encryption = Some(ServerSideEncryption::AwsKms);
+        self.ssekms_key_id = Some(key_id.into());
+        self
+    }
+
+    /// Tagging, metadata, storage class and encryption headers to send with the copy
+    pub fn headers(&self) -> Result<HeaderMap, S3Error> {
+        let mut headers = match &self.metadata {
+            Some(metadata) => metadata.headers()?,
+            None => HeaderMap::new(),
+        };
+        if self.metadata.is_some() || self.content_type.is_some() {
+            headers.insert(
+                HeaderName::from_static("x-amz-metadata-directive"),
+                "REPLACE".parse()?,
+            );
+        }
+        if let Some(content_type) = &self.content_type {
+            headers.insert(CONTENT_TYPE, content_type.parse()?);
+        }
+        if let Some(storage_class) = &self.storage_class {
+            headers.insert(
+                HeaderName::from_static("x-amz-storage-class"),
+                storage_class.as_str().parse()?,
+            );
+        }
+        if let Some(encryption) = &self.server_side_encryption {
+            headers.insert(
+                HeaderName::from_static("x-amz-server-side-encryption"),
+                encryption.as_str().parse()?,
+            );
+        }
+        if let Some(key_id) = &self.ssekms_key_id {
+            headers.insert(
+                HeaderName::from_static("x-amz-server-side-encryption-aws-kms-key-id"),
+                key_id.parse()?,
+            );
+        }
+        match &self.tagging {
+            None => {}
+            Some(TaggingDirective::Copy) => {
+                headers.insert(TAGGING_DIRECTIVE, "COPY".parse()?);
+            }
+            Some(TaggingDirective::Replace(tags)) => {
+                headers.insert(TAGGING_DIRECTIVE, "REPLACE".parse()?);
+                if !tags.is_empty() {
+                    let tags = tags
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", uri_encode(k, true), uri_encode(v, true)))
+                        .collect::<Vec<_>>()
+                        .join("&");
+                    headers.insert(HeaderName::from_static("x-amz-tagging"), tags.parse()?);
+                }
+            }
+        }
+        Ok(headers)
+    }
+}
+
+/// Preconditions for `Bucket::get_object_conditional`
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::GetObjectConditions;
+///
+/// let conditions = GetObjectConditions::new().if_none_match("\"9b2cf535f27731c974343645a3985328\"");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetObjectConditions {
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<OffsetDateTime>,
+    pub if_unmodified_since: Option<OffsetDateTime>,
+}
+
+impl GetObjectConditions {
+    pub fn new() -> Self {
+        GetObjectConditions::default()
+    }
+
+    pub fn if_match(mut self, e_tag: impl Into<String>) -> Self {
+        self.if_match = Some(e_tag.into());
+        self
+    }
+
+    pub fn if_none_match(mut self, e_tag: impl Into<String>) -> Self {
+        self.if_none_match = Some(e_tag.into());
+        self
+    }
+
+    pub fn if_modified_since(mut self, since: OffsetDateTime) -> Self {
+        self.if_modified_since = Some(since);
+        self
+    }
+
+    pub fn if_unmodified_since(mut self, since: OffsetDateTime) -> Self {
+        self.if_unmodified_since = Some(since);
+        self
+    }
+
+    /// Conditional request headers, dates are sent as HTTP dates in GMT
+    pub fn headers(&self) -> Result<HeaderMap, S3Error> {
+        let mut headers = HeaderMap::new();
+        if let Some(e_tag) = &self.if_match {
+            headers.insert(IF_MATCH, e_tag.parse()?);
+        }
+        if let Some(e_tag) = &self.if_none_match {
+            headers.insert(IF_NONE_MATCH, e_tag.parse()?);
+        }
+        if let Some(since) = self.if_modified_since {
+            headers.insert(IF_MODIFIED_SINCE, http_date(since)?.parse()?);
+        }
+        if let Some(since) = self.if_unmodified_since {
+            headers.insert(IF_UNMODIFIED_SINCE, http_date(since)?.parse()?);
+        }
+        Ok(headers)
+    }
+}
+
+/// Response headers S3 sets from the query of a presigned GET in place of the object's own,
+/// for `Bucket::presign_get_with_overrides`
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::ResponseOverrides;
+///
+/// let overrides = ResponseOverrides::new()
+///     .content_disposition("attachment; filename=\"report.pdf\"")
+///     .cache_control("max-age=3600");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResponseOverrides {
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub content_type: Option<String>,
+    pub expires: Option<OffsetDateTime>,
+}
+
+impl ResponseOverrides {
+    pub fn new() -> Self {
+        ResponseOverrides::default()
+    }
+
+    pub fn cache_control(mut self, value: impl Into<String>) -> Self {
+        self.cache_control = Some(value.into());
+        self
+    }
+
+    pub fn content_disposition(mut self, value: impl Into<String>) -> Self {
+        self.content_disposition = Some(value.into());
+        self
+    }
+
+    pub fn content_encoding(mut self, value: impl Into<String>) -> Self {
+        self.content_encoding = Some(value.into());
+        self
+    }
+
+    pub fn content_language(mut self, value: impl Into<String>) -> Self {
+        self.content_language = Some(value.into());
+        self
+    }
+
+    pub fn content_type(mut self, value: impl Into<String>) -> Self {
+        self.content_type = Some(value.into());
+        self
+    }
+
+    pub fn expires(mut self, at: OffsetDateTime) -> Self {
+        self.expires = Some(at);
+        self
+    }
+
+    /// The `response-*` query parameters, `expires` as an HTTP date in GMT
+    pub fn queries(&self) -> Result<HashMap<String, String>, S3Error> {
+        let mut queries = HashMap::new();
+        let values = [
+            ("response-cache-control", &self.cache_control),
+            ("response-content-disposition", &self.content_disposition),
+            ("response-content-encoding", &self.content_encoding),
+            ("response-content-language", &self.content_language),
+            ("response-content-type", &self.content_type),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                queries.insert(name.to_string(), value.clone());
+            }
+        }
+        if let Some(at) = self.expires {
+            queries.insert("response-expires".to_string(), http_date(at)?);
+        }
+        Ok(queries)
+    }
+}
+
+fn http_date(at: OffsetDateTime) -> Result<String, S3Error> {
+    Ok(at.to_offset(UtcOffset::UTC).format(format_description!(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    ))?)
+}
+
+const TAGGING_DIRECTIVE: HeaderName = HeaderName::from_static("x-amz-tagging-directive");
+
+/// A 256 bit AES key for server side encryption with customer provided keys (SSE-C). S3
+/// doesn't keep the key, every request reading or writing the object has to send it along.
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::SseCustomerKey;
+///
+/// let key = SseCustomerKey::new([7; 32]);
+/// let headers = key.headers().unwrap();
+/// assert_eq!(headers["x-amz-server-side-encryption-customer-algorithm"], "AES256");
+/// ```
+#[cfg(feature = "md5")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct SseCustomerKey {
+    key: [u8; 32],
+}
+
+#[cfg(feature = "md5")]
+impl SseCustomerKey {
+    /// Names of the headers carrying the key, in the order `headers` returns them
+    pub const HEADERS: [&'static str; 3] = [
+        "x-amz-server-side-encryption-customer-algorithm",
+        "x-amz-server-side-encryption-customer-key",
+        "x-amz-server-side-encryption-customer-key-md5",
+    ];
+
+    pub fn new(key: [u8; 32]) -> Self {
+        SseCustomerKey { key }
+    }
+
+    /// The algorithm, the base64 encoded key and the base64 encoded MD5 of the key
+    pub fn headers(&self) -> Result<HeaderMap, S3Error> {
+        let [algorithm, key, key_md5] = Self::HEADERS;
+        let mut headers = HeaderMap::new();
+        headers.insert(algorithm, "AES256".parse()?);
+        headers.insert(key, general_purpose::STANDARD.encode(self.key).parse()?);
+        headers.insert(
+            key_md5,
+            general_purpose::STANDARD
+                .encode(md5::compute(self.key).0)
+                .parse()?,
+        );
+        Ok(headers)
+    }
+}
+
+// Keeps the key out of logs
+#[cfg(feature = "md5")]
+impl std::fmt::Debug for SseCustomerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SseCustomerKey").finish_non_exhaustive()
+    }
+}
+
+/// Storage class of an object, as reported by listings and HEAD requests. Classes this crate
+/// doesn't know of, like those of S3 compatible stores, are kept as `Other`.
+///
+/// ```
+/// use s3::serde_types::StorageClass;
+///
+/// assert_eq!("GLACIER_IR".parse(), Ok(StorageClass::GlacierInstantRetrieval));
+/// assert_eq!(StorageClass::from("COLD").as_str(), "COLD");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StorageClass {
+    Standard,
+    ReducedRedundancy,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    Glacier,
+    GlacierInstantRetrieval,
+    DeepArchive,
+    Outposts,
+    Snow,
+    ExpressOnezone,
+    Other(String),
+}
+
+impl StorageClass {
+    pub fn as_str(&self) -> &str {
+        match self {
+            StorageClass::Standard => "STANDARD",
+            StorageClass::ReducedRedundancy => "REDUCED_REDUNDANCY",
+            StorageClass::StandardIa => "STANDARD_IA",
+            StorageClass::OnezoneIa => "ONEZONE_IA",
+            StorageClass::IntelligentTiering => "INTELLIGENT_TIERING",
+            StorageClass::Glacier => "GLACIER",
+            StorageClass::GlacierInstantRetrieval => "GLACIER_IR",
+            StorageClass::DeepArchive => "DEEP_ARCHIVE",
+            StorageClass::Outposts => "OUTPOSTS",
+            StorageClass::Snow => "SNOW",
+            StorageClass::ExpressOnezone => "EXPRESS_ONEZONE",
+            StorageClass::Other(class) => class,
+        }
+    }
+}
+
+impl From<&str> for StorageClass {
+    fn from(class: &str) -> Self {
+        match class {
+            "STANDARD" => StorageClass::Standard,
+            "REDUCED_REDUNDANCY" => StorageClass::ReducedRedundancy,
+            "STANDARD_IA" => StorageClass::StandardIa,
+            "ONEZONE_IA" => StorageClass::OnezoneIa,
+            "INTELLIGENT_TIERING" => StorageClass::IntelligentTiering,
+            "GLACIER" => StorageClass::Glacier,
+            "GLACIER_IR" => StorageClass::GlacierInstantRetrieval,
+            "DEEP_ARCHIVE" => StorageClass::DeepArchive,
+            "OUTPOSTS" => StorageClass::Outposts,
+            "SNOW" => StorageClass::Snow,
+            "EXPRESS_ONEZONE" => StorageClass::ExpressOnezone,
+            other => StorageClass::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::str::FromStr for StorageClass {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(StorageClass::from(s))
+    }
+}
+
+impl fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for StorageClass {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for StorageClass {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let class = String::deserialize(deserializer)?;
+        Ok(StorageClass::from(class.as_str()))
+    }
+}
+
+/// Server side encryption of an object, the `x-amz-server-side-encryption` header. Algorithms
+/// this crate doesn't know of are kept as `Other`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ServerSideEncryption {
+    /// `AES256`, S3 managed keys
+    Aes256,
+    /// `aws:kms`
+    AwsKms,
+    /// `aws:kms:dsse`, dual layer encryption with KMS keys
+    AwsKmsDsse,
+    Other(String),
+}
+
+impl ServerSideEncryption {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ServerSideEncryption::Aes256 => "AES256",
+            ServerSideEncryption::AwsKms => "aws:kms",
+            ServerSideEncryption::AwsKmsDsse => "aws:kms:dsse",
+            ServerSideEncryption::Other(algorithm) => algorithm,
+        }
+    }
+}
+
+impl From<&str> for ServerSideEncryption {
+    fn from(algorithm: &str) -> Self {
+        match algorithm {
+            "AES256" => ServerSideEncryption::Aes256,
+            "aws:kms" => ServerSideEncryption::AwsKms,
+            "aws:kms:dsse" => ServerSideEncryption::AwsKmsDsse,
+            other => ServerSideEncryption::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::str::FromStr for ServerSideEncryption {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ServerSideEncryption::from(s))
+    }
+}
+
+impl fmt::Display for ServerSideEncryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ServerSideEncryption {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ServerSideEncryption {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let algorithm = String::deserialize(deserializer)?;
+        Ok(ServerSideEncryption::from(algorithm.as_str()))
+    }
+}
+
 /// An individual object in a `ListBucketResult`
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Object {
-    #[serde(rename = "LastModified")]
-    /// Date and time the object was last modified.
+    #[serde(rename = "LastModified", default)]
+    /// Date and time the object was last modified, empty when the provider leaves it out
     pub last_modified: String,
     #[serde(rename = "ETag")]
     /// The entity tag is an MD5 hash of the object. The ETag only reflects changes to the
     /// contents of an object, not its metadata.
     pub e_tag: Option<String>,
     #[serde(rename = "StorageClass")]
-    /// STANDARD | STANDARD_IA | REDUCED_REDUNDANCY | GLACIER
-    pub storage_class: Option<String>,
-    #[serde(rename = "Key")]
+    /// Storage class of the object, some providers leave it out
+    pub storage_class: Option<StorageClass>,
+    #[serde(rename = "Key", default)]
     /// The object's key
     pub key: String,
     #[serde(rename = "Owner")]
     /// Bucket owner
     pub owner: Option<Owner>,
-    #[serde(rename = "Size")]
+    #[serde(rename = "Size", default)]
     /// Size in bytes of the object.
     pub size: u64,
 }
 
+impl Object {
+    /// `last_modified` parsed and normalized to UTC, S3 compatible stores don't all report it
+    /// with a `Z` offset
+    pub fn last_modified_at(&self) -> Result<time::OffsetDateTime, S3Error> {
+        let last_modified = time::OffsetDateTime::parse(
+            &self.last_modified,
+            &time::format_description::well_known::Rfc3339,
+        )?;
+        Ok(last_modified.to_offset(time::UtcOffset::UTC))
+    }
+}
+
+/// An `Object` from a listing along with the headers returned by a HEAD request for it, see
+/// `Bucket::list_with_head`
+#[derive(Debug, Clone)]
+pub struct ObjectWithHead {
+    pub object: Object,
+    /// `None` when the HEAD request failed
+    pub head: Option<HeadObjectResult>,
+}
+
 /// An individual upload in a `ListMultipartUploadsResult`
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MultipartUpload {
-    #[serde(rename = "Initiated")]
+    #[serde(
+        rename = "Initiated",
+        deserialize_with = "super::deserializer::rfc3339_deserializer",
+        serialize_with = "super::deserializer::rfc3339_serializer"
+    )]
     /// Date and time the multipart upload was initiated
-    pub initiated: String,
+    pub initiated: time::OffsetDateTime,
     #[serde(rename = "StorageClass")]
     /// STANDARD | STANDARD_IA | REDUCED_REDUNDANCY | GLACIER
     pub storage_class: String,
@@ -179,12 +952,24 @@ pub struct CompleteMultipartUploadData {
     pub parts: Vec<Part>,
 }
 
+/// Body of a successful `UploadPartCopy`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CopyPartResult {
+    #[serde(rename = "ETag")]
+    pub etag: String,
+    #[serde(rename = "LastModified", default)]
+    pub last_modified: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Part {
     #[serde(rename = "PartNumber")]
     pub part_number: u32,
     #[serde(rename = "ETag")]
     pub etag: String,
+    #[serde(rename = "ChecksumCRC32C", skip_serializing_if = "Option::is_none")]
+    /// Base64 encoded CRC32C of the part, required when the upload was initiated with a checksum
+    pub checksum_crc32c: Option<String>,
 }
 
 impl fmt::Display for Part {
@@ -192,22 +977,37 @@ impl fmt::Display for Part {
         write!(f, "<Part>").expect("Can't fail");
         write!(f, "<PartNumber>{}</PartNumber>", self.part_number).expect("Can't fail");
         write!(f, "<ETag>{}</ETag>", self.etag).expect("Can't fail");
+        if let Some(checksum) = &self.checksum_crc32c {
+            write!(f, "<ChecksumCRC32C>{}</ChecksumCRC32C>", checksum).expect("Can't fail");
+        }
         write!(f, "</Part>")
     }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct BucketLocationResult {
-    #[serde(rename = "$value")]
+    #[serde(rename = "$value", default)]
     pub region: String,
 }
 
+impl BucketLocationResult {
+    /// Map the `LocationConstraint` to a `Region`, an empty constraint means `us-east-1`
+    /// and the legacy `EU` token means `eu-west-1`.
+    pub fn to_region(&self) -> Result<Region, RegionError> {
+        match self.region.trim() {
+            "" => Ok(Region::UsEast1),
+            "EU" => Ok(Region::EuWest1),
+            region => Region::from_str(region).map_err(RegionError::from),
+        }
+    }
+}
+
 /// The parsed result of a s3 bucket listing
 ///
 /// This accepts the ListBucketResult format returned for both ListObjects and ListObjectsV2
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ListBucketResult {
-    #[serde(rename = "Name")]
+    #[serde(rename = "Name", default)]
     /// Name of the bucket.
     pub name: String,
     #[serde(rename = "Delimiter")]
@@ -253,10 +1053,32 @@ pub struct ListBucketResult {
     pub common_prefixes: Option<Vec<CommonPrefix>>,
 }
 
+impl ListBucketResult {
+    /// Some providers answer an empty listing with an empty `<Contents/>` element
+    pub(crate) fn drop_keyless_contents(&mut self) {
+        self.contents.retain(|object| !object.key.is_empty());
+    }
+
+    /// ListObjects (v1) only answers `NextMarker` when a delimiter was sent, otherwise a
+    /// truncated page continues after its last key or common prefix.
+    pub(crate) fn fill_next_marker(&mut self) {
+        if !self.is_truncated || self.next_continuation_token.is_some() {
+            return;
+        }
+        let last_key = self.contents.last().map(|object| &object.key);
+        let last_prefix = self
+            .common_prefixes
+            .as_ref()
+            .and_then(|prefixes| prefixes.last())
+            .map(|prefix| &prefix.prefix);
+        self.next_continuation_token = std::cmp::max(last_key, last_prefix).cloned();
+    }
+}
+
 /// The parsed result of a s3 bucket listing of uploads
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ListMultipartUploadsResult {
-    #[serde(rename = "Bucket")]
+    #[serde(rename = "Bucket", default)]
     /// Name of the bucket.
     pub name: String,
     #[serde(rename = "NextKeyMarker")]
@@ -276,6 +1098,7 @@ pub struct ListMultipartUploadsResult {
     /// Specifies the encoding method to used
     pub encoding_type: Option<String>,
     #[serde(
+        default,
         rename = "IsTruncated",
         deserialize_with = "super::deserializer::bool_deserializer"
     )]
@@ -292,16 +1115,261 @@ pub struct ListMultipartUploadsResult {
     pub common_prefixes: Option<Vec<CommonPrefix>>,
 }
 
-/// `CommonPrefix` is used to group keys
-#[derive(Deserialize, Debug, Clone)]
-pub struct CommonPrefix {
-    #[serde(rename = "Prefix")]
-    /// Keys that begin with the indicated prefix.
-    pub prefix: String,
+/// A version of an object in a `ListVersionsResult`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObjectVersion {
+    #[serde(rename = "Key")]
+    /// The object's key
+    pub key: String,
+    #[serde(rename = "VersionId")]
+    /// Version ID of the object, `null` for objects stored while versioning was off
+    pub version_id: String,
+    #[serde(
+        rename = "IsLatest",
+        deserialize_with = "super::deserializer::bool_deserializer"
+    )]
+    /// Whether this is the current version of the object
+    pub is_latest: bool,
+    #[serde(rename = "LastModified")]
+    /// Date and time the version was created.
+    pub last_modified: String,
+    #[serde(rename = "ETag")]
+    pub e_tag: Option<String>,
+    #[serde(rename = "Size")]
+    /// Size in bytes of the version.
+    pub size: u64,
+    #[serde(rename = "StorageClass")]
+    pub storage_class: Option<StorageClass>,
+    #[serde(rename = "Owner")]
+    pub owner: Option<Owner>,
+}
+
+/// A delete marker in a `ListVersionsResult`, left in place of an object deleted while
+/// versioning was on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteMarkerEntry {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "VersionId")]
+    pub version_id: String,
+    #[serde(
+        rename = "IsLatest",
+        deserialize_with = "super::deserializer::bool_deserializer"
+    )]
+    /// Whether the object is currently deleted
+    pub is_latest: bool,
+    #[serde(rename = "LastModified")]
+    pub last_modified: String,
+    #[serde(rename = "Owner")]
+    pub owner: Option<Owner>,
+}
+
+/// The parsed result of a ListObjectVersions request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListVersionsResult {
+    #[serde(rename = "Name", default)]
+    /// Name of the bucket.
+    pub name: String,
+    #[serde(rename = "Prefix")]
+    pub prefix: Option<String>,
+    #[serde(rename = "Delimiter")]
+    pub delimiter: Option<String>,
+    #[serde(rename = "MaxKeys")]
+    pub max_keys: Option<i32>,
+    #[serde(rename = "KeyMarker")]
+    /// Key the listing started after, as sent with the request
+    pub key_marker: Option<String>,
+    #[serde(rename = "VersionIdMarker")]
+    pub version_id_marker: Option<String>,
+    #[serde(
+        default,
+        rename = "IsTruncated",
+        deserialize_with = "super::deserializer::bool_deserializer"
+    )]
+    pub is_truncated: bool,
+    #[serde(rename = "NextKeyMarker")]
+    /// With `next_version_id_marker`, where the next page starts when the listing is truncated
+    pub next_key_marker: Option<String>,
+    #[serde(rename = "NextVersionIdMarker")]
+    pub next_version_id_marker: Option<String>,
+    #[serde(rename = "Version", default)]
+    /// Versions of the listed keys, newest first for each key
+    pub versions: Vec<ObjectVersion>,
+    #[serde(rename = "DeleteMarker", default)]
+    pub delete_markers: Vec<DeleteMarkerEntry>,
+    #[serde(rename = "CommonPrefixes", default)]
+    pub common_prefixes: Option<Vec<CommonPrefix>>,
+}
+
+/// `CommonPrefix` is used to group keys
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommonPrefix {
+    #[serde(rename = "Prefix")]
+    /// Keys that begin with the indicated prefix.
+    pub prefix: String,
+}
+
+/// Prefix of the headers carrying user defined object metadata
+pub const METADATA_HEADER_PREFIX: &str = "x-amz-meta-";
+
+// S3 limits user defined metadata to 2 KB, keys and values combined
+const METADATA_MAX_SIZE: usize = 2048;
+
+/// User defined object metadata, sent and received as `x-amz-meta-*` headers.
+///
+/// Keys are case insensitive and kept lower-cased, the way S3 returns them. Values have to be
+/// printable ASCII, [`Metadata::insert_encoded`] stores anything else RFC 2047 encoded and
+/// [`Metadata::get_decoded`] reads it back.
+///
+/// # Example
+/// ```rust
+/// use s3::serde_types::Metadata;
+///
+/// let mut metadata = Metadata::new();
+/// metadata.insert("Author", "durch").unwrap();
+/// metadata.insert_encoded("Title", "Grüße").unwrap();
+///
+/// assert_eq!(metadata.get("author"), Some("durch"));
+/// assert_eq!(metadata.get("title"), Some("=?UTF-8?B?R3LDvMOfZQ==?="));
+/// assert_eq!(metadata.get_decoded("TITLE").as_deref(), Some("Grüße"));
+/// assert!(metadata.insert("title", "Grüße").is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Metadata(HashMap<String, String>);
+
+impl Metadata {
+    pub fn new() -> Self {
+        Metadata::default()
+    }
+
+    /// Insert a value, failing on keys that aren't valid header names, on values that aren't
+    /// printable ASCII and when the metadata grows past the 2 KB S3 accepts.
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<Option<String>, S3Error> {
+        let key = key.to_ascii_lowercase();
+        let valid_key = !key.is_empty()
+            && key
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+        if !valid_key {
+            return Err(S3Error::InvalidMetadata(format!("invalid key {:?}", key)));
+        }
+        if !value
+            .bytes()
+            .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+        {
+            return Err(S3Error::InvalidMetadata(format!(
+                "value of {} is not printable ASCII",
+                key
+            )));
+        }
+        let size = self.size() + key.len() + value.len()
+            - self.0.get(&key).map_or(0, |old| key.len() + old.len());
+        if size > METADATA_MAX_SIZE {
+            return Err(S3Error::InvalidMetadata(format!(
+                "metadata size {} exceeds {} bytes",
+                size, METADATA_MAX_SIZE
+            )));
+        }
+        Ok(self.0.insert(key, value.to_string()))
+    }
+
+    /// Insert a value, RFC 2047 encoding it when it isn't printable ASCII
+    pub fn insert_encoded(&mut self, key: &str, value: &str) -> Result<Option<String>, S3Error> {
+        if value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            self.insert(key, value)
+        } else {
+            let encoded = format!("=?UTF-8?B?{}?=", general_purpose::STANDARD.encode(value));
+            self.insert(key, &encoded)
+        }
+    }
+
+    /// Metadata received from S3, taken as is
+    pub(crate) fn insert_received(&mut self, key: &str, value: &str) {
+        self.0.insert(key.to_ascii_lowercase(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(&key.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    /// Get a value, decoding it if it is a RFC 2047 encoded word
+    pub fn get_decoded(&self, key: &str) -> Option<String> {
+        self.get(key)
+            .map(|value| decode_encoded_word(value).unwrap_or_else(|| value.to_string()))
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(&key.to_ascii_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// `x-amz-meta-*` headers to send with an upload
+    pub fn headers(&self) -> Result<HeaderMap, S3Error> {
+        let mut headers = HeaderMap::new();
+        for (key, value) in self.iter() {
+            let name =
+                HeaderName::from_bytes(format!("{}{}", METADATA_HEADER_PREFIX, key).as_bytes())
+                    .map_err(|_| S3Error::InvalidMetadata(format!("invalid key {:?}", key)))?;
+            headers.insert(name, value.parse()?);
+        }
+        Ok(headers)
+    }
+
+    fn size(&self) -> usize {
+        self.0.iter().map(|(k, v)| k.len() + v.len()).sum()
+    }
+}
+
+impl From<Metadata> for HashMap<String, String> {
+    fn from(metadata: Metadata) -> Self {
+        metadata.0
+    }
+}
+
+/// Decode a single `=?charset?encoding?text?=` word, UTF-8 and US-ASCII only
+fn decode_encoded_word(value: &str) -> Option<String> {
+    let inner = value.strip_prefix("=?")?.strip_suffix("?=")?;
+    let mut parts = inner.splitn(3, '?');
+    let (charset, encoding, text) = (parts.next()?, parts.next()?, parts.next()?);
+    if !charset.eq_ignore_ascii_case("utf-8") && !charset.eq_ignore_ascii_case("us-ascii") {
+        return None;
+    }
+    let bytes = if encoding.eq_ignore_ascii_case("b") {
+        general_purpose::STANDARD.decode(text).ok()?
+    } else if encoding.eq_ignore_ascii_case("q") {
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut chars = text.bytes();
+        while let Some(b) = chars.next() {
+            match b {
+                b'_' => bytes.push(b' '),
+                b'=' => {
+                    let hex = [chars.next()?, chars.next()?];
+                    bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                }
+                b => bytes.push(b),
+            }
+        }
+        bytes
+    } else {
+        return None;
+    };
+    String::from_utf8(bytes).ok()
 }
 
 // Taken from https://github.com/rusoto/rusoto
-#[derive(Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct HeadObjectResult {
     #[serde(rename = "AcceptRanges")]
     /// Indicates that a range of bytes was specified.
@@ -342,7 +1410,7 @@ pub struct HeadObjectResult {
     pub last_modified: Option<String>,
     #[serde(rename = "Metadata", default)]
     /// A map of metadata to store with the object in S3.
-    pub metadata: Option<::std::collections::HashMap<String, String>>,
+    pub metadata: Option<Metadata>,
     #[serde(rename = "MissingMeta")]
     /// This is set to the number of metadata entries not returned in x-amz-meta headers. This can happen if you create metadata using an API like SOAP that supports more flexible metadata than
     /// the REST API. For example, using SOAP, you can create metadata whose values are not legal HTTP headers.
@@ -382,10 +1450,10 @@ pub struct HeadObjectResult {
     #[serde(rename = "ServerSideEncryption")]
     /// If the object is stored using server-side encryption either with an AWS KMS customer master key (CMK) or an Amazon S3-managed encryption key,
     /// The response includes this header with the value of the server-side encryption algorithm used when storing this object in Amazon S3 (for example, AES256, aws:kms).
-    pub server_side_encryption: Option<String>,
+    pub server_side_encryption: Option<ServerSideEncryption>,
     #[serde(rename = "StorageClass")]
     /// Provides storage class information of the object. Amazon S3 returns this header for all objects except for S3 Standard storage class objects.
-    pub storage_class: Option<String>,
+    pub storage_class: Option<StorageClass>,
     #[serde(rename = "VersionId")]
     /// Version of the object.
     pub version_id: Option<String>,
@@ -394,14 +1462,39 @@ pub struct HeadObjectResult {
     pub website_redirect_location: Option<String>,
 }
 
+/// An object as returned by `Bucket::get_object_full`, its headers parsed as `head_object`
+/// parses them
+#[derive(Debug, Clone)]
+pub struct GetObjectOutput {
+    /// ETag, content type, last modified, length, version, SSE fields and metadata
+    pub head: HeadObjectResult,
+    pub status_code: u16,
+    pub body: bytes::Bytes,
+}
+
+impl From<ResponseData> for GetObjectOutput {
+    fn from(response_data: ResponseData) -> Self {
+        GetObjectOutput {
+            head: HeadObjectResult::from(response_data.headers()),
+            status_code: response_data.status_code(),
+            body: response_data.into_bytes(),
+        }
+    }
+}
+
+/// `<Error>` document of a failed request, see `S3Error::AwsError`
 #[derive(Deserialize, Debug)]
 pub struct AwsError {
     #[serde(rename = "Code")]
     pub code: String,
-    #[serde(rename = "Message")]
+    #[serde(rename = "Message", default)]
     pub message: String,
-    #[serde(rename = "RequestId")]
-    pub request_id: String,
+    #[serde(rename = "RequestId", default)]
+    pub request_id: Option<String>,
+    #[serde(rename = "HostId", default)]
+    pub host_id: Option<String>,
+    #[serde(rename = "Resource", default)]
+    pub resource: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -415,23 +1508,41 @@ impl CorsConfiguration {
     pub fn new(rules: Vec<CorsRule>) -> Self {
         CorsConfiguration { rules }
     }
+
+    /// Preset for the common single-page-app setup, browsers served from `origins` may read
+    /// and upload objects directly (e.g. through presigned URLs) and see the resulting `ETag`.
+    pub fn spa(origins: &[&str]) -> Self {
+        let rule = origins
+            .iter()
+            .fold(CorsRule::builder(), |builder, origin| {
+                builder.allowed_origin(origin)
+            })
+            .allowed_methods(&[
+                HttpMethod::Get,
+                HttpMethod::Head,
+                HttpMethod::Put,
+                HttpMethod::Post,
+            ])
+            .allowed_header("*")
+            .expose_header("ETag")
+            .max_age_seconds(3600)
+            .build();
+        CorsConfiguration::new(vec![rule])
+    }
+}
+
+impl XmlDocument for CorsConfiguration {
+    const ROOT: &'static str = "CORSConfiguration";
 }
 
 impl fmt::Display for CorsConfiguration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let cors = quick_xml::se::to_string(&self).map_err(|_| fmt::Error)?;
-        let preamble = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>";
-        let cors = format!("{}{}", preamble, cors);
-        let cors = cors.replace(
-            "<CORSConfiguration>",
-            "<CORSConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">",
-        );
-
+        let cors = self.to_xml().map_err(|_| fmt::Error)?;
         write!(f, "{}", cors)
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct CorsRule {
     #[serde(rename = "AllowedHeader")]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -469,6 +1580,70 @@ impl CorsRule {
             max_age_seconds,
         }
     }
+
+    pub fn builder() -> CorsRuleBuilder {
+        CorsRuleBuilder::new()
+    }
+}
+
+#[derive(Default)]
+pub struct CorsRuleBuilder {
+    cors_rule: CorsRule,
+}
+
+impl CorsRuleBuilder {
+    pub fn new() -> CorsRuleBuilder {
+        CorsRuleBuilder::default()
+    }
+
+    pub fn allowed_header(mut self, header: &str) -> CorsRuleBuilder {
+        self.cors_rule
+            .allowed_headers
+            .get_or_insert_with(Vec::new)
+            .push(header.to_string());
+        self
+    }
+
+    pub fn allowed_method(mut self, method: HttpMethod) -> CorsRuleBuilder {
+        let method = method.to_string();
+        if !self.cors_rule.allowed_methods.contains(&method) {
+            self.cors_rule.allowed_methods.push(method);
+        }
+        self
+    }
+
+    pub fn allowed_methods(self, methods: &[HttpMethod]) -> CorsRuleBuilder {
+        methods
+            .iter()
+            .fold(self, |builder, method| builder.allowed_method(*method))
+    }
+
+    pub fn allowed_origin(mut self, origin: &str) -> CorsRuleBuilder {
+        self.cors_rule.allowed_origins.push(origin.to_string());
+        self
+    }
+
+    pub fn expose_header(mut self, header: &str) -> CorsRuleBuilder {
+        self.cors_rule
+            .expose_headers
+            .get_or_insert_with(Vec::new)
+            .push(header.to_string());
+        self
+    }
+
+    pub fn id(mut self, id: &str) -> CorsRuleBuilder {
+        self.cors_rule.id = Some(id.to_string());
+        self
+    }
+
+    pub fn max_age_seconds(mut self, max_age_seconds: u32) -> CorsRuleBuilder {
+        self.cors_rule.max_age_seconds = Some(max_age_seconds);
+        self
+    }
+
+    pub fn build(self) -> CorsRule {
+        self.cors_rule
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -484,6 +1659,110 @@ impl BucketLifecycleConfiguration {
     }
 }
 
+impl XmlDocument for BucketLifecycleConfiguration {
+    const ROOT: &'static str = "LifecycleConfiguration";
+}
+
+/// Versioning state of a bucket, changing `mfa_delete` requires an MFA token
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename = "VersioningConfiguration")]
+pub struct VersioningConfiguration {
+    #[serde(rename = "Status", skip_serializing_if = "Option::is_none")]
+    pub status: Option<VersioningStatus>,
+    #[serde(rename = "MfaDelete", skip_serializing_if = "Option::is_none")]
+    pub mfa_delete: Option<MfaDeleteStatus>,
+}
+
+impl VersioningConfiguration {
+    pub fn new(status: VersioningStatus) -> Self {
+        VersioningConfiguration {
+            status: Some(status),
+            mfa_delete: None,
+        }
+    }
+
+    pub fn mfa_delete(mut self, mfa_delete: MfaDeleteStatus) -> Self {
+        self.mfa_delete = Some(mfa_delete);
+        self
+    }
+}
+
+impl XmlDocument for VersioningConfiguration {
+    const ROOT: &'static str = "VersioningConfiguration";
+}
+
+/// Body of a `DeleteObjects` request, the keys deleted by a single request
+#[derive(Serialize, Debug, Clone)]
+pub struct DeleteObjectsRequest {
+    #[serde(rename = "Object")]
+    pub objects: Vec<ObjectIdentifier>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ObjectIdentifier {
+    #[serde(rename = "Key")]
+    pub key: String,
+}
+
+impl DeleteObjectsRequest {
+    pub fn new(keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        DeleteObjectsRequest {
+            objects: keys
+                .into_iter()
+                .map(|key| ObjectIdentifier { key: key.into() })
+                .collect(),
+        }
+    }
+}
+
+impl XmlDocument for DeleteObjectsRequest {
+    const ROOT: &'static str = "Delete";
+}
+
+/// Response to a `DeleteObjects` request, which succeeds as a whole while listing the keys it
+/// could not delete
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DeleteObjectsResult {
+    #[serde(rename = "Deleted", default)]
+    pub deleted: Vec<DeletedObject>,
+    #[serde(rename = "Error", default)]
+    pub errors: Vec<DeleteObjectError>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DeletedObject {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "VersionId")]
+    pub version_id: Option<String>,
+    #[serde(rename = "DeleteMarker")]
+    pub delete_marker: Option<bool>,
+    #[serde(rename = "DeleteMarkerVersionId")]
+    pub delete_marker_version_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DeleteObjectError {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "Message")]
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersioningStatus {
+    Enabled,
+    Suspended,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MfaDeleteStatus {
+    Enabled,
+    Disabled,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct LifecycleRule {
     #[serde(
@@ -719,6 +1998,32 @@ pub struct Tag {
     pub value: String,
 }
 
+/// Object tagging document, body of `PutObjectTagging`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename = "Tagging")]
+pub struct Tagging {
+    #[serde(rename = "TagSet")]
+    pub tag_set: TagSet,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TagSet {
+    #[serde(rename = "Tag", default)]
+    pub tags: Vec<Tag>,
+}
+
+impl Tagging {
+    pub fn new(tags: Vec<Tag>) -> Self {
+        Tagging {
+            tag_set: TagSet { tags },
+        }
+    }
+}
+
+impl XmlDocument for Tagging {
+    const ROOT: &'static str = "Tagging";
+}
+
 impl Tag {
     pub fn new(key: &str, value: &str) -> Self {
         Self {
@@ -808,7 +2113,211 @@ mod test {
         LifecycleRule, NoncurrentVersionExpiration, NoncurrentVersionTransition, Transition,
     };
 
-    use super::{CorsConfiguration, CorsRule};
+    use super::{
+        BucketLocationResult, CopyObjectOptions, CorsConfiguration, CorsRule, DeleteObjectsRequest,
+        DeleteObjectsResult, GetObjectAttributesOptions, GetObjectAttributesOutput,
+        GetObjectConditions, ListBucketResult, ListMultipartUploadsResult, ListVersionsResult,
+        Metadata, MfaDeleteStatus, Object, ObjectAttribute, ResponseOverrides,
+        ServerSideEncryption, StorageClass, Tag, Tagging, VersioningConfiguration,
+        VersioningStatus, XmlDocument,
+    };
+    use crate::command::HttpMethod;
+    use crate::region::Region;
+
+    #[test]
+    fn multipart_lifecycle_responses_carry_headers() {
+        use super::{AbortMultipartUploadResponse, InitiateMultipartUploadResponse};
+        use crate::request::ResponseData;
+        use http::{HeaderMap, HeaderValue};
+
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<InitiateMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Bucket>example-bucket</Bucket>
+  <Key>example-object</Key>
+  <UploadId>VXBsb2FkIElEIGZvciA2aWWpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA</UploadId>
+</InitiateMultipartUploadResult>"#;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-amz-request-id",
+            HeaderValue::from_static("656C76696E6727732072657175657374"),
+        );
+        headers.insert(
+            "x-amz-server-side-encryption",
+            HeaderValue::from_static("aws:kms"),
+        );
+        headers.insert(
+            "x-amz-server-side-encryption-aws-kms-key-id",
+            HeaderValue::from_static("key-1"),
+        );
+        let response_data = ResponseData::new(body.into(), 200, headers);
+        let msg = InitiateMultipartUploadResponse::from_response_data(&response_data).unwrap();
+        assert_eq!(msg.key, "example-object");
+        assert_eq!(msg.status_code, 200);
+        assert_eq!(
+            msg.request_id.as_deref(),
+            Some("656C76696E6727732072657175657374")
+        );
+        assert_eq!(msg.extended_request_id, None);
+        assert_eq!(
+            msg.server_side_encryption,
+            Some(ServerSideEncryption::AwsKms)
+        );
+        assert_eq!(msg.ssekms_key_id.as_deref(), Some("key-1"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-amz-request-id",
+            HeaderValue::from_static("4442587FB7D0A2F9"),
+        );
+        let response_data = ResponseData::new(Default::default(), 204, headers);
+        assert_eq!(
+            AbortMultipartUploadResponse::from(&response_data),
+            AbortMultipartUploadResponse {
+                status_code: 204,
+                request_id: Some("4442587FB7D0A2F9".to_string()),
+                extended_request_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn copy_object_tagging_headers() {
+        assert!(CopyObjectOptions::new().headers().unwrap().is_empty());
+
+        let headers = CopyObjectOptions::new().copy_tags().headers().unwrap();
+        assert_eq!(headers["x-amz-tagging-directive"], "COPY");
+        assert!(headers.get("x-amz-tagging").is_none());
+
+        let headers = CopyObjectOptions::new()
+            .replace_tags([("stage", "archived"), ("owner", "data team&co")])
+            .headers()
+            .unwrap();
+        assert_eq!(headers["x-amz-tagging-directive"], "REPLACE");
+        assert_eq!(
+            headers["x-amz-tagging"],
+            "stage=archived&owner=data%20team%26co"
+        );
+
+        let untagged = CopyObjectOptions::new().replace_tags(Vec::<(String, String)>::new());
+        let headers = untagged.headers().unwrap();
+        assert_eq!(headers["x-amz-tagging-directive"], "REPLACE");
+        assert!(headers.get("x-amz-tagging").is_none());
+    }
+
+    #[test]
+    fn copy_object_metadata_headers() {
+        let mut metadata = Metadata::new();
+        metadata.insert("Stage", "archived").unwrap();
+        let headers = CopyObjectOptions::new()
+            .replace_metadata(metadata)
+            .storage_class(StorageClass::StandardIa)
+            .sse_kms("arn:aws:kms:us-east-1:111122223333:key/1234")
+            .headers()
+            .unwrap();
+        assert_eq!(headers["x-amz-metadata-directive"], "REPLACE");
+        assert_eq!(headers["x-amz-meta-stage"], "archived");
+        assert!(headers.get("content-type").is_none());
+        assert_eq!(headers["x-amz-storage-class"], "STANDARD_IA");
+        assert_eq!(headers["x-amz-server-side-encryption"], "aws:kms");
+        assert_eq!(
+            headers["x-amz-server-side-encryption-aws-kms-key-id"],
+            "arn:aws:kms:us-east-1:111122223333:key/1234"
+        );
+
+        let headers = CopyObjectOptions::new()
+            .content_type("application/pdf")
+            .headers()
+            .unwrap();
+        assert_eq!(headers["x-amz-metadata-directive"], "REPLACE");
+        assert_eq!(headers["content-type"], "application/pdf");
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn get_object_condition_headers() {
+        assert!(GetObjectConditions::new().headers().unwrap().is_empty());
+
+        let since = time::macros::datetime!(1994-11-06 08:49:37 +02:00);
+        let headers = GetObjectConditions::new()
+            .if_none_match("\"abc\"")
+            .if_modified_since(since)
+            .headers()
+            .unwrap();
+        assert_eq!(headers["if-none-match"], "\"abc\"");
+        assert_eq!(
+            headers["if-modified-since"],
+            "Sun, 06 Nov 1994 06:49:37 GMT"
+        );
+        assert!(headers.get("if-match").is_none());
+    }
+
+    #[test]
+    fn get_object_output_parses_headers() {
+        use super::{GetObjectOutput, ServerSideEncryption};
+        use crate::request::ResponseData;
+        use http::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("ETag", HeaderValue::from_static("\"abc\""));
+        headers.insert("Content-Type", HeaderValue::from_static("text/plain"));
+        headers.insert("Content-Length", HeaderValue::from_static("5"));
+        headers.insert("x-amz-version-id", HeaderValue::from_static("v1"));
+        headers.insert(
+            "x-amz-server-side-encryption",
+            HeaderValue::from_static("AES256"),
+        );
+        headers.insert("x-amz-meta-stage", HeaderValue::from_static("draft"));
+        let response_data = ResponseData::new("hello".into(), 200, headers);
+
+        let output = GetObjectOutput::from(response_data);
+        assert_eq!(output.body.as_ref(), b"hello");
+        assert_eq!(output.status_code, 200);
+        assert_eq!(output.head.e_tag.as_deref(), Some("\"abc\""));
+        assert_eq!(output.head.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(output.head.content_length, Some(5));
+        assert_eq!(output.head.version_id.as_deref(), Some("v1"));
+        assert!(matches!(
+            output.head.server_side_encryption,
+            Some(ServerSideEncryption::Aes256)
+        ));
+        assert_eq!(output.head.metadata.unwrap().get("stage"), Some("draft"));
+    }
+
+    #[test]
+    fn response_override_queries() {
+        assert!(ResponseOverrides::new().queries().unwrap().is_empty());
+
+        let queries = ResponseOverrides::new()
+            .content_type("text/csv")
+            .expires(time::macros::datetime!(1994-11-06 08:49:37 UTC))
+            .queries()
+            .unwrap();
+        assert_eq!(queries["response-content-type"], "text/csv");
+        assert_eq!(queries["response-expires"], "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(queries.len(), 2);
+    }
+
+    #[test]
+    fn object_last_modified_is_normalized_to_utc() {
+        let mut object = Object {
+            last_modified: "2024-03-01T10:30:00.000Z".to_string(),
+            e_tag: None,
+            storage_class: None,
+            key: "key".to_string(),
+            owner: None,
+            size: 0,
+        };
+        let utc = object.last_modified_at().unwrap();
+        assert_eq!(utc, time::macros::datetime!(2024-03-01 10:30 UTC));
+
+        object.last_modified = "2024-03-01T12:30:00+02:00".to_string();
+        let normalized = object.last_modified_at().unwrap();
+        assert_eq!(normalized, utc);
+        assert_eq!(normalized.offset(), time::UtcOffset::UTC);
+
+        object.last_modified = "Fri, 01 Mar 2024 10:30:00 GMT".to_string();
+        assert!(object.last_modified_at().is_err());
+    }
 
     #[test]
     fn cors_config_serde() {
@@ -832,6 +2341,120 @@ mod test {
         )
     }
 
+    #[test]
+    fn cors_rule_builder() {
+        let rule = CorsRule::builder()
+            .allowed_header("Authorization")
+            .allowed_header("Header2")
+            .allowed_method(HttpMethod::Get)
+            .allowed_method(HttpMethod::Delete)
+            .allowed_method(HttpMethod::Get)
+            .allowed_origin("*")
+            .id("lala")
+            .build();
+
+        let expected = CorsRule::new(
+            Some(vec!["Authorization".to_string(), "Header2".to_string()]),
+            vec!["GET".to_string(), "DELETE".to_string()],
+            vec!["*".to_string()],
+            None,
+            Some("lala".to_string()),
+            None,
+        );
+        assert_eq!(rule, expected);
+    }
+
+    #[test]
+    fn cors_config_spa() {
+        let config = CorsConfiguration::spa(&["https://app.example.com"]);
+
+        let se = quick_xml::se::to_string(&config).unwrap();
+        assert_eq!(
+            se,
+            r#"<CORSConfiguration><CORSRule><AllowedHeader>*</AllowedHeader><AllowedMethod>GET</AllowedMethod><AllowedMethod>HEAD</AllowedMethod><AllowedMethod>PUT</AllowedMethod><AllowedMethod>POST</AllowedMethod><AllowedOrigin>https://app.example.com</AllowedOrigin><ExposeHeader>ETag</ExposeHeader><MaxAgeSeconds>3600</MaxAgeSeconds></CORSRule></CORSConfiguration>"#
+        )
+    }
+
+    #[test]
+    fn xml_document_declares_namespace() {
+        let config = CorsConfiguration::new(vec![CorsRule::builder()
+            .allowed_method(HttpMethod::Get)
+            .allowed_origin("*")
+            .build()]);
+        assert_eq!(
+            config.to_xml().unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><CORSConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><CORSRule><AllowedMethod>GET</AllowedMethod><AllowedOrigin>*</AllowedOrigin></CORSRule></CORSConfiguration>"#
+        );
+        assert_eq!(config.to_string(), config.to_xml().unwrap());
+
+        let tagging = Tagging::new(vec![Tag::new("a&b", "<c>")]);
+        assert_eq!(
+            tagging.to_xml().unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?><Tagging xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><TagSet><Tag><Key>a&amp;b</Key><Value>&lt;c&gt;</Value></Tag></TagSet></Tagging>"#
+        );
+    }
+
+    #[test]
+    fn bucket_location_result_to_region() {
+        let location: BucketLocationResult = quick_xml::de::from_str(
+            r#"<LocationConstraint xmlns="http://s3.amazonaws.com/doc/2006-03-01/"/>"#,
+        )
+        .unwrap();
+        assert_eq!(location.to_region().unwrap(), Region::UsEast1);
+
+        let location: BucketLocationResult = quick_xml::de::from_str(
+            r#"<LocationConstraint xmlns="http://s3.amazonaws.com/doc/2006-03-01/">EU</LocationConstraint>"#,
+        )
+        .unwrap();
+        assert_eq!(location.to_region().unwrap(), Region::EuWest1);
+
+        let location: BucketLocationResult = quick_xml::de::from_str(
+            r#"<LocationConstraint xmlns="http://s3.amazonaws.com/doc/2006-03-01/">eu-central-1</LocationConstraint>"#,
+        )
+        .unwrap();
+        assert_eq!(location.to_region().unwrap(), Region::EuCentral1);
+    }
+
+    #[test]
+    fn delete_objects_serde() {
+        let xml = DeleteObjectsRequest::new(["a.txt", "b&c.txt"])
+            .to_xml()
+            .unwrap();
+        assert!(xml.ends_with(
+            "<Object><Key>a.txt</Key></Object><Object><Key>b&amp;c.txt</Key></Object></Delete>"
+        ));
+
+        let result: DeleteObjectsResult = quick_xml::de::from_str(
+            r#"<DeleteResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Deleted><Key>a.txt</Key></Deleted>
+                <Deleted>
+                    <Key>b.txt</Key>
+                    <DeleteMarker>true</DeleteMarker>
+                    <DeleteMarkerVersionId>v2</DeleteMarkerVersionId>
+                </Deleted>
+                <Error><Key>c.txt</Key><Code>AccessDenied</Code><Message>Access Denied</Message></Error>
+            </DeleteResult>"#,
+        )
+        .unwrap();
+        assert_eq!(result.deleted.len(), 2);
+        assert_eq!(result.deleted[1].delete_marker, Some(true));
+        assert_eq!(
+            result.deleted[1].delete_marker_version_id.as_deref(),
+            Some("v2")
+        );
+        assert_eq!(result.errors[0].key, "c.txt");
+        assert_eq!(result.errors[0].code, "AccessDenied");
+    }
+
+    #[test]
+    fn versioning_config_to_xml() {
+        let config = VersioningConfiguration::new(VersioningStatus::Enabled)
+            .mfa_delete(MfaDeleteStatus::Enabled);
+        let xml = config.to_xml().unwrap();
+        assert!(xml.contains("<Status>Enabled</Status><MfaDelete>Enabled</MfaDelete>"));
+        assert!(xml.starts_with("<?xml"));
+    }
+
     #[test]
     fn lifecycle_config_serde() {
         let rule = LifecycleRule {
@@ -876,4 +2499,281 @@ mod test {
             r#"<LifecycleConfiguration><Rule><AbortIncompleteMultipartUpload><DaysAfterInitiation>30</DaysAfterInitiation></AbortIncompleteMultipartUpload><Expiration><Date>2024-06-017</Date><Days>30</Days><ExpiredObjectDeleteMarker>true</ExpiredObjectDeleteMarker></Expiration><Filter><ObjectSizeGreaterThan>10</ObjectSizeGreaterThan><ObjectSizeLessThan>50</ObjectSizeLessThan></Filter><ID>lala</ID><NoncurrentVersionExpiration><NewerNoncurrentVersions>30</NewerNoncurrentVersions><NoncurrentDays>30</NoncurrentDays></NoncurrentVersionExpiration><NoncurrentVersionTransition><NewerNoncurrentVersions>30</NewerNoncurrentVersions><NoncurrentDays>30</NoncurrentDays><StorageClass>GLACIER</StorageClass></NoncurrentVersionTransition><Status>Enabled</Status><Transition><Date>2024-06-017</Date><Days>30</Days><StorageClass>GLACIER</StorageClass></Transition></Rule></LifecycleConfiguration>"#
         )
     }
+
+    #[test]
+    fn metadata_normalizes_and_validates() {
+        let mut metadata = Metadata::new();
+        metadata.insert("Content-Origin", "upload").unwrap();
+        assert_eq!(metadata.get("content-origin"), Some("upload"));
+        assert_eq!(metadata.get("CONTENT-ORIGIN"), Some("upload"));
+
+        assert!(metadata.insert("with space", "value").is_err());
+        assert!(metadata.insert("", "value").is_err());
+        assert!(metadata.insert("title", "caf\u{e9}").is_err());
+        assert!(metadata.insert("big", &"a".repeat(2048)).is_err());
+
+        metadata.insert_encoded("Title", "caf\u{e9}").unwrap();
+        assert_eq!(metadata.get("title"), Some("=?UTF-8?B?Y2Fmw6k=?="));
+        assert_eq!(metadata.get_decoded("title").unwrap(), "caf\u{e9}");
+        metadata
+            .insert("quoted", "=?utf-8?Q?caf=C3=A9_au_lait?=")
+            .unwrap();
+        assert_eq!(metadata.get_decoded("quoted").unwrap(), "caf\u{e9} au lait");
+
+        let headers = metadata.headers().unwrap();
+        assert_eq!(headers.get("x-amz-meta-content-origin").unwrap(), "upload");
+        assert_eq!(headers.len(), 3);
+    }
+
+    #[test]
+    fn multipart_upload_initiated_is_parsed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListMultipartUploadsResult>
+    <Bucket>bucket</Bucket>
+    <KeyMarker></KeyMarker>
+    <NextKeyMarker>my-movie.m2ts</NextKeyMarker>
+    <IsTruncated>true</IsTruncated>
+    <Upload>
+        <Key>my-movie.m2ts</Key>
+        <UploadId>VXBsb2FkIElEIGZvciBlbHZpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA</UploadId>
+        <StorageClass>STANDARD</StorageClass>
+        <Initiated>2010-11-10T20:48:33.000Z</Initiated>
+    </Upload>
+</ListMultipartUploadsResult>"#;
+        let result: ListMultipartUploadsResult = quick_xml::de::from_str(xml).unwrap();
+        assert!(result.is_truncated);
+        assert_eq!(
+            result.uploads[0].initiated,
+            time::macros::datetime!(2010-11-10 20:48:33 UTC)
+        );
+    }
+
+    #[test]
+    fn interleaved_versions_and_delete_markers_are_parsed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListVersionsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+    <Name>bucket</Name>
+    <Prefix>photos/</Prefix>
+    <KeyMarker></KeyMarker>
+    <VersionIdMarker></VersionIdMarker>
+    <MaxKeys>3</MaxKeys>
+    <IsTruncated>true</IsTruncated>
+    <NextKeyMarker>photos/b.png</NextKeyMarker>
+    <NextVersionIdMarker>null</NextVersionIdMarker>
+    <DeleteMarker>
+        <Key>photos/a.png</Key>
+        <VersionId>3HL4kqtJlcpXroDTDmJ+rmSpXd3dIbrHY</VersionId>
+        <IsLatest>true</IsLatest>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+    </DeleteMarker>
+    <Version>
+        <Key>photos/a.png</Key>
+        <VersionId>3/L4kqtJl40Nr8X8gdRQBpUMLUo</VersionId>
+        <IsLatest>false</IsLatest>
+        <LastModified>2024-02-01T10:30:00.000Z</LastModified>
+        <ETag>"fba9dede5f27731c9771645a39863328"</ETag>
+        <Size>434234</Size>
+        <StorageClass>STANDARD</StorageClass>
+    </Version>
+    <Version>
+        <Key>photos/b.png</Key>
+        <VersionId>null</VersionId>
+        <IsLatest>true</IsLatest>
+        <LastModified>2024-01-01T10:30:00.000Z</LastModified>
+        <ETag>"9b2cf535f27731c974343645a3985328"</ETag>
+        <Size>166434</Size>
+        <StorageClass>STANDARD</StorageClass>
+    </Version>
+</ListVersionsResult>"#;
+        let result: ListVersionsResult = quick_xml::de::from_str(xml).unwrap();
+        assert!(result.is_truncated);
+        assert_eq!(result.next_key_marker.as_deref(), Some("photos/b.png"));
+        assert_eq!(result.next_version_id_marker.as_deref(), Some("null"));
+        assert_eq!(result.delete_markers.len(), 1);
+        assert!(result.delete_markers[0].is_latest);
+        assert_eq!(result.versions.len(), 2);
+        assert!(!result.versions[0].is_latest);
+        assert_eq!(result.versions[1].version_id, "null");
+        assert_eq!(result.versions[1].size, 166434);
+    }
+
+    #[test]
+    fn truncated_v1_page_continues_after_last_key() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+    <Name>bucket</Name>
+    <MaxKeys>2</MaxKeys>
+    <IsTruncated>true</IsTruncated>
+    <Contents>
+        <Key>a.log</Key>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+        <Size>10</Size>
+    </Contents>
+    <Contents>
+        <Key>b.log</Key>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+        <Size>10</Size>
+    </Contents>
+</ListBucketResult>"#;
+        let mut result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+        result.fill_next_marker();
+        assert_eq!(result.next_continuation_token.as_deref(), Some("b.log"));
+
+        result.is_truncated = false;
+        result.next_continuation_token = None;
+        result.fill_next_marker();
+        assert_eq!(result.next_continuation_token, None);
+    }
+
+    // Listings in the shape MinIO, Ceph RGW, GCS and R2 answer with
+    const MINIO_LISTING: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>test</Name><Prefix></Prefix><KeyCount>1</KeyCount><MaxKeys>1000</MaxKeys><Delimiter>/</Delimiter><IsTruncated>false</IsTruncated><Contents><Key>a.txt</Key><LastModified>2024-03-01T10:30:00.000Z</LastModified><ETag>&#34;9b2cf535f27731c974343645a3985328&#34;</ETag><Size>11</Size><Owner><ID>02d6176db174dc93cb1b899f7c6078f08654445fe8cf1b6ce98d8855f66bdbf4</ID><DisplayName>minio</DisplayName></Owner><StorageClass>STANDARD</StorageClass></Contents><EncodingType>url</EncodingType></ListBucketResult>"#;
+
+    const CEPH_LISTING: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>test</Name><Prefix></Prefix><Marker></Marker><MaxKeys>1000</MaxKeys><IsTruncated>false</IsTruncated><Contents><Key>a.txt</Key><LastModified>2024-03-01T10:30:00.000Z</LastModified><ETag>&quot;9b2cf535f27731c974343645a3985328&quot;</ETag><Size>11</Size><StorageClass>STANDARD</StorageClass><Owner><ID>tester</ID><DisplayName>tester</DisplayName></Owner><Type>Normal</Type></Contents></ListBucketResult>"#;
+
+    const GCS_LISTING: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<ListBucketResult xmlns="http://doc.s3.amazonaws.com/2006-03-01"><Name>test</Name><Prefix/><Marker/><IsTruncated>false</IsTruncated><Contents><Key>a.txt</Key><Generation>1709289000000000</Generation><MetaGeneration>1</MetaGeneration><LastModified>2024-03-01T10:30:00.000Z</LastModified><ETag>"9b2cf535f27731c974343645a3985328"</ETag><Size>11</Size></Contents></ListBucketResult>"#;
+
+    const R2_LISTING: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Contents><Key>a.txt</Key><Size>11</Size><LastModified>2024-03-01T10:30:00.000Z</LastModified><ETag>"9b2cf535f27731c974343645a3985328"</ETag><StorageClass>STANDARD</StorageClass></Contents><IsTruncated>false</IsTruncated><Name>test</Name><Prefix></Prefix><KeyCount>1</KeyCount><MaxKeys>1000</MaxKeys></ListBucketResult>"#;
+
+    #[test]
+    fn provider_listings_parse() {
+        for xml in [MINIO_LISTING, CEPH_LISTING, GCS_LISTING, R2_LISTING] {
+            let result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+            assert_eq!(result.name, "test");
+            assert!(!result.is_truncated);
+            assert_eq!(result.contents.len(), 1);
+            let object = &result.contents[0];
+            assert_eq!(object.key, "a.txt");
+            assert_eq!(object.size, 11);
+            assert_eq!(
+                object.e_tag.as_deref(),
+                Some("\"9b2cf535f27731c974343645a3985328\"")
+            );
+            assert!(object.last_modified_at().is_ok());
+        }
+    }
+
+    #[test]
+    fn listings_missing_optional_fields_parse() {
+        let xml = r#"<ListBucketResult><KeyCount>0</KeyCount><Contents/></ListBucketResult>"#;
+        let mut result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+        result.drop_keyless_contents();
+        assert_eq!(result.name, "");
+        assert!(!result.is_truncated);
+        assert!(result.contents.is_empty());
+
+        let xml = r#"<ListBucketResult><Contents><Key>a.txt</Key><Owner></Owner></Contents></ListBucketResult>"#;
+        let result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(result.contents[0].size, 0);
+        assert_eq!(result.contents[0].owner.as_ref().unwrap().id, "");
+
+        let xml = r#"<ListMultipartUploadsResult><Upload><Key>a.txt</Key><UploadId>VXBsb2FkIElE</UploadId><StorageClass>STANDARD</StorageClass><Initiated>2010-11-10T20:48:33.000Z</Initiated></Upload></ListMultipartUploadsResult>"#;
+        let result: ListMultipartUploadsResult = quick_xml::de::from_str(xml).unwrap();
+        assert!(!result.is_truncated);
+        assert_eq!(result.uploads.len(), 1);
+    }
+
+    #[test]
+    fn list_results_serialize_with_s3_field_names() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+    <Name>bucket</Name>
+    <IsTruncated>false</IsTruncated>
+    <Contents>
+        <Key>a.log</Key>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+        <StorageClass>GLACIER_IR</StorageClass>
+        <Size>10</Size>
+    </Contents>
+</ListBucketResult>"#;
+        let result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["Name"], "bucket");
+        assert_eq!(json["IsTruncated"], false);
+        assert_eq!(json["Contents"][0]["Key"], "a.log");
+        assert_eq!(json["Contents"][0]["StorageClass"], "GLACIER_IR");
+        assert_eq!(json["Contents"][0]["Size"], 10);
+
+        let xml = r#"<ListMultipartUploadsResult>
+    <Bucket>bucket</Bucket>
+    <IsTruncated>false</IsTruncated>
+    <Upload>
+        <Key>my-movie.m2ts</Key>
+        <UploadId>VXBsb2FkIElE</UploadId>
+        <StorageClass>STANDARD</StorageClass>
+        <Initiated>2010-11-10T20:48:33.000Z</Initiated>
+    </Upload>
+</ListMultipartUploadsResult>"#;
+        let result: ListMultipartUploadsResult = quick_xml::de::from_str(xml).unwrap();
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["Upload"][0]["Initiated"], "2010-11-10T20:48:33Z");
+    }
+
+    #[test]
+    fn object_storage_class_is_typed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+    <Name>bucket</Name>
+    <IsTruncated>false</IsTruncated>
+    <Contents>
+        <Key>archived.log</Key>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+        <Size>10</Size>
+        <StorageClass>DEEP_ARCHIVE</StorageClass>
+    </Contents>
+    <Contents>
+        <Key>cold.log</Key>
+        <LastModified>2024-03-01T10:30:00.000Z</LastModified>
+        <Size>10</Size>
+        <StorageClass>COLD</StorageClass>
+    </Contents>
+</ListBucketResult>"#;
+        let result: ListBucketResult = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(
+            result.contents[0].storage_class,
+            Some(StorageClass::DeepArchive)
+        );
+        assert_eq!(
+            result.contents[1].storage_class,
+            Some(StorageClass::Other("COLD".to_string()))
+        );
+        assert_eq!(StorageClass::DeepArchive.to_string(), "DEEP_ARCHIVE");
+
+        assert_eq!(
+            "aws:kms".parse::<ServerSideEncryption>(),
+            Ok(ServerSideEncryption::AwsKms)
+        );
+        assert_eq!(ServerSideEncryption::from("sm4").as_str(), "sm4");
+    }
+
+    #[test]
+    fn get_object_attributes_partial_output() {
+        let options = GetObjectAttributesOptions::new(&[
+            ObjectAttribute::ObjectSize,
+            ObjectAttribute::ObjectParts,
+            ObjectAttribute::ObjectSize,
+        ]);
+        assert_eq!(options.attributes_header(), "ObjectSize,ObjectParts");
+
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<GetObjectAttributesOutput>
+    <ObjectParts>
+        <PartsCount>2</PartsCount>
+        <Part><PartNumber>1</PartNumber><Size>5242880</Size></Part>
+        <Part><PartNumber>2</PartNumber><Size>100</Size></Part>
+    </ObjectParts>
+    <ObjectSize>5242980</ObjectSize>
+</GetObjectAttributesOutput>"#;
+        let output: GetObjectAttributesOutput = quick_xml::de::from_str(xml).unwrap();
+        assert!(output.etag.is_none());
+        assert!(output.checksum.is_none());
+        assert_eq!(output.object_size, Some(5242980));
+        let parts = output.object_parts.unwrap();
+        assert_eq!(parts.parts_count, Some(2));
+        assert_eq!(parts.part.len(), 2);
+        assert!(parts.part[0].checksum_crc32c.is_none());
+    }
 }
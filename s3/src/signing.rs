@@ -45,7 +45,7 @@ use crate::LONG_DATETIME;
 
 use std::fmt::Write as _;
 
-const SHORT_DATE: &[time::format_description::FormatItem<'static>] =
+pub(crate) const SHORT_DATE: &[time::format_description::FormatItem<'static>] =
     format_description!("[year][month][day]");
 
 pub type HmacSha256 = Hmac<Sha256>;
@@ -94,6 +94,31 @@ pub fn uri_encode(string: &str, encode_slash: bool) -> String {
     }
 }
 
+/// Path of the object `key` in request URLs, the one encoding pass keys go through so the
+/// URL sent and the canonical request signed for it agree.
+pub fn encode_key(key: &str) -> String {
+    uri_encode(key.strip_prefix('/').unwrap_or(key), false)
+}
+
+/// Whether `key` has a `.` or `..` segment, which `Url` removes, encoded or not
+pub fn has_dot_segment(key: &str) -> bool {
+    key.strip_prefix('/')
+        .unwrap_or(key)
+        .split('/')
+        .any(|segment| matches!(segment, "." | ".."))
+}
+
+/// Append `key=value` to the query of `url`, encoded as in canonical query strings rather
+/// than as a form, where a space becomes `+`.
+pub fn append_query(url: &mut Url, key: &str, value: &str) {
+    let pair = format!("{}={}", uri_encode(key, true), uri_encode(value, true));
+    let query = match url.query() {
+        Some(query) if !query.is_empty() => format!("{}&{}", query, pair),
+        _ => pair,
+    };
+    url.set_query(Some(&query));
+}
+
 /// Generate a canonical URI string from the given URL.
 pub fn canonical_uri_string(uri: &Url) -> String {
     // decode `Url`'s percent-encoding and then reencode it
@@ -104,20 +129,20 @@ pub fn canonical_uri_string(uri: &Url) -> String {
 
 /// Generate a canonical query string from the query pairs in the given URL.
 pub fn canonical_query_string(uri: &Url) -> String {
+    // Sorted by encoded key, then encoded value, which keeps repeated keys in a stable order
     let mut keyvalues: Vec<(String, String)> = uri
         .query_pairs()
-        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .map(|(key, value)| {
+            (
+                utf8_percent_encode(&key, FRAGMENT_SLASH).to_string(),
+                utf8_percent_encode(&value, FRAGMENT_SLASH).to_string(),
+            )
+        })
         .collect();
     keyvalues.sort();
     let keyvalues: Vec<String> = keyvalues
         .iter()
-        .map(|(k, v)| {
-            format!(
-                "{}={}",
-                utf8_percent_encode(k, FRAGMENT_SLASH),
-                utf8_percent_encode(v, FRAGMENT_SLASH)
-            )
-        })
+        .map(|(k, v)| format!("{}={}", k, v))
         .collect();
     keyvalues.join("&")
 }
@@ -164,12 +189,22 @@ pub fn canonical_request(
     ))
 }
 
-/// Generate an AWS scope string.
+/// Generate an AWS scope string for S3.
 pub fn scope_string(datetime: &OffsetDateTime, region: &Region) -> Result<String, S3Error> {
+    scope_string_for_service(datetime, region, "s3")
+}
+
+/// Generate an AWS scope string for the given service.
+pub fn scope_string_for_service(
+    datetime: &OffsetDateTime,
+    region: &Region,
+    service: &str,
+) -> Result<String, S3Error> {
     Ok(format!(
-        "{date}/{region}/s3/aws4_request",
+        "{date}/{region}/{service}/aws4_request",
         date = datetime.format(SHORT_DATE)?,
-        region = region
+        region = region,
+        service = service
     ))
 }
 
@@ -179,13 +214,23 @@ pub fn string_to_sign(
     datetime: &OffsetDateTime,
     region: &Region,
     canonical_req: &str,
+) -> Result<String, S3Error> {
+    string_to_sign_for_service(datetime, region, "s3", canonical_req)
+}
+
+/// Like [`string_to_sign`], scoped to the given service.
+pub fn string_to_sign_for_service(
+    datetime: &OffsetDateTime,
+    region: &Region,
+    service: &str,
+    canonical_req: &str,
 ) -> Result<String, S3Error> {
     let mut hasher = Sha256::default();
     hasher.update(canonical_req.as_bytes());
     let string_to = format!(
         "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{hash}",
         timestamp = datetime.format(LONG_DATETIME)?,
-        scope = scope_string(datetime, region)?,
+        scope = scope_string_for_service(datetime, region, service)?,
         hash = hex::encode(hasher.finalize().as_slice())
     );
     Ok(string_to)
@@ -218,12 +263,31 @@ pub fn authorization_header(
     region: &Region,
     signed_headers: &str,
     signature: &str,
+) -> Result<String, S3Error> {
+    authorization_header_for_service(
+        access_key,
+        datetime,
+        region,
+        "s3",
+        signed_headers,
+        signature,
+    )
+}
+
+/// Like [`authorization_header`], scoped to the given service.
+pub fn authorization_header_for_service(
+    access_key: &str,
+    datetime: &OffsetDateTime,
+    region: &Region,
+    service: &str,
+    signed_headers: &str,
+    signature: &str,
 ) -> Result<String, S3Error> {
     Ok(format!(
         "AWS4-HMAC-SHA256 Credential={access_key}/{scope},\
             SignedHeaders={signed_headers},Signature={signature}",
         access_key = access_key,
-        scope = scope_string(datetime, region)?,
+        scope = scope_string_for_service(datetime, region, service)?,
         signed_headers = signed_headers,
         signature = signature
     ))
@@ -276,10 +340,24 @@ pub fn authorization_query_params_no_sig(
     Ok(query_params)
 }
 
+/// Query parameters a presigned URL is signed with, custom queries must not reuse them.
+pub(crate) const PRESIGN_QUERY_PARAMS: [&str; 7] = [
+    "X-Amz-Algorithm",
+    "X-Amz-Credential",
+    "X-Amz-Date",
+    "X-Amz-Expires",
+    "X-Amz-SignedHeaders",
+    "X-Amz-Security-Token",
+    "X-Amz-Signature",
+];
+
 pub fn flatten_queries(queries: Option<&HashMap<String, String>>) -> Result<String, S3Error> {
     match queries {
         None => Ok(String::new()),
         Some(queries) => {
+            // Sorted so the same queries always give the same URL
+            let mut queries: Vec<_> = queries.iter().collect();
+            queries.sort();
             let mut query_str = String::new();
             for (k, v) in queries {
                 write!(
@@ -366,6 +444,11 @@ mod tests {
         let url = Url::parse("http://s3.amazonaws.com/examplebucket?key=c&key=a&key=b").unwrap();
         let canonical = canonical_query_string(&url);
         assert_eq!("key=a&key=b&key=c", canonical);
+
+        // `<` sorts after `0` but its encoding before
+        let url = Url::parse("http://s3.amazonaws.com/examplebucket?a0=x&a%3C=y&a0=").unwrap();
+        let canonical = canonical_query_string(&url);
+        assert_eq!("a%3C=y&a0=&a0=x", canonical);
     }
 
     #[test]
@@ -473,6 +556,74 @@ mod tests {
         assert!(deserialized.is_truncated);
     }
 
+    const TRICKY_KEYS: &[&str] = &[
+        "a+b.txt",
+        "with space/and+plus.txt",
+        "hash#and?question.txt",
+        "percent%2Fslash%20.txt",
+        "unicode/привет 你好 ü.txt",
+        "reserved!$&'()*,;=:@[].txt",
+        "tilde~and_underscore-dash.txt",
+    ];
+
+    #[test]
+    fn tricky_keys_are_sent_as_signed() {
+        for key in TRICKY_KEYS {
+            let mut url = Url::parse(&format!(
+                "https://s3.amazonaws.com/rust-s3/{}",
+                encode_key(key)
+            ))
+            .unwrap();
+            append_query(&mut url, "prefix", key);
+            append_query(&mut url, "list-type", "2");
+
+            assert_eq!(
+                canonical_uri_string(&url),
+                format!("/rust-s3/{}", uri_encode(key, false)),
+                "{}",
+                key
+            );
+            assert_eq!(url.path(), canonical_uri_string(&url), "{}", key);
+
+            let canonical = canonical_query_string(&url);
+            let mut sent = url.query().unwrap().split('&').collect::<Vec<_>>();
+            sent.sort();
+            assert_eq!(canonical, sent.join("&"), "{}", key);
+        }
+    }
+
+    #[test]
+    fn scope_defaults_to_s3() {
+        let datetime = Date::from_calendar_date(2015, 8.try_into().unwrap(), 30)
+            .unwrap()
+            .with_hms(12, 36, 0)
+            .unwrap()
+            .assume_utc();
+        let region = "us-east-1".parse().unwrap();
+        assert_eq!(
+            scope_string(&datetime, &region).unwrap(),
+            scope_string_for_service(&datetime, &region, "s3").unwrap()
+        );
+        assert_eq!(
+            "20150830/us-east-1/s3express/aws4_request",
+            scope_string_for_service(&datetime, &region, "s3express").unwrap()
+        );
+        assert_eq!(
+            authorization_header("AKID", &datetime, &region, "host", "sig").unwrap(),
+            "AWS4-HMAC-SHA256 Credential=AKID/20150830/us-east-1/s3/aws4_request,\
+                SignedHeaders=host,Signature=sig"
+        );
+    }
+
+    #[test]
+    fn dot_segments_are_detected() {
+        assert!(has_dot_segment("a/../b"));
+        assert!(has_dot_segment("/./a"));
+        assert!(has_dot_segment(".."));
+        assert!(!has_dot_segment("a/..b/c."));
+        assert!(!has_dot_segment("/.hidden"));
+    }
+
     #[test]
     fn test_uri_encode() {
         assert_eq!(uri_encode(r#"~!@#$%^&*()-_=+[]\{}|;:'",.<>? привет 你好"#, true), "~%21%40%23%24%25%5E%26%2A%28%29-_%3D%2B%5B%5D%5C%7B%7D%7C%3B%3A%27%22%2C.%3C%3E%3F%20%D0%BF%D1%80%D0%B8%D0%B2%D0%B5%D1%82%20%E4%BD%A0%E5%A5%BD");
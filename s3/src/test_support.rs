@@ -0,0 +1,190 @@
+//! Throwaway buckets for integration tests against LocalStack, MinIO or any other S3 endpoint,
+//! behind the `test-support` feature.
+//!
+//! [`TestBucket::ephemeral`] creates a uniquely named bucket on the endpoint the environment
+//! points to, the bucket is emptied of objects, versions and multipart uploads and deleted
+//! when the `TestBucket` is dropped. [`TestBucket::remove`] does the same and reports errors,
+//! which dropping can only log.
+//!
+//! - `S3_TEST_ENDPOINT`, `http://localhost:4566` (LocalStack) when unset
+//! - `S3_TEST_REGION`, `us-east-1` when unset
+//! - `S3_TEST_ACCESS_KEY_ID` and `S3_TEST_SECRET_ACCESS_KEY`, `test` when unset, which
+//!   LocalStack accepts
+//!
+//! ```rust,no_run
+//! use s3::test_support::TestBucket;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), s3::error::S3Error> {
+//! let bucket = TestBucket::ephemeral().await?;
+//! bucket.put_object("/hello.txt", b"hello").await?;
+//! assert_eq!(bucket.get_object("/hello.txt").await?.as_slice(), b"hello");
+//! // bucket and object are deleted here
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Deref;
+
+use crate::bucket::Bucket;
+use crate::bucket_ops::BucketConfiguration;
+use crate::creds::Credentials;
+use crate::error::S3Error;
+use crate::region::Region;
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:4566";
+
+/// A bucket that is deleted along with everything in it when dropped, derefs to `Bucket`
+#[derive(Debug)]
+pub struct TestBucket {
+    bucket: Box<Bucket>,
+    removed: bool,
+}
+
+impl TestBucket {
+    /// Create `rust-s3-test-<uuid>` on the endpoint given by the environment, path style
+    #[maybe_async::maybe_async]
+    pub async fn ephemeral() -> Result<TestBucket, S3Error> {
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or(default.to_string());
+        let region = Region::Custom {
+            region: var("S3_TEST_REGION", "us-east-1"),
+            endpoint: var("S3_TEST_ENDPOINT", DEFAULT_ENDPOINT),
+        };
+        let credentials = Credentials::new(
+            Some(&var("S3_TEST_ACCESS_KEY_ID", "test")),
+            Some(&var("S3_TEST_SECRET_ACCESS_KEY", "test")),
+            None,
+            None,
+            None,
+        )?;
+        let name = format!("rust-s3-test-{}", uuid::Uuid::new_v4().simple());
+        let response = Bucket::create_with_path_style(
+            &name,
+            region,
+            credentials,
+            BucketConfiguration::default(),
+        )
+        .await?;
+        if response.response_code >= 300 {
+            return Err(S3Error::from_response(
+                response.response_code,
+                response.response_text,
+            ));
+        }
+        Ok(TestBucket {
+            bucket: response.bucket,
+            removed: false,
+        })
+    }
+
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
+    /// Empty and delete the bucket now rather than when dropped
+    #[maybe_async::maybe_async]
+    pub async fn remove(mut self) -> Result<(), S3Error> {
+        self.removed = true;
+        empty_and_delete(&self.bucket).await
+    }
+}
+
+impl Deref for TestBucket {
+    type Target = Bucket;
+
+    fn deref(&self) -> &Bucket {
+        &self.bucket
+    }
+}
+
+impl Drop for TestBucket {
+    fn drop(&mut self) {
+        if self.removed {
+            return;
+        }
+        let bucket = (*self.bucket).clone();
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "sync")] {
+                let result = empty_and_delete(&bucket);
+            } else if #[cfg(feature = "with-tokio")] {
+                // Tests are often dropped on a single threaded runtime that blocking here would
+                // stall, the cleanup gets a runtime and a client of its own on another thread
+                let result = std::thread::spawn(move || -> Result<(), S3Error> {
+                    let mut bucket = bucket;
+                    bucket.shutdown()?;
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?;
+                    runtime.block_on(empty_and_delete(&bucket))
+                })
+                .join()
+                .unwrap_or(Err(S3Error::HttpFail));
+            } else if #[cfg(feature = "with-async-std")] {
+                let result = std::thread::spawn(move || {
+                    async_std::task::block_on(empty_and_delete(&bucket))
+                })
+                .join()
+                .unwrap_or(Err(S3Error::HttpFail));
+            }
+        }
+        if let Err(e) = result {
+            log::warn!("Could not delete test bucket {}: {}", self.bucket.name, e);
+        }
+    }
+}
+
+/// Abort the uploads, delete the objects and their versions, then the bucket
+#[maybe_async::maybe_async]
+async fn empty_and_delete(bucket: &Bucket) -> Result<(), S3Error> {
+    for page in bucket.list_multiparts_uploads(None, None).await? {
+        for upload in page.uploads {
+            bucket.abort_upload(&upload.key, &upload.id).await?;
+        }
+    }
+    for page in bucket.list(String::new(), None).await? {
+        for object in page.contents {
+            bucket.delete_object(&object.key).await?;
+        }
+    }
+    // Providers without versioning have nothing left to delete
+    if let Ok(pages) = bucket.list_object_versions(String::new(), None).await {
+        for page in pages {
+            for version in page.versions {
+                bucket
+                    .delete_object_version(&version.key, &version.version_id, None)
+                    .await?;
+            }
+            for marker in page.delete_markers {
+                bucket
+                    .delete_object_version(&marker.key, &marker.version_id, None)
+                    .await?;
+            }
+        }
+    }
+    bucket.delete().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestBucket;
+
+    #[ignore]
+    #[maybe_async::test(
+        feature = "sync",
+        async(all(not(feature = "sync"), feature = "with-tokio"), tokio::test),
+        async(
+            all(not(feature = "sync"), feature = "with-async-std"),
+            async_std::test
+        )
+    )]
+    async fn test_ephemeral_bucket_is_removed() {
+        let bucket = TestBucket::ephemeral().await.unwrap();
+        bucket.put_object("/a.txt", b"a").await.unwrap();
+        assert!(bucket.name.starts_with("rust-s3-test-"));
+        let copy = bucket.bucket().clone();
+        bucket.remove().await.unwrap();
+        let exists = copy.exists().await.unwrap_or(false);
+        assert!(!exists);
+    }
+}
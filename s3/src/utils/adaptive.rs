@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use crate::bucket::CHUNK_SIZE;
+use crate::utils::PartSize;
+
+/// Parts completing faster than this are grown, parts taking longer than four times it are shrunk
+const TARGET_PART_LATENCY: Duration = Duration::from_secs(2);
+
+/// Bounds for `Bucket::put_object_stream_adaptive`, the uploader starts conservatively and
+/// grows towards `max_concurrency` parts in flight and `max_part_size` bytes per part while
+/// throughput keeps improving. At most `max_memory` bytes are buffered at any time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptiveUploadConfig {
+    pub min_part_size: PartSize,
+    pub max_part_size: PartSize,
+    pub max_concurrency: usize,
+    pub max_memory: usize,
+}
+
+impl Default for AdaptiveUploadConfig {
+    fn default() -> Self {
+        AdaptiveUploadConfig {
+            min_part_size: PartSize::mib(8),
+            max_part_size: PartSize::mib(64),
+            max_concurrency: 8,
+            max_memory: 32 * CHUNK_SIZE,
+        }
+    }
+}
+
+impl AdaptiveUploadConfig {
+    // Make the bounds consistent, at least one part fits in memory
+    fn normalized(mut self) -> Self {
+        self.max_part_size = self.max_part_size.max(self.min_part_size);
+        self.max_concurrency = self.max_concurrency.max(1);
+        self.max_memory = self.max_memory.max(self.min_part_size.bytes());
+        self
+    }
+}
+
+/// Picks concurrency and part size from the latency of completed parts. Concurrency is raised
+/// one part at a time while the estimated aggregate throughput of a window of parts improves and
+/// halved when it drops, part size doubles for fast parts and halves for slow ones.
+#[derive(Debug)]
+pub(crate) struct AdaptiveTuner {
+    config: AdaptiveUploadConfig,
+    concurrency: usize,
+    part_size: usize,
+    window_parts: usize,
+    window_bytes: u64,
+    window_latency: Duration,
+    last_throughput: f64,
+}
+
+impl AdaptiveTuner {
+    pub(crate) fn new(config: AdaptiveUploadConfig) -> Self {
+        let config = config.normalized();
+        let mut tuner = AdaptiveTuner {
+            config,
+            concurrency: 1,
+            part_size: config.min_part_size.bytes(),
+            window_parts: 0,
+            window_bytes: 0,
+            window_latency: Duration::ZERO,
+            last_throughput: 0.0,
+        };
+        if tuner.fits(2, tuner.part_size) {
+            tuner.concurrency = 2.min(config.max_concurrency);
+        }
+        tuner
+    }
+
+    pub(crate) fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub(crate) fn part_size(&self) -> usize {
+        self.part_size
+    }
+
+    /// Record a part of `bytes` that took `latency` to upload
+    pub(crate) fn record_part(&mut self, bytes: usize, latency: Duration) {
+        self.window_parts += 1;
+        self.window_bytes += bytes as u64;
+        self.window_latency += latency;
+
+        // Only parts of the current size say something about it, the last part is usually short
+        if bytes >= self.part_size {
+            if latency < TARGET_PART_LATENCY {
+                let grown = (self.part_size * 2).min(self.config.max_part_size.bytes());
+                if self.fits(self.concurrency, grown) {
+                    self.part_size = grown;
+                }
+            } else if latency > TARGET_PART_LATENCY * 4 {
+                self.part_size = (self.part_size / 2).max(self.config.min_part_size.bytes());
+            }
+        }
+
+        if self.window_parts < self.concurrency {
+            return;
+        }
+
+        let latency = self.window_latency.as_secs_f64().max(f64::EPSILON);
+        let throughput = self.window_bytes as f64 / latency * self.concurrency as f64;
+        if throughput > self.last_throughput * 1.05 {
+            if self.concurrency < self.config.max_concurrency
+                && self.fits(self.concurrency + 1, self.part_size)
+            {
+                self.concurrency += 1;
+            }
+        } else if throughput < self.last_throughput * 0.8 {
+            self.concurrency = (self.concurrency / 2).max(1);
+        }
+        self.last_throughput = throughput;
+        self.window_parts = 0;
+        self.window_bytes = 0;
+        self.window_latency = Duration::ZERO;
+    }
+
+    // In flight parts plus the one being read ahead
+    fn fits(&self, concurrency: usize, part_size: usize) -> bool {
+        (concurrency + 1) * part_size <= self.config.max_memory
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AdaptiveTuner, AdaptiveUploadConfig};
+    use crate::utils::PartSize;
+    use std::time::Duration;
+
+    const MIB: usize = 1024 * 1024;
+
+    #[test]
+    fn tuner_grows_within_bounds() {
+        let config = AdaptiveUploadConfig {
+            min_part_size: PartSize::mib(8),
+            max_part_size: PartSize::mib(32),
+            max_concurrency: 4,
+            max_memory: 128 * MIB,
+        };
+        let mut tuner = AdaptiveTuner::new(config);
+        assert_eq!(tuner.concurrency(), 2);
+        assert_eq!(tuner.part_size(), 8 * MIB);
+
+        // Parts complete quickly and at the same speed, so more of them in flight helps
+        for _ in 0..32 {
+            let part_size = tuner.part_size();
+            tuner.record_part(part_size, Duration::from_millis(500));
+            assert!((tuner.concurrency() + 1) * tuner.part_size() <= config.max_memory);
+        }
+        assert_eq!(tuner.part_size(), 32 * MIB);
+        assert!(tuner.concurrency() <= config.max_concurrency);
+    }
+
+    #[test]
+    fn tuner_backs_off_when_throughput_drops() {
+        let config = AdaptiveUploadConfig {
+            min_part_size: PartSize::mib(8),
+            max_part_size: PartSize::mib(8),
+            max_concurrency: 8,
+            max_memory: 1024 * MIB,
+        };
+        let mut tuner = AdaptiveTuner::new(config);
+        for _ in 0..20 {
+            tuner.record_part(8 * MIB, Duration::from_millis(500));
+        }
+        let concurrency = tuner.concurrency();
+        assert!(concurrency > 2);
+
+        for _ in 0..concurrency {
+            tuner.record_part(8 * MIB, Duration::from_secs(10));
+        }
+        assert!(tuner.concurrency() < concurrency);
+    }
+
+    #[test]
+    fn tuner_normalizes_bounds() {
+        let tuner = AdaptiveTuner::new(AdaptiveUploadConfig {
+            min_part_size: PartSize::MIN,
+            max_part_size: PartSize::MIN,
+            max_concurrency: 0,
+            max_memory: 0,
+        });
+        assert_eq!(tuner.part_size(), 5 * MIB);
+        assert_eq!(tuner.concurrency(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::S3Error;
+
+/// A key a batch operation failed on, with the error it failed with
+#[derive(Debug)]
+pub struct BatchFailure {
+    pub key: String,
+    pub error: S3Error,
+}
+
+/// Outcome of a batch operation such as `Bucket::delete_objects`, which carries on past keys
+/// it fails on and reports them here rather than failing the whole call.
+///
+/// ```
+/// use s3::error::S3Error;
+/// use s3::utils::BatchResult;
+///
+/// let mut result = BatchResult::new();
+/// result.push_ok("a.txt", 204u16);
+/// result.push_err("b.txt", S3Error::HttpFailWithBody(403, String::new()));
+/// assert!(!result.is_complete());
+/// assert_eq!(result.failed()[0].key, "b.txt");
+/// ```
+#[derive(Debug)]
+pub struct BatchResult<T> {
+    succeeded: Vec<(String, T)>,
+    failed: Vec<BatchFailure>,
+}
+
+impl<T> Default for BatchResult<T> {
+    fn default() -> Self {
+        BatchResult {
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl<T> BatchResult<T> {
+    pub fn new() -> Self {
+        BatchResult::default()
+    }
+
+    pub fn push_ok(&mut self, key: impl Into<String>, value: T) {
+        self.succeeded.push((key.into(), value));
+    }
+
+    pub fn push_err(&mut self, key: impl Into<String>, error: S3Error) {
+        self.failed.push(BatchFailure {
+            key: key.into(),
+            error,
+        });
+    }
+
+    pub fn push(&mut self, key: impl Into<String>, result: Result<T, S3Error>) {
+        match result {
+            Ok(value) => self.push_ok(key, value),
+            Err(error) => self.push_err(key, error),
+        }
+    }
+
+    /// Keys the operation went through for, in the order they completed
+    pub fn succeeded(&self) -> &[(String, T)] {
+        &self.succeeded
+    }
+
+    pub fn failed(&self) -> &[BatchFailure] {
+        &self.failed
+    }
+
+    /// `true` when no key failed
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn into_parts(self) -> (Vec<(String, T)>, Vec<BatchFailure>) {
+        (self.succeeded, self.failed)
+    }
+
+    /// The successes, or the first failure if there is one
+    pub fn into_result(self) -> Result<Vec<(String, T)>, Box<BatchFailure>> {
+        match self.failed.into_iter().next() {
+            Some(failure) => Err(Box::new(failure)),
+            None => Ok(self.succeeded),
+        }
+    }
+}
+
+impl<T> FromIterator<(String, Result<T, S3Error>)> for BatchResult<T> {
+    fn from_iter<I: IntoIterator<Item = (String, Result<T, S3Error>)>>(iter: I) -> Self {
+        let mut batch = BatchResult::new();
+        for (key, result) in iter {
+            batch.push(key, result);
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BatchResult;
+    use crate::error::S3Error;
+
+    #[test]
+    fn failures_keep_their_key() {
+        let batch: BatchResult<u16> = vec![
+            ("a".to_string(), Ok(204)),
+            (
+                "b".to_string(),
+                Err(S3Error::HttpFailWithBody(403, "AccessDenied".to_string())),
+            ),
+            ("c".to_string(), Ok(204)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(!batch.is_complete());
+        assert_eq!(batch.succeeded().len(), 2);
+        assert_eq!(batch.failed()[0].key, "b");
+        assert!(matches!(
+            batch.failed()[0].error,
+            S3Error::HttpFailWithBody(403, _)
+        ));
+
+        let failure = batch.into_result().unwrap_err();
+        assert_eq!(failure.key, "b");
+    }
+}
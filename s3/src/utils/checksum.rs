@@ -0,0 +1,144 @@
+use base64::engine::general_purpose;
+use base64::Engine;
+
+/// Header carrying the CRC32C checksum, sent as a trailer of `aws-chunked` bodies
+pub const CRC32C_TRAILER: &str = "x-amz-checksum-crc32c";
+
+/// Payload hash announcing an unsigned `aws-chunked` body followed by a trailer
+pub const STREAMING_UNSIGNED_PAYLOAD_TRAILER: &str = "STREAMING-UNSIGNED-PAYLOAD-TRAILER";
+
+// Castagnoli polynomial, reversed
+const CRC32C_POLYNOMIAL: u32 = 0x82f6_3b78;
+
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32C_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental CRC32C, data can be fed as it is read, so streamed uploads are checksummed
+/// without a second pass.
+///
+/// # Example
+/// ```rust
+/// use s3::utils::Crc32c;
+///
+/// let mut crc = Crc32c::new();
+/// crc.update(b"1234");
+/// crc.update(b"56789");
+/// assert_eq!(crc.finalize(), 0xe3069283);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32c {
+    state: u32,
+}
+
+impl Default for Crc32c {
+    fn default() -> Self {
+        Crc32c { state: !0 }
+    }
+}
+
+impl Crc32c {
+    pub fn new() -> Self {
+        Crc32c::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.state =
+                CRC32C_TABLE[((self.state ^ *byte as u32) & 0xff) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn finalize(&self) -> u32 {
+        !self.state
+    }
+
+    /// Base64 of the big-endian checksum, the format S3 expects in `x-amz-checksum-crc32c`
+    pub fn to_base64(&self) -> String {
+        general_purpose::STANDARD.encode(self.finalize().to_be_bytes())
+    }
+
+    pub fn checksum(data: &[u8]) -> Crc32c {
+        let mut crc = Crc32c::new();
+        crc.update(data);
+        crc
+    }
+}
+
+fn aws_chunked_trailer(checksum: &str) -> String {
+    format!("{}:{}\r\n\r\n", CRC32C_TRAILER, checksum)
+}
+
+/// Encode `content` as a single `aws-chunked` chunk, followed by the terminating empty chunk
+/// and the trailer with `checksum`, the CRC32C of `content` computed while it was read.
+pub(crate) fn aws_chunked_body(content: &[u8], checksum: &Crc32c) -> Vec<u8> {
+    let trailer = aws_chunked_trailer(&checksum.to_base64());
+    let mut body = Vec::with_capacity(aws_chunked_len(content.len()));
+    if !content.is_empty() {
+        body.extend_from_slice(format!("{:x}\r\n", content.len()).as_bytes());
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"0\r\n");
+    body.extend_from_slice(trailer.as_bytes());
+    body
+}
+
+/// Length of `aws_chunked_body` for a payload of `content_length` bytes
+pub(crate) fn aws_chunked_len(content_length: usize) -> usize {
+    // A base64 encoded CRC32C is always 8 characters long
+    let trailer_len = aws_chunked_trailer("AAAAAA==").len();
+    let chunk_len = if content_length == 0 {
+        0
+    } else {
+        format!("{:x}\r\n", content_length).len() + content_length + 2
+    };
+    chunk_len + "0\r\n".len() + trailer_len
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aws_chunked_body, aws_chunked_len, Crc32c};
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(Crc32c::checksum(b"123456789").finalize(), 0xe3069283);
+        assert_eq!(Crc32c::checksum(b"").to_base64(), "AAAAAA==");
+        assert_eq!(Crc32c::checksum(&[0u8; 32]).finalize(), 0x8a9136aa);
+    }
+
+    #[test]
+    fn test_aws_chunked_body() {
+        let body = aws_chunked_body(b"123456789", &Crc32c::checksum(b"123456789"));
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            "9\r\n123456789\r\n0\r\nx-amz-checksum-crc32c:4waSgw==\r\n\r\n"
+        );
+        assert_eq!(body.len(), aws_chunked_len(9));
+
+        let body = aws_chunked_body(b"", &Crc32c::new());
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            "0\r\nx-amz-checksum-crc32c:AAAAAA==\r\n\r\n"
+        );
+        assert_eq!(body.len(), aws_chunked_len(0));
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Progress of a `put_object_stream` upload, reported to the bucket's `UploadEvents`.
+///
+/// `key` is the object being uploaded, so one listener can follow several uploads at a time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UploadEvent {
+    /// A part started uploading
+    PartStarted {
+        key: String,
+        number: u32,
+        bytes: usize,
+    },
+    /// A part was uploaded
+    PartCompleted {
+        key: String,
+        number: u32,
+        bytes: usize,
+        etag: String,
+    },
+    /// A part upload failed and is about to be retried
+    Retrying {
+        key: String,
+        number: u32,
+        attempt: u64,
+        error: String,
+    },
+    /// The object was written, `parts` is 0 for objects sent with a single `PUT`
+    Completed {
+        key: String,
+        bytes: usize,
+        parts: u32,
+    },
+    /// The multipart upload was aborted after a part failed
+    Aborted { key: String, error: String },
+}
+
+/// Listener for `UploadEvent`s, set on a bucket with `Bucket::with_upload_events`.
+///
+/// Events are handed over from the upload itself, so the listener should return quickly,
+/// forwarding to a channel is the way to do slow work with them.
+///
+/// ```
+/// use s3::utils::{UploadEvent, UploadEvents};
+///
+/// let events = UploadEvents::new(|event| {
+///     if let UploadEvent::PartCompleted { key, number, .. } = event {
+///         println!("{}: part {} done", key, number);
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct UploadEvents(Arc<dyn Fn(UploadEvent) + Send + Sync>);
+
+impl UploadEvents {
+    pub fn new(listener: impl Fn(UploadEvent) + Send + Sync + 'static) -> Self {
+        UploadEvents(Arc::new(listener))
+    }
+
+    /// Send events to `sender`, events sent after the receiver is dropped are discarded
+    #[cfg(feature = "with-tokio")]
+    pub fn channel(sender: tokio::sync::mpsc::UnboundedSender<UploadEvent>) -> Self {
+        UploadEvents::new(move |event| {
+            let _ = sender.send(event);
+        })
+    }
+
+    pub(crate) fn emit(&self, event: UploadEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for UploadEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadEvents").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "with-tokio"))]
+mod test {
+    use super::{UploadEvent, UploadEvents};
+
+    #[test]
+    fn events_are_forwarded_to_channel() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let events = UploadEvents::channel(sender);
+        let event = UploadEvent::PartStarted {
+            key: "key".to_string(),
+            number: 1,
+            bytes: 8,
+        };
+        events.emit(event.clone());
+        assert_eq!(receiver.try_recv().unwrap(), event);
+
+        drop(receiver);
+        // A listener that went away doesn't fail the upload
+        events.emit(event);
+    }
+}
@@ -1,16 +1,34 @@
+mod adaptive;
+mod batch;
+mod checksum;
+mod events;
+mod pool;
 mod time_utils;
+mod trace;
+mod units;
 
+pub(crate) use adaptive::AdaptiveTuner;
+pub use adaptive::AdaptiveUploadConfig;
+pub use batch::{BatchFailure, BatchResult};
+pub use checksum::*;
+pub use events::{UploadEvent, UploadEvents};
+pub use pool::BufferPool;
 pub use time_utils::*;
+pub use trace::{RequestRecord, RequestTrace};
+pub use units::{Expiry, PageSize, PartSize, Timeout};
 
 use std::str::FromStr;
 
 use crate::error::S3Error;
 use crate::request::ResponseData;
+use crate::serde_types::{DeletedObject, Metadata, Part, METADATA_HEADER_PREFIX};
 use crate::{bucket::CHUNK_SIZE, serde_types::HeadObjectResult};
 
+#[cfg(feature = "md5")]
 use std::fs::File;
 
 use std::io::Read;
+#[cfg(feature = "md5")]
 use std::path::Path;
 
 #[cfg(feature = "with-tokio")]
@@ -22,6 +40,29 @@ use async_std::io::Read as AsyncRead;
 #[cfg(feature = "with-async-std")]
 use async_std::io::ReadExt as AsyncReadExt;
 
+/// Options for `Bucket::put_object_stream_from_stream` and `Bucket::put_object_stream_with_options`
+#[derive(Clone, Debug)]
+pub struct PutStreamOptions {
+    pub content_type: String,
+    /// Streams shorter than this many bytes are sent with a single `PUT`, longer ones with a
+    /// multipart upload. At most 5 GiB, the largest object a single `PUT` can create.
+    pub multipart_threshold: usize,
+    pub part_size: PartSize,
+    /// Parts uploaded at the same time, each one holds `part_size` bytes in memory
+    pub max_concurrency: usize,
+}
+
+impl Default for PutStreamOptions {
+    fn default() -> Self {
+        PutStreamOptions {
+            content_type: "application/octet-stream".to_string(),
+            multipart_threshold: CHUNK_SIZE,
+            part_size: PartSize::mib(8),
+            max_concurrency: 4,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PutStreamResponse {
     status_code: u16,
@@ -44,6 +85,109 @@ impl PutStreamResponse {
     }
 }
 
+/// Outcome of a `DeleteObject`, on versioned buckets a plain delete creates a delete marker
+/// whose version is reported here, deleting a version reports whether it was a marker itself.
+#[derive(Clone, Debug)]
+pub struct DeleteObjectResult {
+    status_code: u16,
+    delete_marker: bool,
+    version_id: Option<String>,
+}
+
+impl DeleteObjectResult {
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    /// `x-amz-delete-marker`, `false` on unversioned buckets
+    pub fn delete_marker(&self) -> bool {
+        self.delete_marker
+    }
+
+    /// `x-amz-version-id`, the version of the created delete marker or of the deleted version
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+}
+
+impl DeleteObjectResult {
+    /// Outcome of a key deleted by a `DeleteObjects` request answered with `status_code`
+    pub(crate) fn from_deleted(status_code: u16, deleted: &DeletedObject) -> Self {
+        DeleteObjectResult {
+            status_code,
+            delete_marker: deleted.delete_marker.unwrap_or_default(),
+            version_id: deleted
+                .delete_marker_version_id
+                .clone()
+                .or_else(|| deleted.version_id.clone()),
+        }
+    }
+}
+
+impl From<&ResponseData> for DeleteObjectResult {
+    fn from(response_data: &ResponseData) -> Self {
+        DeleteObjectResult {
+            status_code: response_data.status_code(),
+            delete_marker: response_data
+                .header("x-amz-delete-marker")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            version_id: response_data.header("x-amz-version-id").map(String::from),
+        }
+    }
+}
+
+/// Progress of a `Bucket::resume_transfer`, kept by the caller so that an interrupted transfer
+/// continues from the last uploaded part instead of starting over.
+#[derive(Clone, Debug, Default)]
+pub struct TransferCheckpoint {
+    pub(crate) upload_id: Option<String>,
+    pub(crate) parts: Vec<Part>,
+    pub(crate) transferred_bytes: u64,
+    pub(crate) e_tag: Option<String>,
+}
+
+impl TransferCheckpoint {
+    pub fn new() -> Self {
+        TransferCheckpoint::default()
+    }
+
+    /// Id of the multipart upload on the destination, once one was initiated
+    pub fn upload_id(&self) -> Option<&str> {
+        self.upload_id.as_deref()
+    }
+
+    /// Parts already uploaded to the destination
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    /// Bytes of the source object already uploaded to the destination
+    pub fn transferred_bytes(&self) -> u64 {
+        self.transferred_bytes
+    }
+
+    /// ETag of the source object, a resumed transfer fails with `S3Error::ObjectChanged` once
+    /// the source was replaced
+    pub fn e_tag(&self) -> Option<&str> {
+        self.e_tag.as_deref()
+    }
+}
+
+/// Progress of a `Bucket::get_object_to_writer_resumable`, persisted by a supervisor so that a
+/// download picks up where it stopped. The ETag pins the resumed download to the object the
+/// first bytes were read from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DownloadCheckpoint {
+    /// Bytes of the object written so far
+    pub offset: u64,
+    /// ETag of the object being downloaded, `None` until the first response
+    pub e_tag: Option<String>,
+}
+
+/// ETag S3 gives the file at `path` when uploaded with `put_object_stream`, in `CHUNK_SIZE`
+/// parts from `CHUNK_SIZE` on. Requires the `md5` feature.
+///
 /// # Example
 /// ```rust,no_run
 /// use s3::utils::etag_for_path;
@@ -52,27 +196,76 @@ impl PutStreamResponse {
 /// let etag = etag_for_path(path).unwrap();
 /// println!("{}", etag);
 /// ```
+#[cfg(feature = "md5")]
 pub fn etag_for_path(path: impl AsRef<Path>) -> Result<String, S3Error> {
     let mut file = File::open(path)?;
-    let mut last_digest: [u8; 16];
+    etag_for_reader(&mut file, CHUNK_SIZE, PartSize::new(CHUNK_SIZE)?)
+}
+
+/// ETag S3 gives what `reader` holds when uploaded with `PutStreamOptions` of the same
+/// `multipart_threshold` and `part_size`: a plain MD5 below the threshold, the multipart form
+/// with the part count appended from the threshold on, even for a single part. Requires the
+/// `md5` feature.
+///
+/// # Example
+/// ```rust
+/// use s3::utils::{etag_for_reader, PartSize};
+///
+/// let mut reader = std::io::Cursor::new(vec![33; 1000]);
+/// let etag = etag_for_reader(&mut reader, 16 * 1024 * 1024, PartSize::mib(16)).unwrap();
+/// assert_eq!(etag, "8122ef1c2b2331f7986349560248cf56");
+/// ```
+#[cfg(feature = "md5")]
+pub fn etag_for_reader<R: Read + ?Sized>(
+    reader: &mut R,
+    multipart_threshold: usize,
+    part_size: PartSize,
+) -> Result<String, S3Error> {
+    let threshold = multipart_threshold.max(1);
+    let mut whole = md5::Context::new();
     let mut digests = Vec::new();
-    let mut chunks = 0;
+    let mut total = 0;
     loop {
-        let chunk = read_chunk(&mut file)?;
-        last_digest = md5::compute(&chunk).into();
-        digests.extend_from_slice(&last_digest);
-        chunks += 1;
-        if chunk.len() < CHUNK_SIZE {
+        let chunk = read_sized(reader, part_size.bytes())?;
+        if chunk.is_empty() {
+            break;
+        }
+        total += chunk.len();
+        // Only needed while the content may still stay below the threshold
+        if total < threshold {
+            whole.consume(&chunk);
+        }
+        digests.push(md5::compute(&chunk));
+        if chunk.len() < part_size.bytes() {
             break;
         }
     }
-    let etag = if chunks <= 1 {
-        format!("{:x}", md5::Digest(last_digest))
+    if total < threshold {
+        Ok(format!("{:x}", whole.compute()))
     } else {
-        let digest = format!("{:x}", md5::compute(digests));
-        format!("{}-{}", digest, chunks)
-    };
-    Ok(etag)
+        Ok(multipart_etag(&digests))
+    }
+}
+
+/// ETag S3 gives `content` when uploaded with `PutStreamOptions` of the same
+/// `multipart_threshold` and `part_size`, see `etag_for_reader`. Requires the `md5` feature.
+#[cfg(feature = "md5")]
+pub fn etag_for_bytes(content: &[u8], multipart_threshold: usize, part_size: PartSize) -> String {
+    if content.len() < multipart_threshold.max(1) {
+        return format!("{:x}", md5::compute(content));
+    }
+    let digests: Vec<_> = content
+        .chunks(part_size.bytes())
+        .map(md5::compute)
+        .collect();
+    multipart_etag(&digests)
+}
+
+/// MD5 of the part digests with the part count appended
+#[cfg(feature = "md5")]
+fn multipart_etag(digests: &[md5::Digest]) -> String {
+    let concatenated: Vec<u8> = digests.iter().flat_map(|digest| digest.0).collect();
+    format!("{:x}-{}", md5::compute(concatenated), digests.len())
 }
 
 pub fn read_chunk<R: Read + ?Sized>(reader: &mut R) -> Result<Vec<u8>, S3Error> {
@@ -94,6 +287,211 @@ pub async fn read_chunk_async<R: AsyncRead + Unpin + ?Sized>(
     Ok(chunk)
 }
 
+/// `read_chunk`, with the CRC32C of the chunk computed as each read lands rather than in a
+/// second pass over it
+pub fn read_chunk_with_checksum<R: Read + ?Sized>(
+    reader: &mut R,
+) -> Result<(Vec<u8>, Crc32c), S3Error> {
+    let mut chunk = Vec::new();
+    let crc = read_into_with_checksum(reader, &mut chunk, CHUNK_SIZE)?;
+
+    Ok((chunk, crc))
+}
+
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub async fn read_chunk_with_checksum_async<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+) -> Result<(Vec<u8>, Crc32c), S3Error> {
+    let mut chunk = Vec::new();
+    let crc = read_into_with_checksum_async(reader, &mut chunk, CHUNK_SIZE).await?;
+
+    Ok((chunk, crc))
+}
+
+/// `read_into`, returning the CRC32C of what was read, computed as each read lands
+pub(crate) fn read_into_with_checksum<R: Read + ?Sized>(
+    reader: &mut R,
+    chunk: &mut Vec<u8>,
+    size: usize,
+) -> Result<Crc32c, S3Error> {
+    chunk.resize(size, 0);
+    let mut filled = 0;
+    let mut crc = Crc32c::new();
+    while filled < size {
+        let read = match reader.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        };
+        crc.update(&chunk[filled..filled + read]);
+        filled += read;
+    }
+    chunk.truncate(filled);
+
+    Ok(crc)
+}
+
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub(crate) async fn read_into_with_checksum_async<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    chunk: &mut Vec<u8>,
+    size: usize,
+) -> Result<Crc32c, S3Error> {
+    chunk.resize(size, 0);
+    let mut filled = 0;
+    let mut crc = Crc32c::new();
+    while filled < size {
+        let read = match reader.read(&mut chunk[filled..]).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        };
+        crc.update(&chunk[filled..filled + read]);
+        filled += read;
+    }
+    chunk.truncate(filled);
+
+    Ok(crc)
+}
+
+/// Read at most `size` bytes from `reader`, allocating only what the size hint asks for.
+pub fn read_sized<R: Read + ?Sized>(reader: &mut R, size: usize) -> Result<Vec<u8>, S3Error> {
+    let mut chunk = Vec::with_capacity(size);
+    read_into(reader, &mut chunk, size)?;
+
+    Ok(chunk)
+}
+
+/// Check the `read` bytes of a stream announced as `expected` bytes long, reading past the
+/// announced size fails as soon as it happens, reading less once the stream is `done`.
+pub fn check_stream_size(expected: Option<u64>, read: usize, done: bool) -> Result<(), S3Error> {
+    match expected {
+        Some(expected) if read as u64 > expected || (done && read as u64 != expected) => {
+            Err(S3Error::StreamSizeMismatch {
+                expected,
+                read: read as u64,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read up to `size` bytes into `chunk`, which is expected to be empty
+pub fn read_into<R: Read + ?Sized>(
+    reader: &mut R,
+    chunk: &mut Vec<u8>,
+    size: usize,
+) -> Result<(), S3Error> {
+    let mut take = reader.take(size as u64);
+    take.read_to_end(chunk)?;
+
+    Ok(())
+}
+
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub async fn read_sized_async<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    size: usize,
+) -> Result<Vec<u8>, S3Error> {
+    let mut chunk = Vec::with_capacity(size);
+    read_into_async(reader, &mut chunk, size).await?;
+
+    Ok(chunk)
+}
+
+/// Read up to `size` bytes into `chunk`, which is expected to be empty
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub async fn read_into_async<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    chunk: &mut Vec<u8>,
+    size: usize,
+) -> Result<(), S3Error> {
+    let mut take = reader.take(size as u64);
+    take.read_to_end(chunk).await?;
+
+    Ok(())
+}
+
+/// `head` followed by what is left of `reader`
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub(crate) fn chain_async<'a, R: AsyncRead + Unpin + ?Sized>(
+    head: &'a [u8],
+    reader: &'a mut R,
+) -> impl AsyncRead + Unpin + 'a {
+    AsyncReadExt::chain(head, reader)
+}
+
+/// Next `part_size` bytes of `stream`, fewer only for the last part and `None` once the stream
+/// is exhausted. Bytes past the part are kept in `leftover` for the next call, a part that lies
+/// within a single chunk of the stream is sliced out of it without copying.
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub(crate) async fn next_stream_part<St, E>(
+    stream: &mut St,
+    leftover: &mut bytes::Bytes,
+    part_size: usize,
+) -> Result<Option<bytes::Bytes>, S3Error>
+where
+    St: futures::Stream<Item = Result<bytes::Bytes, E>> + Unpin + ?Sized,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    use bytes::{Bytes, BytesMut};
+    use futures::StreamExt;
+
+    let mut part = BytesMut::new();
+    loop {
+        let needed = part_size - part.len();
+        if leftover.len() >= needed {
+            if part.is_empty() {
+                return Ok(Some(leftover.split_to(needed)));
+            }
+            part.extend_from_slice(&leftover.split_to(needed));
+            return Ok(Some(part.freeze()));
+        }
+        if !leftover.is_empty() {
+            part.reserve(needed);
+            part.extend_from_slice(leftover);
+            *leftover = Bytes::new();
+        }
+        match stream.next().await {
+            Some(chunk) => {
+                *leftover = chunk.map_err(std::io::Error::other)?;
+            }
+            None if part.is_empty() => return Ok(None),
+            None => return Ok(Some(part.freeze())),
+        }
+    }
+}
+
+/// Await `read`, the next part of an upload, while the parts in `in_flight` keep uploading.
+/// Futures in a `FuturesUnordered` only make progress while the set is polled, awaiting the
+/// read alone would stall them. Parts that complete in the meantime go to `completed`.
+#[cfg(any(feature = "with-tokio", feature = "with-async-std"))]
+pub(crate) async fn read_while_uploading<T, P>(
+    read: impl std::future::Future<Output = Result<T, S3Error>>,
+    in_flight: &mut futures::stream::FuturesUnordered<P>,
+    mut completed: impl FnMut(P::Output) -> Result<(), S3Error>,
+) -> Result<T, S3Error>
+where
+    P: std::future::Future,
+{
+    use futures::future::{select, Either};
+    use futures::StreamExt;
+
+    futures::pin_mut!(read);
+    loop {
+        if in_flight.is_empty() {
+            return read.await;
+        }
+        match select(read.as_mut(), in_flight.next()).await {
+            Either::Left((chunk, _)) => return chunk,
+            Either::Right((Some(part), _)) => completed(part)?,
+            Either::Right((None, _)) => return read.await,
+        }
+    }
+}
+
 pub trait GetAndConvertHeaders {
     fn get_and_convert<T: FromStr>(&self, header: &str) -> Option<T>;
     fn get_string(&self, header: &str) -> Option<String>;
@@ -126,14 +524,11 @@ impl From<&http::HeaderMap> for HeadObjectResult {
             last_modified: headers.get_string("Last-Modified"),
             ..Default::default()
         };
-        let mut values = ::std::collections::HashMap::new();
+        let mut values = Metadata::new();
         for (key, value) in headers.iter() {
-            if key.as_str().starts_with("x-amz-meta-") {
+            if let Some(key) = key.as_str().strip_prefix(METADATA_HEADER_PREFIX) {
                 if let Ok(value) = value.to_str() {
-                    values.insert(
-                        key.as_str()["x-amz-meta-".len()..].to_owned(),
-                        value.to_owned(),
-                    );
+                    values.insert_received(key, value);
                 }
             }
         }
@@ -152,8 +547,8 @@ impl From<&http::HeaderMap> for HeadObjectResult {
         result.sse_customer_key_md5 =
             headers.get_string("x-amz-server-side-encryption-customer-key-MD5");
         result.ssekms_key_id = headers.get_string("x-amz-server-side-encryption-aws-kms-key-id");
-        result.server_side_encryption = headers.get_string("x-amz-server-side-encryption");
-        result.storage_class = headers.get_string("x-amz-storage-class");
+        result.server_side_encryption = headers.get_and_convert("x-amz-server-side-encryption");
+        result.storage_class = headers.get_and_convert("x-amz-storage-class");
         result.version_id = headers.get_string("x-amz-version-id");
         result.website_redirect_location = headers.get_string("x-amz-website-redirect-location");
         result
@@ -202,14 +597,11 @@ impl From<&reqwest::header::HeaderMap> for HeadObjectResult {
                 .map(|v| v.to_str().unwrap_or_default().to_string()),
             ..Default::default()
         };
-        let mut values = ::std::collections::HashMap::new();
+        let mut values = Metadata::new();
         for (key, value) in headers.iter() {
-            if key.as_str().starts_with("x-amz-meta-") {
+            if let Some(key) = key.as_str().strip_prefix(METADATA_HEADER_PREFIX) {
                 if let Ok(value) = value.to_str() {
-                    values.insert(
-                        key.as_str()["x-amz-meta-".len()..].to_owned(),
-                        value.to_owned(),
-                    );
+                    values.insert_received(key, value);
                 }
             }
         }
@@ -249,10 +641,10 @@ impl From<&reqwest::header::HeaderMap> for HeadObjectResult {
             .map(|v| v.to_str().unwrap_or_default().to_string());
         result.server_side_encryption = headers
             .get("x-amz-server-side-encryption")
-            .map(|v| v.to_str().unwrap_or_default().to_string());
+            .map(|v| v.to_str().unwrap_or_default().into());
         result.storage_class = headers
             .get("x-amz-storage-class")
-            .map(|v| v.to_str().unwrap_or_default().to_string());
+            .map(|v| v.to_str().unwrap_or_default().into());
         result.version_id = headers
             .get("x-amz-version-id")
             .map(|v| v.to_str().unwrap_or_default().to_string());
@@ -286,7 +678,7 @@ impl From<&attohttpc::header::HeaderMap> for HeadObjectResult {
                 .get("Content-Length")
                 .map(|v| v.to_str().unwrap_or_default().parse().unwrap_or_default()),
             content_type: headers
-                .get("get-Type")
+                .get("Content-Type")
                 .map(|v| v.to_str().unwrap_or_default().to_string()),
             delete_marker: headers
                 .get("x-amz-delete-marker")
@@ -305,14 +697,11 @@ impl From<&attohttpc::header::HeaderMap> for HeadObjectResult {
                 .map(|v| v.to_str().unwrap_or_default().to_string()),
             ..Default::default()
         };
-        let mut values = ::std::collections::HashMap::new();
+        let mut values = Metadata::new();
         for (key, value) in headers.iter() {
-            if key.as_str().starts_with("x-amz-meta-") {
+            if let Some(key) = key.as_str().strip_prefix(METADATA_HEADER_PREFIX) {
                 if let Ok(value) = value.to_str() {
-                    values.insert(
-                        key.as_str()["x-amz-meta-".len()..].to_owned(),
-                        value.to_owned(),
-                    );
+                    values.insert_received(key, value);
                 }
             }
         }
@@ -352,10 +741,10 @@ impl From<&attohttpc::header::HeaderMap> for HeadObjectResult {
             .map(|v| v.to_str().unwrap_or_default().to_string());
         result.server_side_encryption = headers
             .get("x-amz-server-side-encryption")
-            .map(|v| v.to_str().unwrap_or_default().to_string());
+            .map(|v| v.to_str().unwrap_or_default().into());
         result.storage_class = headers
             .get("x-amz-storage-class")
-            .map(|v| v.to_str().unwrap_or_default().to_string());
+            .map(|v| v.to_str().unwrap_or_default().into());
         result.version_id = headers
             .get("x-amz-version-id")
             .map(|v| v.to_str().unwrap_or_default().to_string());
@@ -366,23 +755,93 @@ impl From<&attohttpc::header::HeaderMap> for HeadObjectResult {
     }
 }
 
+/// Whether `body` is an `<Error>` document, past the whitespace S3 sends to keep long running
+/// requests alive and the XML declaration
+pub(crate) fn is_error_document(body: &[u8]) -> bool {
+    let Ok(body) = std::str::from_utf8(body) else {
+        return false;
+    };
+    let mut body = body.trim_start();
+    if let Some(declaration) = body.strip_prefix("<?xml") {
+        body = match declaration.split_once("?>") {
+            Some((_, rest)) => rest.trim_start(),
+            None => return false,
+        };
+    }
+    body.starts_with("<Error>") || body.starts_with("<Error ")
+}
+
 pub(crate) fn error_from_response_data(response_data: ResponseData) -> Result<S3Error, S3Error> {
     let utf8_content = String::from_utf8(response_data.as_slice().to_vec())?;
-    Err(S3Error::HttpFailWithBody(
+    Err(S3Error::from_response(
         response_data.status_code(),
         utf8_content,
     ))
 }
 
+/// Throttling and server errors worth sending a request again for
+pub(crate) fn is_transient_status(status_code: u16) -> bool {
+    matches!(status_code, 429 | 500 | 502 | 503 | 504)
+}
+
+/// Wait before retry `attempt`, with the backoff of `retry!`, for at most `time_left`
+#[maybe_async::maybe_async]
+pub(crate) async fn backoff(attempt: u64, time_left: Option<std::time::Duration>) {
+    let mut delay = std::time::Duration::from_secs(attempt.pow(2));
+    if let Some(time_left) = time_left {
+        delay = delay.min(time_left);
+    }
+    sleep(delay).await;
+}
+
+#[maybe_async::async_impl]
+pub(crate) async fn sleep(delay: std::time::Duration) {
+    #[cfg(feature = "with-tokio")]
+    tokio::time::sleep(delay).await;
+    #[cfg(feature = "with-async-std")]
+    async_std::task::sleep(delay).await;
+}
+
+#[maybe_async::sync_impl]
+pub(crate) fn sleep(delay: std::time::Duration) {
+    std::thread::sleep(delay);
+}
+
+/// Run blocking `f`, such as file or instance metadata reads, off the async executor threads
+#[maybe_async::async_impl]
+pub(crate) async fn blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    #[cfg(feature = "with-tokio")]
+    return match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    };
+    #[cfg(all(feature = "with-async-std", not(feature = "with-tokio")))]
+    return async_std::task::spawn_blocking(f).await;
+}
+
+#[maybe_async::sync_impl]
+pub(crate) fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    f()
+}
+
 /// Retries a given expression a specified number of times with exponential backoff.
 ///
 /// This macro attempts to execute the provided expression up to `N` times, where `N`
-/// is the value set by `set_retries`. If the expression returns `Ok`, it returns the value.
+/// is the value set by `set_retries` unless given as the third argument. If the expression
+/// returns `Ok`, it returns the value.
 /// If the expression returns `Err`, it logs a warning and retries after a delay that increases
 /// exponentially with each retry.
 ///
 /// The delay between retries is calculated as `1 * retry_cnt.pow(2)` seconds, where `retry_cnt`
-/// is the current retry attempt.
+/// is the current retry attempt. An optional fourth argument gives the time left until a
+/// deadline, `Option<Duration>` evaluated before every delay, which caps the delay.
 ///
 /// This macro supports both asynchronous and synchronous contexts:
 /// - For `tokio` users, it uses `tokio::time::sleep`.
@@ -400,20 +859,36 @@ pub(crate) fn error_from_response_data(response_data: ResponseData) -> Result<S3
 /// If all retry attempts fail, the last error is returned.
 #[macro_export]
 macro_rules! retry {
-    ($e:expr) => {{
+    ($e:expr) => {
+        $crate::retry!($e, |_, _| {})
+    };
+    ($e:expr, $on_retry:expr) => {
+        $crate::retry!($e, $on_retry, $crate::get_retries())
+    };
+    ($e:expr, $on_retry:expr, $max_retries:expr) => {
+        $crate::retry!($e, $on_retry, $max_retries, None::<std::time::Duration>)
+    };
+    ($e:expr, $on_retry:expr, $max_retries:expr, $time_left:expr) => {{
         let mut retry_cnt: u64 = 0;
-        let max_retries = $crate::get_retries() as u64;
+        let max_retries = $max_retries as u64;
 
         loop {
             match $e {
                 Ok(v) => break Ok(v),
                 Err(e) => {
                     log::warn!("Retrying {e}");
-                    if retry_cnt >= max_retries {
+                    if retry_cnt >= max_retries
+                        || matches!(e, $crate::error::S3Error::DeadlineExceeded)
+                    {
                         break Err(e);
                     }
                     retry_cnt += 1;
-                    let delay = std::time::Duration::from_secs(1 * retry_cnt.pow(2));
+                    ($on_retry)(retry_cnt, &e);
+                    let mut delay = std::time::Duration::from_secs(1 * retry_cnt.pow(2));
+                    // Past the deadline the next attempt fails without being sent
+                    if let Some(time_left) = $time_left {
+                        delay = delay.min(time_left);
+                    }
                     #[cfg(feature = "with-tokio")]
                     tokio::time::sleep(delay).await;
                     #[cfg(feature = "with-async-std")]
@@ -429,16 +904,145 @@ macro_rules! retry {
 
 #[cfg(test)]
 mod test {
+    #[cfg(feature = "md5")]
     use crate::utils::etag_for_path;
+    #[cfg(feature = "md5")]
     use std::fs::File;
+    #[cfg(feature = "md5")]
     use std::io::prelude::*;
     use std::io::Cursor;
 
+    #[cfg(feature = "md5")]
     fn object(size: u32) -> Vec<u8> {
         (0..size).map(|_| 33).collect()
     }
 
     #[test]
+    fn error_documents_are_told_from_results() {
+        use crate::utils::is_error_document;
+
+        let error = "\n  \n<?xml version=\"1.0\"?>\n<Error><Code>InternalError</Code></Error>";
+        assert!(is_error_document(error.as_bytes()));
+        assert!(is_error_document(b"<Error><Code>SlowDown</Code></Error>"));
+        assert!(!is_error_document(
+            b"<?xml version=\"1.0\"?><CopyObjectResult><ETag>\"abc\"</ETag></CopyObjectResult>"
+        ));
+        assert!(!is_error_document(b"<Errors/>"));
+        assert!(!is_error_document(b""));
+    }
+
+    #[test]
+    fn delete_object_result_from_headers() {
+        use crate::request::ResponseData;
+        use crate::utils::DeleteObjectResult;
+        use http::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-delete-marker", HeaderValue::from_static("true"));
+        headers.insert(
+            "x-amz-version-id",
+            HeaderValue::from_static("3HL4kqtJlcpXroDTDmJ+rmSpXd3dIbrHY"),
+        );
+        let result =
+            DeleteObjectResult::from(&ResponseData::new(bytes::Bytes::new(), 204, headers));
+        assert_eq!(result.status_code(), 204);
+        assert!(result.delete_marker());
+        assert_eq!(
+            result.version_id(),
+            Some("3HL4kqtJlcpXroDTDmJ+rmSpXd3dIbrHY")
+        );
+
+        let result = DeleteObjectResult::from(&ResponseData::new(
+            bytes::Bytes::new(),
+            204,
+            HeaderMap::new(),
+        ));
+        assert!(!result.delete_marker());
+        assert_eq!(result.version_id(), None);
+    }
+
+    #[test]
+    fn response_headers_ignore_case_and_keep_repeats() {
+        use crate::request::ResponseData;
+        use http::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("etag", HeaderValue::from_static("\"abc\""));
+        headers.append("x-amz-meta-tag", HeaderValue::from_static("a"));
+        headers.append("x-amz-meta-tag", HeaderValue::from_static("b"));
+        let response_data = ResponseData::new(bytes::Bytes::new(), 200, headers);
+        assert_eq!(response_data.header("ETag"), Some("\"abc\""));
+        assert_eq!(response_data.header("etag"), Some("\"abc\""));
+        assert_eq!(
+            response_data
+                .headers()
+                .get_all("X-Amz-Meta-Tag")
+                .iter()
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[cfg(feature = "with-tokio")]
+    #[tokio::test]
+    async fn stream_parts_are_cut_at_part_size() {
+        use bytes::Bytes;
+
+        let chunks = [
+            Bytes::from_static(b"0123456789"),
+            Bytes::from_static(b"abc"),
+            Bytes::from_static(b"defg"),
+        ];
+        let mut stream = futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+        let mut leftover = Bytes::new();
+        let mut parts = Vec::new();
+        while let Some(part) = super::next_stream_part(&mut stream, &mut leftover, 4)
+            .await
+            .unwrap()
+        {
+            parts.push(part);
+        }
+        assert_eq!(
+            parts,
+            ["0123", "4567", "89ab", "cdef", "g"].map(|part| Bytes::from_static(part.as_bytes()))
+        );
+    }
+
+    #[cfg(feature = "with-tokio")]
+    #[tokio::test]
+    async fn stream_head_read_to_threshold_is_recut_at_part_size() {
+        use bytes::Bytes;
+        use futures::StreamExt;
+
+        let chunks = [
+            Bytes::from_static(b"0123456789"),
+            Bytes::from_static(b"abc"),
+        ];
+        let mut stream = futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+        let mut leftover = Bytes::new();
+        let head = super::next_stream_part(&mut stream, &mut leftover, 6)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(head, Bytes::from_static(b"012345"));
+
+        let mut stream = futures::stream::iter([Ok(head), Ok(leftover)]).chain(stream);
+        let mut leftover = Bytes::new();
+        let mut parts = Vec::new();
+        while let Some(part) = super::next_stream_part(&mut stream, &mut leftover, 4)
+            .await
+            .unwrap()
+        {
+            parts.push(part);
+        }
+        assert_eq!(
+            parts,
+            ["0123", "4567", "89ab", "c"].map(|part| Bytes::from_static(part.as_bytes()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "md5")]
     fn test_etag_large_file() {
         let path = "test_etag";
         std::fs::remove_file(path).unwrap_or(());
@@ -455,6 +1059,7 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "md5")]
     fn test_etag_small_file() {
         let path = "test_etag";
         std::fs::remove_file(path).unwrap_or(());
@@ -470,6 +1075,62 @@ mod test {
         assert_eq!(etag, "8122ef1c2b2331f7986349560248cf56");
     }
 
+    #[test]
+    #[cfg(feature = "md5")]
+    fn test_etag_custom_part_size() {
+        use super::{etag_for_bytes, etag_for_reader, PartSize};
+
+        const MIB: usize = 1024 * 1024;
+        let test = object(10_000_000);
+        let etag = etag_for_reader(&mut Cursor::new(&test), 8 * MIB, PartSize::mib(8)).unwrap();
+        assert_eq!(etag, "e438487f09f09c042b2de097765e5ac2-2");
+        assert_eq!(etag_for_bytes(&test, 8 * MIB, PartSize::mib(8)), etag);
+
+        let single = etag_for_bytes(&test, 16 * MIB, PartSize::mib(16));
+        assert_eq!(single, format!("{:x}", md5::compute(&test)));
+        assert_eq!(
+            etag_for_reader(&mut Cursor::new(&test), 16 * MIB, PartSize::mib(16)).unwrap(),
+            single
+        );
+
+        // Ends on a full part, no empty part is counted
+        let test = object(10 * MIB as u32);
+        let etag = etag_for_reader(&mut Cursor::new(&test), 5 * MIB, PartSize::mib(5)).unwrap();
+        assert!(etag.ends_with("-2"));
+        assert_eq!(etag_for_bytes(&test, 5 * MIB, PartSize::mib(5)), etag);
+    }
+
+    #[test]
+    #[cfg(feature = "md5")]
+    fn test_etag_single_part_uploads() {
+        use super::{etag_for_bytes, etag_for_reader, PartSize};
+
+        const MIB: usize = 1024 * 1024;
+        let one_part_etag =
+            |content: &[u8]| format!("{:x}-1", md5::compute(md5::compute(content).0));
+
+        // Exactly one part size is uploaded as a single part multipart upload
+        let test = object(8 * MIB as u32);
+        let etag = etag_for_reader(&mut Cursor::new(&test), 8 * MIB, PartSize::mib(8)).unwrap();
+        assert_eq!(etag, one_part_etag(&test));
+        assert_eq!(etag_for_bytes(&test, 8 * MIB, PartSize::mib(8)), etag);
+
+        // Exactly the threshold, below the part size
+        let test = object(5 * MIB as u32);
+        let etag = etag_for_reader(&mut Cursor::new(&test), 5 * MIB, PartSize::mib(8)).unwrap();
+        assert_eq!(etag, one_part_etag(&test));
+        assert_eq!(etag_for_bytes(&test, 5 * MIB, PartSize::mib(8)), etag);
+
+        // One byte short of the threshold is a single PUT
+        let short = &test[..5 * MIB - 1];
+        let plain = format!("{:x}", md5::compute(short));
+        assert_eq!(
+            etag_for_reader(&mut Cursor::new(short), 5 * MIB, PartSize::mib(8)).unwrap(),
+            plain
+        );
+        assert_eq!(etag_for_bytes(short, 5 * MIB, PartSize::mib(8)), plain);
+    }
+
     #[test]
     fn test_read_chunk_all_zero() {
         let blob = vec![0u8; 10_000_000];
@@ -491,4 +1152,118 @@ mod test {
         let result = super::read_chunk(&mut blob).unwrap();
         assert_eq!(result.len(), 1_611_392);
     }
+
+    #[test]
+    fn test_check_stream_size() {
+        use super::check_stream_size;
+        use crate::error::S3Error;
+
+        assert!(check_stream_size(None, 10, true).is_ok());
+        assert!(check_stream_size(Some(10), 5, false).is_ok());
+        assert!(check_stream_size(Some(10), 10, true).is_ok());
+        assert!(matches!(
+            check_stream_size(Some(10), 5, true),
+            Err(S3Error::StreamSizeMismatch {
+                expected: 10,
+                read: 5
+            })
+        ));
+        assert!(matches!(
+            check_stream_size(Some(10), 11, false),
+            Err(S3Error::StreamSizeMismatch {
+                expected: 10,
+                read: 11
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_chunk_with_checksum() {
+        use crate::utils::Crc32c;
+
+        let blob = (0..10_000_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut reader = Cursor::new(blob.clone());
+
+        let (chunk, crc) = super::read_chunk_with_checksum(&mut reader).unwrap();
+        assert_eq!(chunk, blob[..crate::bucket::CHUNK_SIZE]);
+        assert_eq!(crc.finalize(), Crc32c::checksum(&chunk).finalize());
+
+        let (chunk, crc) = super::read_chunk_with_checksum(&mut reader).unwrap();
+        assert_eq!(chunk, blob[crate::bucket::CHUNK_SIZE..]);
+        assert_eq!(crc.finalize(), Crc32c::checksum(&chunk).finalize());
+    }
+
+    #[test]
+    fn test_read_sized() {
+        let blob = vec![1u8; 1000];
+        let mut blob = Cursor::new(blob);
+
+        let result = super::read_sized(&mut blob, 600).unwrap();
+        assert_eq!(result.len(), 600);
+
+        let result = super::read_sized(&mut blob, 600).unwrap();
+        assert_eq!(result.len(), 400);
+    }
+
+    #[test]
+    fn test_transient_status() {
+        for status_code in [429, 500, 502, 503, 504] {
+            assert!(super::is_transient_status(status_code));
+        }
+        for status_code in [200, 400, 403, 404, 412, 501] {
+            assert!(!super::is_transient_status(status_code));
+        }
+    }
+
+    #[cfg(feature = "with-tokio")]
+    #[tokio::test]
+    async fn retries_wait_at_most_until_the_deadline() {
+        use crate::error::S3Error;
+        use std::time::{Duration, Instant};
+
+        let started = Instant::now();
+        super::backoff(3, Some(Duration::from_millis(10))).await;
+        let attempts = std::cell::Cell::new(0);
+        let result: Result<(), S3Error> = crate::retry!(
+            {
+                attempts.set(attempts.get() + 1);
+                Err(S3Error::HttpFail)
+            },
+            |_, _| {},
+            2,
+            Some(Duration::ZERO)
+        );
+        assert!(matches!(result, Err(S3Error::HttpFail)));
+        assert_eq!(attempts.get(), 3);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(feature = "with-tokio")]
+    #[tokio::test]
+    async fn parts_in_flight_upload_while_the_next_part_is_read() {
+        use crate::error::S3Error;
+        use futures::stream::FuturesUnordered;
+
+        let (sent, received) = tokio::sync::oneshot::channel();
+        let mut in_flight = FuturesUnordered::new();
+        in_flight.push(async move {
+            sent.send(()).unwrap();
+            1
+        });
+        // The read only finishes once the part in flight has run
+        let read = async {
+            received.await.map_err(std::io::Error::other)?;
+            Ok::<_, S3Error>(b"next".to_vec())
+        };
+        let mut completed = Vec::new();
+        let chunk = super::read_while_uploading(read, &mut in_flight, |part| {
+            completed.push(part);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(chunk, b"next");
+        assert_eq!(completed, [1]);
+        assert!(in_flight.is_empty());
+    }
 }
@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+
+/// Part buffers kept around between the parts of streaming uploads, so that long running
+/// uploaders don't allocate a fresh buffer for every part. Clones share the same buffers,
+/// one pool can serve several buckets.
+#[derive(Clone, Debug, Default)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Keep at most `max_buffers` idle buffers, typically the number of parts in flight
+    pub fn new(max_buffers: usize) -> Self {
+        BufferPool {
+            buffers: Arc::new(Mutex::new(Vec::with_capacity(max_buffers))),
+            max_buffers,
+        }
+    }
+
+    /// An empty buffer with room for at least `capacity` bytes
+    pub fn take(&self, capacity: usize) -> Vec<u8> {
+        let buffer = match self.buffers.lock() {
+            Ok(mut buffers) => buffers.pop(),
+            Err(_) => None,
+        };
+        match buffer {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.reserve(capacity);
+                buffer
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Return a buffer to the pool, it is dropped if the pool is full
+    pub fn give(&self, buffer: Vec<u8>) {
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < self.max_buffers {
+                buffers.push(buffer);
+            }
+        }
+    }
+
+    /// Number of idle buffers
+    pub fn len(&self) -> usize {
+        self.buffers
+            .lock()
+            .map(|buffers| buffers.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BufferPool;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::new(1);
+        let mut buffer = pool.take(1024);
+        buffer.extend_from_slice(b"part");
+        let ptr = buffer.as_ptr();
+        pool.give(buffer);
+        pool.give(Vec::new());
+        assert_eq!(pool.len(), 1);
+
+        let buffer = pool.take(16);
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 1024);
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert!(pool.is_empty());
+    }
+}
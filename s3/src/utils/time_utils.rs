@@ -1,6 +1,64 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
 use time::OffsetDateTime;
 
+/// Source of the current time for request signing, presigned URL and POST policy expiry and
+/// credential refresh, set per bucket with `Bucket::with_clock`.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// The system clock, used unless a bucket is given another one
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        now_utc()
+    }
+}
+
+/// A clock that only moves when told to, for testing expiry and rotation deterministically.
+/// Clones share their time, so the clock can be moved after handing it to a bucket.
+///
+/// ```
+/// use s3::utils::{Clock, MockClock};
+/// use time::macros::datetime;
+///
+/// let clock = MockClock::new(datetime!(2024-03-01 10:00 UTC));
+/// let bucket_clock = clock.clone();
+/// clock.advance(time::Duration::hours(1));
+/// assert_eq!(bucket_clock.now(), datetime!(2024-03-01 11:00 UTC));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<OffsetDateTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: OffsetDateTime) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: OffsetDateTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    pub fn advance(&self, duration: time::Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 fn real_time() -> Result<u64, SystemTimeError> {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
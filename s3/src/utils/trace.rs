@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use time::OffsetDateTime;
+
+use crate::command::HttpMethod;
+
+/// Metadata of one request sent by a bucket, kept by its `RequestTrace`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestRecord {
+    pub method: HttpMethod,
+    pub path: String,
+    pub status: u16,
+    /// `x-amz-request-id` of the response
+    pub request_id: Option<String>,
+    /// `x-amz-id-2` of the response
+    pub extended_request_id: Option<String>,
+    pub at: OffsetDateTime,
+}
+
+/// Ring buffer of the last requests a bucket sent, set with `Bucket::with_request_trace`.
+///
+/// Clones of the bucket record into the same trace, once full the oldest record is dropped.
+/// Retried requests are counted apart from the records, which only hold final responses.
+///
+/// ```
+/// use s3::utils::RequestTrace;
+///
+/// let trace = RequestTrace::new(32);
+/// assert!(trace.records().is_empty());
+/// ```
+#[derive(Clone)]
+pub struct RequestTrace {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<RequestRecord>>>,
+    retries: Arc<AtomicU64>,
+}
+
+impl RequestTrace {
+    pub fn new(capacity: usize) -> Self {
+        RequestTrace {
+            capacity,
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            retries: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Recorded requests, oldest first
+    pub fn records(&self) -> Vec<RequestRecord> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Requests retried since the trace was created or cleared
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.retries.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record(&self, record: RequestRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<RequestRecord>> {
+        // A poisoned trace only means a panic while pushing, the records are still usable
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for RequestTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTrace")
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RequestRecord, RequestTrace};
+    use crate::command::HttpMethod;
+    use time::OffsetDateTime;
+
+    fn record(path: &str) -> RequestRecord {
+        RequestRecord {
+            method: HttpMethod::Get,
+            path: path.to_string(),
+            status: 200,
+            request_id: Some("4442587FB7D0A2F9".to_string()),
+            extended_request_id: None,
+            at: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn oldest_records_are_dropped() {
+        let trace = RequestTrace::new(2);
+        let shared = trace.clone();
+        trace.record(record("/a"));
+        trace.record(record("/b"));
+        shared.record(record("/c"));
+
+        let paths: Vec<_> = trace.records().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, vec!["/b", "/c"]);
+
+        trace.clear();
+        assert!(shared.records().is_empty());
+
+        let disabled = RequestTrace::new(0);
+        disabled.record(record("/a"));
+        assert!(disabled.records().is_empty());
+    }
+
+    #[test]
+    fn retries_are_counted_across_clones() {
+        let trace = RequestTrace::new(2);
+        trace.record_retry();
+        trace.clone().record_retry();
+        assert_eq!(trace.retries(), 2);
+        assert!(trace.records().is_empty());
+
+        trace.clear();
+        assert_eq!(trace.retries(), 0);
+    }
+}
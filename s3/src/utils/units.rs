@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use crate::error::S3Error;
+
+const MIB: usize = 1024 * 1024;
+
+/// Lifetime of a presigned URL, at most a week.
+///
+/// The `const` constructors panic on out of range values, which fails compilation when used in
+/// a `const` context, `Expiry::new` checks values only known at runtime.
+///
+/// ```
+/// use s3::utils::Expiry;
+///
+/// const EXPIRY: Expiry = Expiry::hours(2);
+/// assert_eq!(EXPIRY.as_secs(), 7200);
+/// assert!(Expiry::new(604_801).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Expiry(u32);
+
+impl Expiry {
+    pub const MAX: Expiry = Expiry(7 * 24 * 60 * 60);
+
+    pub fn new(secs: u32) -> Result<Self, S3Error> {
+        if secs > Self::MAX.0 {
+            return Err(S3Error::MaxExpiry(secs));
+        }
+        Ok(Expiry(secs))
+    }
+
+    pub const fn seconds(secs: u32) -> Self {
+        assert!(
+            secs <= Self::MAX.0,
+            "presigned URLs expire after a week at most"
+        );
+        Expiry(secs)
+    }
+
+    pub const fn minutes(minutes: u32) -> Self {
+        assert!(
+            minutes <= Self::MAX.0 / 60,
+            "presigned URLs expire after a week at most"
+        );
+        Expiry(minutes * 60)
+    }
+
+    pub const fn hours(hours: u32) -> Self {
+        assert!(
+            hours <= Self::MAX.0 / 3600,
+            "presigned URLs expire after a week at most"
+        );
+        Expiry(hours * 3600)
+    }
+
+    pub const fn days(days: u32) -> Self {
+        assert!(days <= 7, "presigned URLs expire after a week at most");
+        Expiry(days * 86400)
+    }
+
+    pub const fn as_secs(&self) -> u32 {
+        self.0
+    }
+}
+
+impl TryFrom<u32> for Expiry {
+    type Error = S3Error;
+
+    fn try_from(secs: u32) -> Result<Self, Self::Error> {
+        Expiry::new(secs)
+    }
+}
+
+/// Size of a multipart upload part, between 5 MiB and 5 GiB
+///
+/// ```
+/// use s3::utils::PartSize;
+///
+/// const PART_SIZE: PartSize = PartSize::mib(16);
+/// assert_eq!(PART_SIZE.bytes(), 16 * 1024 * 1024);
+/// assert!(PartSize::new(1024).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartSize(usize);
+
+impl PartSize {
+    /// Smallest part S3 accepts for all but the last part of an upload
+    pub const MIN: PartSize = PartSize(5 * MIB);
+    /// Saturates at `usize::MAX` on 32 bit targets
+    pub const MAX: PartSize = PartSize(MIB.saturating_mul(5 * 1024));
+
+    pub fn new(bytes: usize) -> Result<Self, S3Error> {
+        if !(Self::MIN.0..=Self::MAX.0).contains(&bytes) {
+            return Err(S3Error::InvalidPartSize(bytes));
+        }
+        Ok(PartSize(bytes))
+    }
+
+    pub const fn mib(mib: usize) -> Self {
+        assert!(mib >= 5, "parts are at least 5 MiB");
+        assert!(mib <= 5 * 1024, "parts are at most 5 GiB");
+        PartSize(mib.saturating_mul(MIB))
+    }
+
+    pub const fn gib(gib: usize) -> Self {
+        assert!(gib >= 1, "parts are at least 5 MiB");
+        assert!(gib <= 5, "parts are at most 5 GiB");
+        PartSize(gib.saturating_mul(1024 * MIB))
+    }
+
+    pub const fn bytes(&self) -> usize {
+        self.0
+    }
+}
+
+impl TryFrom<usize> for PartSize {
+    type Error = S3Error;
+
+    fn try_from(bytes: usize) -> Result<Self, Self::Error> {
+        PartSize::new(bytes)
+    }
+}
+
+/// Number of entries returned per page by listing operations, between 1 and 1000
+///
+/// ```
+/// use s3::utils::PageSize;
+///
+/// const PAGE_SIZE: PageSize = PageSize::of(100);
+/// assert_eq!(PAGE_SIZE.get(), 100);
+/// assert!(PageSize::new(0).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PageSize(u16);
+
+impl PageSize {
+    pub const MAX: PageSize = PageSize(1000);
+
+    pub fn new(size: usize) -> Result<Self, S3Error> {
+        if size == 0 || size > Self::MAX.0 as usize {
+            return Err(S3Error::InvalidPageSize(size));
+        }
+        Ok(PageSize(size as u16))
+    }
+
+    pub const fn of(size: u16) -> Self {
+        assert!(size >= 1, "pages hold at least one entry");
+        assert!(size <= Self::MAX.0, "pages hold at most 1000 entries");
+        PageSize(size)
+    }
+
+    pub const fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<usize> for PageSize {
+    type Error = S3Error;
+
+    fn try_from(size: usize) -> Result<Self, Self::Error> {
+        PageSize::new(size)
+    }
+}
+
+impl std::fmt::Display for PageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Time a request or an idle connection is given, longer than zero
+///
+/// ```
+/// use std::time::Duration;
+/// use s3::utils::Timeout;
+///
+/// const TIMEOUT: Timeout = Timeout::seconds(30);
+/// assert_eq!(TIMEOUT.as_duration(), Duration::from_secs(30));
+/// assert!(Timeout::new(Duration::ZERO).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timeout(Duration);
+
+impl Timeout {
+    pub fn new(duration: Duration) -> Result<Self, S3Error> {
+        if duration.is_zero() {
+            return Err(S3Error::InvalidTimeout(duration));
+        }
+        Ok(Timeout(duration))
+    }
+
+    pub const fn millis(millis: u64) -> Self {
+        assert!(millis > 0, "timeouts are longer than zero");
+        Timeout(Duration::from_millis(millis))
+    }
+
+    pub const fn seconds(secs: u64) -> Self {
+        assert!(secs > 0, "timeouts are longer than zero");
+        Timeout(Duration::from_secs(secs))
+    }
+
+    pub const fn minutes(minutes: u64) -> Self {
+        assert!(minutes > 0, "timeouts are longer than zero");
+        Timeout(Duration::from_secs(minutes.saturating_mul(60)))
+    }
+
+    pub const fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl TryFrom<Duration> for Timeout {
+    type Error = S3Error;
+
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        Timeout::new(duration)
+    }
+}
+
+impl From<Timeout> for Duration {
+    fn from(timeout: Timeout) -> Self {
+        timeout.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Expiry, PageSize, PartSize, Timeout};
+    use crate::error::S3Error;
+    use std::time::Duration;
+
+    #[test]
+    fn unit_bounds() {
+        assert_eq!(Expiry::days(7), Expiry::MAX);
+        assert_eq!(Expiry::minutes(5).as_secs(), 300);
+        assert!(matches!(
+            Expiry::try_from(604_801),
+            Err(S3Error::MaxExpiry(604_801))
+        ));
+
+        assert_eq!(PartSize::gib(5), PartSize::MAX);
+        assert_eq!(PartSize::new(5 * 1024 * 1024).unwrap(), PartSize::MIN);
+        assert!(PartSize::new(PartSize::MAX.bytes() + 1).is_err());
+
+        assert_eq!(PageSize::new(1000).unwrap(), PageSize::MAX);
+        assert!(PageSize::try_from(1001).is_err());
+
+        assert_eq!(Timeout::minutes(1), Timeout::seconds(60));
+        assert_eq!(
+            Timeout::try_from(Duration::from_millis(1500)).unwrap(),
+            Timeout::millis(1500)
+        );
+        assert!(matches!(
+            Timeout::new(Duration::ZERO),
+            Err(S3Error::InvalidTimeout(_))
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn const_constructors_reject_out_of_range() {
+        let _ = PartSize::mib(4);
+    }
+}
@@ -0,0 +1,765 @@
+//! Row writers that stream CSV or JSON Lines straight into objects.
+//!
+//! [`CsvObjectWriter`] and [`JsonLinesObjectWriter`] buffer rows in memory, optionally gzip
+//! compressed, and upload the buffer as a multipart upload part whenever it reaches
+//! `CHUNK_SIZE`, so at most one part is held in memory. An object is finished and the next row
+//! starts a new one once the object holds [`WriterOptions::max_object_bytes`] or has been open
+//! for [`WriterOptions::max_object_age`]. Keys for the objects come from [`ObjectKeys`], either
+//! a closure given the sequence number of the object or a [`KeyPartitioner`] laying objects out
+//! by time, as in `logs/2024/05/17/13/<uuid>.jsonl`.
+//!
+//! [`RotatingUploader`] does the same for bytes that are already encoded.
+//!
+//! Objects smaller than a part are sent with a single `PUT`. Age is measured with the bucket's
+//! clock, see `Bucket::with_clock`, and only checked when a row is written or
+//! `rotate_if_due` is called. With a [`KeyPartitioner`] an object is also finished once the
+//! clock moves into the next partition, a row always lands in the partition of the time it is
+//! written.
+
+use std::io::Write;
+use std::time::Duration;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::bucket::{Bucket, CHUNK_SIZE};
+use crate::error::S3Error;
+use crate::serde_types::Part;
+use crate::utils::error_from_response_data;
+
+const GZIP_CONTENT_TYPE: &str = "application/gzip";
+
+/// Compression and rotation of the objects written by a writer
+#[derive(Clone, Debug, Default)]
+pub struct WriterOptions {
+    pub gzip: bool,
+    /// Finish an object once this many bytes, counted before compression, were written to it
+    pub max_object_bytes: Option<usize>,
+    /// Finish an object once it has been open this long
+    pub max_object_age: Option<Duration>,
+}
+
+impl WriterOptions {
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    pub fn max_object_bytes(mut self, max_object_bytes: usize) -> Self {
+        self.max_object_bytes = Some(max_object_bytes);
+        self
+    }
+
+    pub fn max_object_age(mut self, max_object_age: Duration) -> Self {
+        self.max_object_age = Some(max_object_age);
+        self
+    }
+
+    fn is_due(&self, written: usize, opened_at: OffsetDateTime, now: OffsetDateTime) -> bool {
+        self.max_object_bytes.is_some_and(|max| written >= max)
+            || self
+                .max_object_age
+                .is_some_and(|max| now - opened_at >= max)
+    }
+}
+
+enum Buffer {
+    Plain(Vec<u8>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl Buffer {
+    fn new(gzip: bool) -> Self {
+        if gzip {
+            Buffer::Gzip(GzEncoder::new(Vec::new(), Compression::default()))
+        } else {
+            Buffer::Plain(Vec::new())
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), S3Error> {
+        match self {
+            Buffer::Plain(buffer) => buffer.extend_from_slice(data),
+            Buffer::Gzip(encoder) => encoder.write_all(data)?,
+        }
+        Ok(())
+    }
+
+    /// Bytes ready to be uploaded
+    fn len(&self) -> usize {
+        match self {
+            Buffer::Plain(buffer) => buffer.len(),
+            Buffer::Gzip(encoder) => encoder.get_ref().len(),
+        }
+    }
+
+    /// Take the bytes ready to be uploaded, a gzip stream carries on where the taken bytes end
+    fn take(&mut self) -> Vec<u8> {
+        match self {
+            Buffer::Plain(buffer) => std::mem::take(buffer),
+            Buffer::Gzip(encoder) => std::mem::take(encoder.get_mut()),
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, S3Error> {
+        match self {
+            Buffer::Plain(buffer) => Ok(buffer),
+            Buffer::Gzip(encoder) => Ok(encoder.finish()?),
+        }
+    }
+}
+
+/// Names the objects written by a [`RotatingUploader`]
+pub trait ObjectKeys {
+    /// Key of the object with this sequence number, opened at `opened_at`
+    fn next_key(&mut self, sequence: u64, opened_at: OffsetDateTime) -> String;
+
+    /// Partition of keys opened at `at`, an open object is finished once the partition of the
+    /// current time is another one. `None` for keys that aren't partitioned by time.
+    fn partition_at(&self, _at: OffsetDateTime) -> Option<String> {
+        None
+    }
+}
+
+impl<F> ObjectKeys for F
+where
+    F: FnMut(u64) -> String,
+{
+    fn next_key(&mut self, sequence: u64, _opened_at: OffsetDateTime) -> String {
+        self(sequence)
+    }
+}
+
+/// Time partition a [`KeyPartitioner`] groups objects by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+}
+
+/// Keys of the form `prefix/yyyy/mm/dd/hh/<uuid><suffix>`, down to the configured
+/// [`Granularity`] of the UTC time the object is opened at.
+///
+/// ```
+/// use s3::writers::{Granularity, KeyPartitioner};
+/// use time::macros::datetime;
+///
+/// let keys = KeyPartitioner::new("logs").granularity(Granularity::Day).suffix(".jsonl");
+/// assert_eq!(keys.partition(datetime!(2024-05-17 13:45 UTC)), "logs/2024/05/17");
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyPartitioner {
+    prefix: String,
+    granularity: Granularity,
+    suffix: String,
+}
+
+impl KeyPartitioner {
+    /// Hourly partitions under `prefix`, a trailing `/` is optional
+    pub fn new(prefix: impl Into<String>) -> Self {
+        let mut prefix = prefix.into();
+        if prefix.ends_with('/') {
+            prefix.pop();
+        }
+        KeyPartitioner {
+            prefix,
+            granularity: Granularity::Hour,
+            suffix: String::new(),
+        }
+    }
+
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Appended to every key, e.g. `.csv.gz`
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// Partition objects opened at `at` go to, without a trailing `/`
+    pub fn partition(&self, at: OffsetDateTime) -> String {
+        let at = at.to_offset(UtcOffset::UTC);
+        let mut partition = self.prefix.clone();
+        let parts = [
+            (Granularity::Year, format!("{:04}", at.year())),
+            (Granularity::Month, format!("{:02}", u8::from(at.month()))),
+            (Granularity::Day, format!("{:02}", at.day())),
+            (Granularity::Hour, format!("{:02}", at.hour())),
+            (Granularity::Minute, format!("{:02}", at.minute())),
+        ];
+        for (granularity, part) in parts {
+            if !partition.is_empty() {
+                partition.push('/');
+            }
+            partition.push_str(&part);
+            if granularity == self.granularity {
+                break;
+            }
+        }
+        partition
+    }
+
+    /// Key for a new object opened at `at`
+    pub fn key(&self, at: OffsetDateTime) -> String {
+        format!(
+            "{}/{}{}",
+            self.partition(at),
+            uuid::Uuid::new_v4(),
+            self.suffix
+        )
+    }
+}
+
+impl ObjectKeys for KeyPartitioner {
+    fn next_key(&mut self, _sequence: u64, opened_at: OffsetDateTime) -> String {
+        self.key(opened_at)
+    }
+
+    fn partition_at(&self, at: OffsetDateTime) -> Option<String> {
+        Some(self.partition(at))
+    }
+}
+
+struct OpenObject {
+    key: String,
+    buffer: Buffer,
+    upload: Option<(String, Vec<Part>)>,
+    written: usize,
+    opened_at: OffsetDateTime,
+    partition: Option<String>,
+}
+
+/// Uploads bytes to a sequence of objects, switching to a new object once
+/// [`WriterOptions`] thresholds pass. The CSV and JSON Lines writers are built on it.
+///
+/// # Example:
+///
+/// ```rust,no_run
+/// use s3::bucket::Bucket;
+/// use s3::creds::Credentials;
+/// use s3::writers::{Granularity, KeyPartitioner, RotatingUploader, WriterOptions};
+/// use anyhow::Result;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+///
+/// let bucket_name = "rust-s3-test";
+/// let region = "us-east-1".parse()?;
+/// let credentials = Credentials::default()?;
+/// let bucket = Bucket::new(bucket_name, region, credentials)?;
+///
+/// let keys = KeyPartitioner::new("logs").granularity(Granularity::Hour).suffix(".log.gz");
+/// let options = WriterOptions::default().gzip(true).max_object_bytes(64 * 1024 * 1024);
+/// let mut uploader = RotatingUploader::new(&bucket, keys, "text/plain", options);
+/// uploader.write(b"service started\n").await?;
+/// let keys = uploader.finish().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RotatingUploader {
+    bucket: Bucket,
+    keys: Box<dyn ObjectKeys + Send>,
+    content_type: String,
+    options: WriterOptions,
+    sequence: u64,
+    current: Option<OpenObject>,
+    finished: Vec<String>,
+}
+
+impl RotatingUploader {
+    /// Objects are uploaded with `content_type`, or `application/gzip` when compressed
+    pub fn new(
+        bucket: &Bucket,
+        keys: impl ObjectKeys + Send + 'static,
+        content_type: &str,
+        options: WriterOptions,
+    ) -> Self {
+        RotatingUploader {
+            bucket: bucket.clone(),
+            keys: Box::new(keys),
+            content_type: if options.gzip {
+                GZIP_CONTENT_TYPE.to_string()
+            } else {
+                content_type.to_string()
+            },
+            options,
+            sequence: 0,
+            current: None,
+            finished: Vec::new(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn open(&mut self) {
+        let opened_at = self.bucket.now();
+        let key = self.keys.next_key(self.sequence, opened_at);
+        self.sequence += 1;
+        self.current = Some(OpenObject {
+            key,
+            buffer: Buffer::new(self.options.gzip),
+            upload: None,
+            written: 0,
+            opened_at,
+            partition: self.keys.partition_at(opened_at),
+        });
+    }
+
+    /// Whether the clock moved out of the partition of the current object
+    fn partition_changed(&self, now: OffsetDateTime) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|object| object.partition != self.keys.partition_at(now))
+    }
+
+    /// Finish the current object if the clock moved into another partition, so that the next
+    /// write opens an object in the partition it belongs to
+    #[maybe_async::maybe_async]
+    async fn rotate_if_partition_changed(&mut self) -> Result<(), S3Error> {
+        if self.partition_changed(self.bucket.now()) {
+            self.rotate().await?;
+        }
+        Ok(())
+    }
+
+    /// Write `data` to the current object, opening one if needed
+    #[maybe_async::maybe_async]
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), S3Error> {
+        self.rotate_if_partition_changed().await?;
+        if self.current.is_none() {
+            self.open();
+        }
+        let object = self.current.as_mut().expect("object is open");
+        object.buffer.write(data)?;
+        object.written += data.len();
+        if object.buffer.len() >= CHUNK_SIZE {
+            let chunk = object.buffer.take();
+            self.upload_part(chunk).await?;
+        }
+        self.rotate_if_due().await
+    }
+
+    #[maybe_async::maybe_async]
+    async fn upload_part(&mut self, chunk: Vec<u8>) -> Result<(), S3Error> {
+        let object = self.current.as_mut().expect("object is open");
+        if object.upload.is_none() {
+            let msg = self
+                .bucket
+                .initiate_multipart_upload(&object.key, &self.content_type)
+                .await?;
+            object.upload = Some((msg.upload_id, Vec::new()));
+        }
+        let (upload_id, parts) = object.upload.as_mut().expect("upload is initiated");
+        let result = put_part(
+            &self.bucket,
+            chunk,
+            &object.key,
+            parts.len() as u32 + 1,
+            upload_id,
+            &self.content_type,
+        )
+        .await;
+        match result {
+            Ok(part) => {
+                parts.push(part);
+                Ok(())
+            }
+            Err(error) => {
+                let object = self.current.take().expect("object is open");
+                let (upload_id, _) = object.upload.expect("upload is initiated");
+                let _ = self.bucket.abort_upload(&object.key, &upload_id).await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Finish the current object if it is due for rotation or the clock moved into another
+    /// partition, for rotating by time while nothing is written
+    #[maybe_async::maybe_async]
+    pub async fn rotate_if_due(&mut self) -> Result<(), S3Error> {
+        let now = self.bucket.now();
+        let due = self.partition_changed(now)
+            || self
+                .current
+                .as_ref()
+                .is_some_and(|object| self.options.is_due(object.written, object.opened_at, now));
+        if due {
+            self.rotate().await?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current object, the next write starts a new one
+    #[maybe_async::maybe_async]
+    pub async fn rotate(&mut self) -> Result<(), S3Error> {
+        let Some(object) = self.current.take() else {
+            return Ok(());
+        };
+        let content = object.buffer.finish()?;
+        match object.upload {
+            None => {
+                let response_data = self
+                    .bucket
+                    .put_object_with_content_type(&object.key, &content, &self.content_type)
+                    .await?;
+                if response_data.status_code() >= 300 {
+                    return Err(error_from_response_data(response_data)?);
+                }
+            }
+            Some((upload_id, mut parts)) => {
+                let result = if content.is_empty() {
+                    Ok(None)
+                } else {
+                    put_part(
+                        &self.bucket,
+                        content,
+                        &object.key,
+                        parts.len() as u32 + 1,
+                        &upload_id,
+                        &self.content_type,
+                    )
+                    .await
+                    .map(Some)
+                };
+                let result = match result {
+                    Ok(part) => {
+                        parts.extend(part);
+                        self.bucket
+                            .complete_multipart_upload(&object.key, &upload_id, parts)
+                            .await
+                    }
+                    Err(error) => Err(error),
+                };
+                if let Err(error) = result {
+                    let _ = self.bucket.abort_upload(&object.key, &upload_id).await;
+                    return Err(error);
+                }
+            }
+        }
+        self.finished.push(object.key);
+        Ok(())
+    }
+
+    /// Finish the current object and return the keys of all objects written
+    #[maybe_async::maybe_async]
+    pub async fn finish(mut self) -> Result<Vec<String>, S3Error> {
+        self.rotate().await?;
+        Ok(self.finished)
+    }
+}
+
+#[maybe_async::async_impl]
+async fn put_part(
+    bucket: &Bucket,
+    chunk: Vec<u8>,
+    key: &str,
+    part_number: u32,
+    upload_id: &str,
+    content_type: &str,
+) -> Result<Part, S3Error> {
+    bucket
+        .put_multipart_chunk(chunk, key, part_number, upload_id, content_type)
+        .await
+}
+
+#[maybe_async::sync_impl]
+fn put_part(
+    bucket: &Bucket,
+    chunk: Vec<u8>,
+    key: &str,
+    part_number: u32,
+    upload_id: &str,
+    content_type: &str,
+) -> Result<Part, S3Error> {
+    bucket.put_multipart_chunk(&chunk, key, part_number, upload_id, content_type)
+}
+
+/// Writes CSV rows to objects, repeating the header at the top of every object.
+///
+/// # Example:
+///
+/// ```rust,no_run
+/// use s3::bucket::Bucket;
+/// use s3::creds::Credentials;
+/// use s3::writers::{CsvObjectWriter, WriterOptions};
+/// use std::time::Duration;
+/// use anyhow::Result;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+///
+/// let bucket_name = "rust-s3-test";
+/// let region = "us-east-1".parse()?;
+/// let credentials = Credentials::default()?;
+/// let bucket = Bucket::new(bucket_name, region, credentials)?;
+///
+/// let options = WriterOptions::default()
+///     .gzip(true)
+///     .max_object_age(Duration::from_secs(300));
+/// let mut writer = CsvObjectWriter::new(&bucket, |n| format!("metrics/{:06}.csv.gz", n), options)
+///     .with_header(["host", "cpu"]);
+/// writer.write_record(["web-1", "0.42"]).await?;
+/// let keys = writer.finish().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CsvObjectWriter {
+    inner: RotatingUploader,
+    header: Option<String>,
+}
+
+impl CsvObjectWriter {
+    pub fn new(
+        bucket: &Bucket,
+        keys: impl ObjectKeys + Send + 'static,
+        options: WriterOptions,
+    ) -> Self {
+        CsvObjectWriter {
+            inner: RotatingUploader::new(bucket, keys, "text/csv", options),
+            header: None,
+        }
+    }
+
+    pub fn with_header<I, S>(mut self, header: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.header = Some(csv_line(header));
+        self
+    }
+
+    /// Write one row, fields are quoted where needed
+    #[maybe_async::maybe_async]
+    pub async fn write_record<I, S>(&mut self, record: I) -> Result<(), S3Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        // A new partition starts a new object, which needs the header
+        self.inner.rotate_if_partition_changed().await?;
+        if !self.inner.is_open() {
+            if let Some(header) = &self.header {
+                self.inner.open();
+                // The header doesn't count towards rotation, an object always gets a row
+                let object = self.inner.current.as_mut().expect("object is open");
+                object.buffer.write(header.as_bytes())?;
+            }
+        }
+        self.inner.write(csv_line(record).as_bytes()).await
+    }
+
+    /// Finish the current object if it is due for rotation, for rotating by age while no rows
+    /// come in
+    #[maybe_async::maybe_async]
+    pub async fn rotate_if_due(&mut self) -> Result<(), S3Error> {
+        self.inner.rotate_if_due().await
+    }
+
+    /// Finish the current object, the next row starts a new one
+    #[maybe_async::maybe_async]
+    pub async fn rotate(&mut self) -> Result<(), S3Error> {
+        self.inner.rotate().await
+    }
+
+    /// Finish the current object and return the keys of all objects written
+    #[maybe_async::maybe_async]
+    pub async fn finish(self) -> Result<Vec<String>, S3Error> {
+        self.inner.finish().await
+    }
+}
+
+/// Writes values as JSON Lines, one serialized value per line.
+///
+/// Rotation and compression work as for [`CsvObjectWriter`].
+pub struct JsonLinesObjectWriter {
+    inner: RotatingUploader,
+}
+
+impl JsonLinesObjectWriter {
+    pub fn new(
+        bucket: &Bucket,
+        keys: impl ObjectKeys + Send + 'static,
+        options: WriterOptions,
+    ) -> Self {
+        JsonLinesObjectWriter {
+            inner: RotatingUploader::new(bucket, keys, "application/x-ndjson", options),
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn write<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S3Error> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+        self.inner.write(&line).await
+    }
+
+    /// Finish the current object if it is due for rotation, for rotating by age while no values
+    /// come in
+    #[maybe_async::maybe_async]
+    pub async fn rotate_if_due(&mut self) -> Result<(), S3Error> {
+        self.inner.rotate_if_due().await
+    }
+
+    /// Finish the current object, the next value starts a new one
+    #[maybe_async::maybe_async]
+    pub async fn rotate(&mut self) -> Result<(), S3Error> {
+        self.inner.rotate().await
+    }
+
+    /// Finish the current object and return the keys of all objects written
+    #[maybe_async::maybe_async]
+    pub async fn finish(self) -> Result<Vec<String>, S3Error> {
+        self.inner.finish().await
+    }
+}
+
+// RFC 4180, fields holding a separator, quote or line break are quoted with `""` escaping a quote
+fn csv_line<I, S>(fields: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut line = String::new();
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        let field = field.as_ref();
+        if field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(field);
+        }
+    }
+    line.push_str("\r\n");
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(
+            csv_line(["plain", "with,comma", "say \"hi\"", "two\nlines", ""]),
+            "plain,\"with,comma\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n"
+        );
+    }
+
+    #[test]
+    fn gzip_buffer_survives_taking_parts() {
+        let mut buffer = Buffer::new(true);
+        let mut uploaded = Vec::new();
+        for i in 0..10_000 {
+            buffer
+                .write(format!("{{\"row\":{}}}\n", i).as_bytes())
+                .unwrap();
+            if buffer.len() > 1024 {
+                uploaded.extend(buffer.take());
+            }
+        }
+        uploaded.extend(buffer.finish().unwrap());
+
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(uploaded.as_slice())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content.lines().count(), 10_000);
+        assert_eq!(content.lines().last(), Some("{\"row\":9999}"));
+    }
+
+    #[test]
+    fn rotation_is_due() {
+        let opened_at = OffsetDateTime::UNIX_EPOCH;
+        let options = WriterOptions::default()
+            .max_object_bytes(100)
+            .max_object_age(Duration::from_secs(60));
+        assert!(!options.is_due(99, opened_at, opened_at + Duration::from_secs(59)));
+        assert!(options.is_due(100, opened_at, opened_at));
+        assert!(options.is_due(0, opened_at, opened_at + Duration::from_secs(60)));
+        assert!(!WriterOptions::default().is_due(usize::MAX, opened_at, opened_at));
+    }
+
+    #[test]
+    fn keys_are_partitioned_by_time() {
+        use time::macros::datetime;
+
+        let at = datetime!(2024-05-17 13:45:10 +02:00);
+        let keys = KeyPartitioner::new("logs/").suffix(".jsonl");
+        assert_eq!(keys.partition(at), "logs/2024/05/17/11");
+        assert_eq!(
+            keys.clone().granularity(Granularity::Month).partition(at),
+            "logs/2024/05"
+        );
+        assert_eq!(
+            KeyPartitioner::new("")
+                .granularity(Granularity::Minute)
+                .partition(at),
+            "2024/05/17/11/45"
+        );
+
+        let mut keys = keys;
+        let key = keys.next_key(0, at);
+        let id = key
+            .strip_prefix("logs/2024/05/17/11/")
+            .and_then(|key| key.strip_suffix(".jsonl"))
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+        assert_ne!(keys.next_key(1, at), key);
+    }
+
+    #[test]
+    fn objects_rotate_into_the_next_partition() {
+        use crate::creds::Credentials;
+        use crate::utils::MockClock;
+        use time::macros::datetime;
+
+        let clock = MockClock::new(datetime!(2024-05-17 13:59:58 UTC));
+        let bucket = Bucket::new(
+            "rust-s3",
+            "us-east-1".parse().unwrap(),
+            Credentials::anonymous().unwrap(),
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+        let keys = KeyPartitioner::new("logs").granularity(Granularity::Hour);
+        let mut uploader =
+            RotatingUploader::new(&bucket, keys, "text/plain", WriterOptions::default());
+
+        uploader.open();
+        let key = &uploader.current.as_ref().unwrap().key;
+        assert!(key.starts_with("logs/2024/05/17/13/"));
+        assert!(!uploader.partition_changed(bucket.now()));
+
+        clock.advance(time::Duration::seconds(1));
+        assert!(!uploader.partition_changed(bucket.now()));
+        clock.advance(time::Duration::seconds(1));
+        assert!(uploader.partition_changed(bucket.now()));
+
+        // Keys without partitions only rotate by size and age
+        let mut uploader = RotatingUploader::new(
+            &bucket,
+            |n| format!("logs/{}", n),
+            "text/plain",
+            WriterOptions::default(),
+        );
+        uploader.open();
+        clock.advance(time::Duration::days(1));
+        assert!(!uploader.partition_changed(bucket.now()));
+    }
+}